@@ -1,23 +1,96 @@
-use std::str::FromStr;
-
 use jtmb_reflect_db::SchemaInspector;
-use sqlx::{ConnectOptions, Connection};
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    sqlx::any::install_default_drivers();
-    let mut args = std::env::args();
-    let _ = args.next();
-    let database_url_env = std::env::var("DATABASE_URL").ok();
-    let database_url = args.next();
-    let database_url = database_url
-        .or(database_url_env)
-        .ok_or("Expected database url")?;
-
-    let any_opts = sqlx::any::AnyConnectOptions::from_str(&database_url)?;
-    let mut c = any_opts.connect().await?;
-    let mut tx = c.begin().await?;
-
-    let tables = tx.get_metadata().await?;
+use jtmb_reflect_db::render::{self, Dialect};
+use jtmb_reflect_db::schema::Database;
+use sqlx::Connection;
+
+const USAGE: &str = "usage: jtmb_reflect_db_bootstrap [url] [json | table | ddl <sqlite|postgres|mysql> | dot | mermaid]\n\
+the url defaults to DATABASE_URL, the output to json";
+
+enum Output {
+    Json,
+    Table,
+    Ddl(Dialect),
+    Dot,
+    Mermaid,
+}
+
+fn parse_output(
+    format: Option<&str>,
+    mut args: impl Iterator<Item = String>,
+) -> Result<Output, anyhow::Error> {
+    Ok(match format {
+        None | Some("json") => Output::Json,
+        Some("table") => Output::Table,
+        Some("ddl") => Output::Ddl(
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("ddl needs a dialect\n{USAGE}"))?
+                .parse()?,
+        ),
+        Some("dot") => Output::Dot,
+        Some("mermaid") => Output::Mermaid,
+        Some(other) => anyhow::bail!("unknown output {other}\n{USAGE}"),
+    })
+}
+
+fn parse_args() -> Result<(String, Output), anyhow::Error> {
+    let mut args = std::env::args().skip(1).peekable();
+    // a url has a scheme, anything else is the output
+    let url = match args.next_if(|a| a.contains(':')) {
+        Some(url) => url,
+        None => std::env::var("DATABASE_URL")
+            .map_err(|_| anyhow::anyhow!("no url and no DATABASE_URL\n{USAGE}"))?,
+    };
+    let format = args.next();
+    Ok((url, parse_output(format.as_deref(), args)?))
+}
+
+// the inspectors of the backend the url names, Any has no postgres one
+async fn inspect(url: &str) -> Result<Database, anyhow::Error> {
+    let scheme = url.split(':').next().unwrap_or_default();
+    match scheme {
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            let mut c = sqlx::SqliteConnection::connect(url).await?;
+            let mut tx = c.begin().await?;
+            tx.get_metadata().await
+        }
+        #[cfg(feature = "postgresql")]
+        "postgres" | "postgresql" => {
+            let mut c = sqlx::PgConnection::connect(url).await?;
+            let mut tx = c.begin().await?;
+            tx.get_metadata().await
+        }
+        #[cfg(feature = "mysql")]
+        "mysql" | "mariadb" => {
+            let mut c = sqlx::MySqlConnection::connect(url).await?;
+            let mut tx = c.begin().await?;
+            tx.get_metadata().await
+        }
+        other => anyhow::bail!("no inspector for {other} in this build\n{USAGE}"),
+    }
+}
+
+#[cfg(feature = "serialize")]
+fn json(database: &Database) -> Result<String, anyhow::Error> {
+    Ok(serde_json::to_string_pretty(database)? + "\n")
+}
+
+#[cfg(not(feature = "serialize"))]
+fn json(_: &Database) -> Result<String, anyhow::Error> {
+    anyhow::bail!("json output needs the serialize feature")
+}
 
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let (url, output) = parse_args()?;
+    let database = inspect(&url).await?;
+    let text = match output {
+        Output::Json => json(&database)?,
+        Output::Table => render::table(&database),
+        Output::Ddl(dialect) => render::ddl(&database, dialect),
+        Output::Dot => render::dot(&database),
+        Output::Mermaid => render::mermaid(&database),
+    };
+    print!("{text}");
     Ok(())
 }
@@ -1,6 +1,6 @@
 use sqlx::Database;
 
-use super::sqlite::{PRAGMA_TABLE_INFO_SQL, PragmaTableInfo};
+use super::sqlite::{PragmaTableInfo, TableMetadata};
 
 // #[macro_use]
 use crate::meta_query_sqlite_pragma_table_info;
@@ -8,12 +8,29 @@ use crate::meta_query_sqlite_pragma_table_info;
 #[derive(Debug)]
 pub enum AnyColumnInfo {
     Sqlite(PragmaTableInfo),
+    #[cfg(feature = "mysql")]
+    MySql(super::mysql::ColumnMetadata),
+}
+
+#[derive(Debug)]
+pub enum AnyTableInfo {
+    Sqlite(TableMetadata),
+    #[cfg(feature = "mysql")]
+    MySql(super::mysql::TableMetadata),
+}
+
+// postgres reports arrays the Any driver can not decode, it has to go through its own transaction
+fn unsupported(backend: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "the Any inspector reads sqlite and mysql, inspect {backend} through its own transaction"
+    )
 }
 
 #[async_trait::async_trait]
 impl<'t> crate::SchemaInspector<sqlx::any::Any> for sqlx::Transaction<'t, sqlx::any::Any> {
     type ColumnInfo = AnyColumnInfo;
-    type TableInfo = ();
+    type TableInfo = AnyTableInfo;
+    type InformationSchema = crate::schema::Database;
     async fn get_columns(
         &mut self,
         for_table_name: &str,
@@ -22,31 +39,50 @@ impl<'t> crate::SchemaInspector<sqlx::any::Any> for sqlx::Transaction<'t, sqlx::
             sqlx::sqlite::Sqlite::NAME => {
                 let q = meta_query_sqlite_pragma_table_info!(for_table_name);
                 let t = q.fetch_all(&mut **self).await?;
-                let t = t.into_iter().map(|t| AnyColumnInfo::Sqlite(t)).collect();
-                dbg!(&t);
-                Ok(t)
+                Ok(t.into_iter().map(AnyColumnInfo::Sqlite).collect())
             }
-            _ => {
-                todo!("not implemented yet")
+            #[cfg(feature = "mysql")]
+            sqlx::mysql::MySql::NAME => {
+                let t =
+                    super::mysql::get_tables::<sqlx::any::Any>(&mut **self, Some(for_table_name))
+                        .await?
+                        .pop()
+                        .ok_or_else(|| anyhow::anyhow!("no table {for_table_name}"))?;
+                Ok(t.columns.into_iter().map(AnyColumnInfo::MySql).collect())
             }
+            other => Err(unsupported(other)),
         }
     }
     async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
         match self.backend_name() {
             sqlx::sqlite::Sqlite::NAME => {
-                let meta_query_pragma_table_info = crate::query_get_all_table_metadata!();
-                let t = meta_query_pragma_table_info.fetch_all(&mut **self).await?;
+                let t = super::sqlite::get_tables::<sqlx::any::Any>(&mut **self).await?;
+                Ok(t.into_iter().map(AnyTableInfo::Sqlite).collect())
+            }
+            #[cfg(feature = "mysql")]
+            sqlx::mysql::MySql::NAME => {
+                let t = super::mysql::get_tables::<sqlx::any::Any>(&mut **self, None).await?;
+                Ok(t.into_iter().map(AnyTableInfo::MySql).collect())
+            }
+            other => Err(unsupported(other)),
+        }
+    }
+    async fn get_metadata(&mut self) -> Result<Self::InformationSchema, anyhow::Error> {
+        match self.backend_name() {
+            sqlx::sqlite::Sqlite::NAME => {
+                super::sqlite::get_metadata::<sqlx::any::Any>(&mut **self).await
             }
-            _ => {
-                todo!("not implemented yet")
+            #[cfg(feature = "mysql")]
+            sqlx::mysql::MySql::NAME => {
+                super::mysql::get_metadata::<sqlx::any::Any>(&mut **self).await
             }
+            other => Err(unsupported(other)),
         }
-        Ok(vec![])
     }
 }
 
 /// Asks Database if Table exists with Column and Datatype. Only valid with the any driver for sqlite
-pub async fn sqlite_meta_fn_does_table_exist_with_column_and_datatype<Executor>(
+pub async fn sqlite_meta_fn_does_table_exist_with_column_and_datatype<'executor, Executor>(
     executor: Executor,
     table_name: String,
     column_name: String,
@@ -54,9 +90,7 @@ pub async fn sqlite_meta_fn_does_table_exist_with_column_and_datatype<Executor>(
     include_hidden: bool,
 ) -> Result<bool, anyhow::Error>
 where
-    for<'executor> Executor: sqlx::any::AnyExecutor<'executor, Database = sqlx::any::Any>,
-    for<'row> (i32,): sqlx::FromRow<'row, <sqlx::any::Any as sqlx::Database>::Row>,
-    for<'row> i32: sqlx::FromRow<'row, <sqlx::any::Any as sqlx::Database>::Row>,
+    Executor: sqlx::any::AnyExecutor<'executor>,
 {
     super::sqlite::meta_fn_get_columns_for_table_with_datatype::<Executor, sqlx::any::Any>(
         executor,
@@ -64,7 +98,9 @@ where
         column_name,
         r#type,
         include_hidden,
+        1,
+        0,
     )
     .await
-    .map(|t| t != 0)
+    .map(|t| !t.is_empty())
 }
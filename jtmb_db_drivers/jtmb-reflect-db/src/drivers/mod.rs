@@ -1,2 +1,6 @@
 pub mod any;
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "postgresql")]
+pub mod postgres;
 pub mod sqlite;
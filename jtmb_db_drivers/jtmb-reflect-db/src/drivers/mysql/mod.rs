@@ -0,0 +1,687 @@
+// mysql and mariadb introspection, all from information_schema. the queries only read plain
+// strings and integers so the Any inspector can share them when the backend is mysql.
+// every column is cast to CHAR or SIGNED since information_schema mixes collations and unsigned
+// types between versions, and mysql 8 returns the column names upper case unless aliased
+
+use sqlx::prelude::FromRow;
+
+pub const SQL_GET_DATABASE: &str = "SELECT CAST(DATABASE() AS CHAR)";
+
+/// base tables of schema ?, or only table ? when it is not null
+pub const SQL_GET_TABLES: &str = r#"SELECT CAST(TABLE_SCHEMA AS CHAR) AS `schema`, CAST(TABLE_NAME AS CHAR) AS `name`,
+CAST(ENGINE AS CHAR) AS `engine`, CAST(TABLE_COLLATION AS CHAR) AS `collation`, CAST(TABLE_COMMENT AS CHAR) AS `comment`
+FROM information_schema.TABLES
+WHERE TABLE_TYPE = 'BASE TABLE' AND TABLE_SCHEMA = ? AND (? IS NULL OR TABLE_NAME = ?)
+ORDER BY TABLE_NAME"#;
+
+pub const SQL_GET_COLUMNS: &str = r#"SELECT CAST(ORDINAL_POSITION AS SIGNED) AS `position`, CAST(COLUMN_NAME AS CHAR) AS `name`,
+CAST(COLUMN_TYPE AS CHAR) AS `column_type`, CAST(IS_NULLABLE = 'YES' AS SIGNED) AS `nullable`,
+CAST(COLUMN_DEFAULT AS CHAR) AS `default_value`, CAST(EXTRA AS CHAR) AS `extra`,
+CAST(NULLIF(GENERATION_EXPRESSION, '') AS CHAR) AS `generated`, CAST(COLUMN_KEY = 'PRI' AS SIGNED) AS `is_pk`,
+CAST(COLUMN_COMMENT AS CHAR) AS `comment`
+FROM information_schema.COLUMNS
+WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?
+ORDER BY ORDINAL_POSITION"#;
+
+pub const SQL_GET_INDEXES: &str = r#"SELECT CAST(INDEX_NAME AS CHAR) AS `name`, CAST(NON_UNIQUE AS SIGNED) AS `non_unique`,
+CAST(COLUMN_NAME AS CHAR) AS `column_name`, CAST(SUB_PART AS SIGNED) AS `sub_part`, CAST(INDEX_TYPE AS CHAR) AS `method`
+FROM information_schema.STATISTICS
+WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?
+ORDER BY INDEX_NAME, SEQ_IN_INDEX"#;
+
+pub const SQL_GET_FOREIGN_KEYS: &str = r#"SELECT CAST(k.CONSTRAINT_NAME AS CHAR) AS `name`, CAST(k.COLUMN_NAME AS CHAR) AS `column_name`,
+CAST(k.REFERENCED_TABLE_SCHEMA AS CHAR) AS `referenced_schema`, CAST(k.REFERENCED_TABLE_NAME AS CHAR) AS `referenced_table`,
+CAST(k.REFERENCED_COLUMN_NAME AS CHAR) AS `referenced_column`,
+CAST(r.UPDATE_RULE AS CHAR) AS `on_update`, CAST(r.DELETE_RULE AS CHAR) AS `on_delete`
+FROM information_schema.KEY_COLUMN_USAGE k
+JOIN information_schema.REFERENTIAL_CONSTRAINTS r ON r.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA AND r.CONSTRAINT_NAME = k.CONSTRAINT_NAME AND r.TABLE_NAME = k.TABLE_NAME
+WHERE k.TABLE_SCHEMA = ? AND k.TABLE_NAME = ? AND k.REFERENCED_TABLE_NAME IS NOT NULL
+ORDER BY k.CONSTRAINT_NAME, k.ORDINAL_POSITION"#;
+
+/// mysql 8.0.16 and mariadb 10.2 onwards
+pub const SQL_GET_CHECKS: &str = r#"SELECT CAST(c.CONSTRAINT_NAME AS CHAR) AS `name`, CAST(c.CHECK_CLAUSE AS CHAR) AS `definition`
+FROM information_schema.TABLE_CONSTRAINTS t
+JOIN information_schema.CHECK_CONSTRAINTS c ON c.CONSTRAINT_SCHEMA = t.CONSTRAINT_SCHEMA AND c.CONSTRAINT_NAME = t.CONSTRAINT_NAME
+WHERE t.TABLE_SCHEMA = ? AND t.TABLE_NAME = ? AND t.CONSTRAINT_TYPE = 'CHECK'
+ORDER BY c.CONSTRAINT_NAME"#;
+
+#[derive(FromRow, Debug)]
+pub struct MySqlTableRow {
+    pub schema: String,
+    pub name: String,
+    pub engine: Option<String>,
+    pub collation: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct MySqlColumnRow {
+    pub position: i64,
+    pub name: String,
+    pub column_type: String,
+    pub nullable: i64,
+    pub default_value: Option<String>,
+    pub extra: Option<String>,
+    pub generated: Option<String>,
+    pub is_pk: i64,
+    pub comment: Option<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct MySqlIndexRow {
+    pub name: String,
+    pub non_unique: i64,
+    pub column_name: Option<String>,
+    pub sub_part: Option<i64>,
+    pub method: String,
+}
+
+#[derive(FromRow, Debug)]
+pub struct MySqlForeignKeyRow {
+    pub name: String,
+    pub column_name: String,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_column: String,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(FromRow, Debug)]
+pub struct MySqlCheckRow {
+    pub name: String,
+    pub definition: String,
+}
+
+/// COLUMN_TYPE read into what it means, display widths and zerofill dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MySqlType {
+    /// tinyint(1), which is what BOOL and BOOLEAN are stored as
+    Bool,
+    /// tinyint 1, smallint 2, mediumint 3, int 4, bigint 8 bytes
+    Integer {
+        bytes: u8,
+        unsigned: bool,
+    },
+    Decimal {
+        precision: u32,
+        scale: u32,
+        unsigned: bool,
+    },
+    Float,
+    Double,
+    Bit(u32),
+    Char(u32),
+    Varchar(u32),
+    /// tinytext, text, mediumtext or longtext, by name
+    Text(String),
+    Binary(u32),
+    Varbinary(u32),
+    /// tinyblob, blob, mediumblob or longblob, by name
+    Blob(String),
+    Enum(Vec<String>),
+    Set(Vec<String>),
+    Date,
+    Time,
+    DateTime,
+    Timestamp,
+    Year,
+    Json,
+    /// anything else, spatial types and newer additions, as mysql wrote it
+    Other(String),
+}
+
+// 'a','it''s' into a, it's
+fn quoted_list(args: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut chars = args.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' if chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    value.push('\'');
+                }
+                '\'' => break,
+                '\\' => value.extend(chars.next()),
+                c => value.push(c),
+            }
+        }
+        values.push(value);
+    }
+    values
+}
+
+impl MySqlType {
+    pub fn from_column_type(column_type: &str) -> Self {
+        let trimmed = column_type.trim();
+        let (name, args, rest) = match (trimmed.find('('), trimmed.rfind(')')) {
+            (Some(open), Some(close)) if open < close => (
+                &trimmed[..open],
+                &trimmed[open + 1..close],
+                &trimmed[close + 1..],
+            ),
+            _ => trimmed
+                .split_once(' ')
+                .map_or((trimmed, "", ""), |(name, rest)| (name, "", rest)),
+        };
+        let name = name.trim().to_lowercase();
+        let unsigned = rest
+            .split_whitespace()
+            .any(|w| w.eq_ignore_ascii_case("unsigned"));
+        let numbers: Vec<u32> = args
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        let n = |i: usize, default: u32| numbers.get(i).copied().unwrap_or(default);
+        let integer = |bytes| Self::Integer { bytes, unsigned };
+        match name.as_str() {
+            "tinyint" if numbers == [1] && !unsigned => Self::Bool,
+            "bool" | "boolean" => Self::Bool,
+            "tinyint" => integer(1),
+            "smallint" => integer(2),
+            "mediumint" => integer(3),
+            "int" | "integer" => integer(4),
+            "bigint" => integer(8),
+            "decimal" | "numeric" => Self::Decimal {
+                precision: n(0, 10),
+                scale: n(1, 0),
+                unsigned,
+            },
+            "float" => Self::Float,
+            "double" | "real" => Self::Double,
+            "bit" => Self::Bit(n(0, 1)),
+            "char" => Self::Char(n(0, 1)),
+            "varchar" => Self::Varchar(n(0, 0)),
+            "tinytext" | "text" | "mediumtext" | "longtext" => Self::Text(name),
+            "binary" => Self::Binary(n(0, 1)),
+            "varbinary" => Self::Varbinary(n(0, 0)),
+            "tinyblob" | "blob" | "mediumblob" | "longblob" => Self::Blob(name),
+            "enum" => Self::Enum(quoted_list(args)),
+            "set" => Self::Set(quoted_list(args)),
+            "date" => Self::Date,
+            "time" => Self::Time,
+            "datetime" => Self::DateTime,
+            "timestamp" => Self::Timestamp,
+            "year" => Self::Year,
+            "json" => Self::Json,
+            _ => Self::Other(column_type.to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ColumnMetadata {
+    pub schema: String,
+    pub table_name: String,
+    pub position: i32,
+    pub name: String,
+    /// COLUMN_TYPE as mysql reports it, e.g. int(10) unsigned or enum('a','b')
+    pub column_type: String,
+    pub r#type: MySqlType,
+    pub nullable: bool,
+    /// as information_schema has it. mariadb quotes string literals and writes NULL for no default,
+    /// mysql does neither
+    pub default_value: Option<String>,
+    pub auto_increment: bool,
+    /// EXTRA, e.g. on update CURRENT_TIMESTAMP or VIRTUAL GENERATED
+    pub extra: String,
+    /// the expression of a generated column
+    pub generated: Option<String>,
+    pub is_pk: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMetadata {
+    pub name: String,
+    pub unique: bool,
+    pub primary: bool,
+    /// BTREE, HASH, FULLTEXT or SPATIAL
+    pub method: String,
+    /// key columns in index order, None where the index is on an expression
+    pub columns: Vec<Option<String>>,
+    /// the prefix length of each key column, None where the whole column is indexed
+    pub prefix_lengths: Vec<Option<i64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyMetadata {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckMetadata {
+    pub name: String,
+    /// CHECK_CLAUSE, without the CHECK keyword
+    pub definition: String,
+}
+
+#[derive(Debug)]
+pub struct TableMetadata {
+    pub schema: String,
+    pub name: String,
+    pub engine: Option<String>,
+    pub collation: Option<String>,
+    pub columns: Vec<ColumnMetadata>,
+    /// primary key columns in key order, empty without a primary key
+    pub primary_key: Vec<String>,
+    pub indexes: Vec<IndexMetadata>,
+    pub foreign_keys: Vec<ForeignKeyMetadata>,
+    pub checks: Vec<CheckMetadata>,
+    pub comment: Option<String>,
+}
+
+/// reads everything information_schema has on one table. generic over the database so the Any
+/// inspector can share it when the backend is mysql
+pub async fn get_table_metadata<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+    table: MySqlTableRow,
+) -> Result<TableMetadata, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> MySqlColumnRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlIndexRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlForeignKeyRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlCheckRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let (schema, name) = (table.schema.as_str(), table.name.as_str());
+    let rows: Vec<MySqlColumnRow> = sqlx::query_as(SQL_GET_COLUMNS)
+        .bind(schema)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let columns = rows
+        .into_iter()
+        .map(|c| {
+            let extra = c.extra.unwrap_or_default();
+            ColumnMetadata {
+                schema: table.schema.clone(),
+                table_name: table.name.clone(),
+                position: c.position as i32,
+                name: c.name,
+                r#type: MySqlType::from_column_type(&c.column_type),
+                column_type: c.column_type,
+                nullable: c.nullable != 0,
+                default_value: c.default_value,
+                auto_increment: extra.to_lowercase().contains("auto_increment"),
+                extra,
+                generated: c.generated,
+                is_pk: c.is_pk != 0,
+                comment: c.comment.filter(|c| !c.is_empty()),
+            }
+        })
+        .collect();
+
+    let rows: Vec<MySqlIndexRow> = sqlx::query_as(SQL_GET_INDEXES)
+        .bind(schema)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut indexes: Vec<IndexMetadata> = vec![];
+    for row in rows {
+        match indexes.last_mut() {
+            Some(index) if index.name == row.name => {
+                index.columns.push(row.column_name);
+                index.prefix_lengths.push(row.sub_part);
+            }
+            _ => indexes.push(IndexMetadata {
+                unique: row.non_unique == 0,
+                primary: row.name == "PRIMARY",
+                name: row.name,
+                method: row.method,
+                columns: vec![row.column_name],
+                prefix_lengths: vec![row.sub_part],
+            }),
+        }
+    }
+    let primary_key = indexes
+        .iter()
+        .find(|i| i.primary)
+        .map(|i| i.columns.iter().flatten().cloned().collect())
+        .unwrap_or_default();
+
+    let rows: Vec<MySqlForeignKeyRow> = sqlx::query_as(SQL_GET_FOREIGN_KEYS)
+        .bind(schema)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut foreign_keys: Vec<ForeignKeyMetadata> = vec![];
+    for row in rows {
+        match foreign_keys.last_mut() {
+            Some(fk) if fk.name == row.name => {
+                fk.columns.push(row.column_name);
+                fk.referenced_columns.push(row.referenced_column);
+            }
+            _ => foreign_keys.push(ForeignKeyMetadata {
+                name: row.name,
+                columns: vec![row.column_name],
+                referenced_schema: row.referenced_schema,
+                referenced_table: row.referenced_table,
+                referenced_columns: vec![row.referenced_column],
+                on_update: row.on_update,
+                on_delete: row.on_delete,
+            }),
+        }
+    }
+
+    let checks: Vec<MySqlCheckRow> = sqlx::query_as(SQL_GET_CHECKS)
+        .bind(schema)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let checks = checks
+        .into_iter()
+        .map(|c| CheckMetadata {
+            name: c.name,
+            definition: c.definition,
+        })
+        .collect();
+
+    Ok(TableMetadata {
+        schema: table.schema,
+        name: table.name,
+        engine: table.engine,
+        collation: table.collation,
+        columns,
+        primary_key,
+        indexes,
+        foreign_keys,
+        checks,
+        comment: table.comment.filter(|c| !c.is_empty()),
+    })
+}
+
+/// the base tables of the current database, or only `table_name`
+pub async fn get_tables<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+    table_name: Option<&str>,
+) -> Result<Vec<TableMetadata>, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'q> Option<&'q str>: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> (Option<String>,): sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlTableRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlColumnRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlIndexRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlForeignKeyRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlCheckRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let (database,): (Option<String>,) = sqlx::query_as(SQL_GET_DATABASE)
+        .fetch_one(&mut *conn)
+        .await?;
+    let database =
+        database.ok_or_else(|| anyhow::anyhow!("no database selected, add one to the url"))?;
+    let rows: Vec<MySqlTableRow> = sqlx::query_as(SQL_GET_TABLES)
+        .bind(database.as_str())
+        .bind(table_name)
+        .bind(table_name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut tables = Vec::with_capacity(rows.len());
+    for table in rows {
+        tables.push(get_table_metadata::<Database>(&mut *conn, table).await?);
+    }
+    Ok(tables)
+}
+
+impl From<&MySqlType> for crate::schema::LogicalType {
+    fn from(t: &MySqlType) -> Self {
+        match t {
+            MySqlType::Bool => Self::Boolean,
+            MySqlType::Integer { bytes, unsigned } => Self::Integer {
+                bytes: *bytes,
+                unsigned: *unsigned,
+            },
+            MySqlType::Decimal {
+                precision, scale, ..
+            } => Self::Decimal {
+                precision: Some(*precision),
+                scale: Some(*scale),
+            },
+            MySqlType::Float => Self::Float,
+            MySqlType::Double => Self::Double,
+            MySqlType::Bit(1) => Self::Boolean,
+            MySqlType::Bit(n) => Self::Other(format!("bit({n})")),
+            MySqlType::Char(n) => Self::Char(Some(*n)),
+            MySqlType::Varchar(n) => Self::Varchar(Some(*n)),
+            MySqlType::Text(_) => Self::Text,
+            MySqlType::Binary(n) => Self::Binary(Some(*n)),
+            MySqlType::Varbinary(n) => Self::Varbinary(Some(*n)),
+            MySqlType::Blob(_) => Self::Blob,
+            MySqlType::Enum(values) => Self::Enum(values.clone()),
+            MySqlType::Set(values) => Self::Set(values.clone()),
+            MySqlType::Date => Self::Date,
+            MySqlType::Time => Self::Time {
+                with_time_zone: false,
+            },
+            MySqlType::DateTime => Self::Timestamp {
+                with_time_zone: false,
+            },
+            // stored as utc and read back in the session's zone
+            MySqlType::Timestamp => Self::Timestamp {
+                with_time_zone: true,
+            },
+            MySqlType::Year => Self::Other("year".to_string()),
+            MySqlType::Json => Self::Json,
+            MySqlType::Other(name) => Self::Other(name.clone()),
+        }
+    }
+}
+
+// the default as sql, the model's promise. mysql leaves string literals unquoted and marks
+// expressions by DEFAULT_GENERATED, mariadb quotes literals and spells a missing default NULL
+fn default_sql(c: &ColumnMetadata) -> Option<String> {
+    let default = c.default_value.as_deref()?;
+    let timestamp = default.to_uppercase().starts_with("CURRENT_TIMESTAMP");
+    let quoted = matches!(
+        c.r#type,
+        MySqlType::Char(_)
+            | MySqlType::Varchar(_)
+            | MySqlType::Text(_)
+            | MySqlType::Binary(_)
+            | MySqlType::Varbinary(_)
+            | MySqlType::Blob(_)
+            | MySqlType::Enum(_)
+            | MySqlType::Set(_)
+            | MySqlType::Date
+            | MySqlType::Time
+            | MySqlType::DateTime
+            | MySqlType::Timestamp
+            | MySqlType::Year
+    );
+    match default {
+        "NULL" => None,
+        _ if timestamp || default.starts_with('\'') => Some(default.to_string()),
+        _ if c.extra.contains("DEFAULT_GENERATED") => Some(format!("({default})")),
+        _ if quoted => Some(format!("'{}'", default.replace('\'', "''"))),
+        _ => Some(default.to_string()),
+    }
+}
+
+impl From<TableMetadata> for crate::schema::Table {
+    fn from(t: TableMetadata) -> Self {
+        let columns = t
+            .columns
+            .into_iter()
+            .map(|c| {
+                let default = default_sql(&c);
+                crate::schema::Column {
+                    r#type: (&c.r#type).into(),
+                    name: c.name,
+                    native_type: c.column_type,
+                    nullable: c.nullable,
+                    default,
+                    auto_increment: c.auto_increment,
+                    generated: c.generated,
+                    comment: c.comment,
+                }
+            })
+            .collect();
+        let indexes = t
+            .indexes
+            .into_iter()
+            .map(|i| crate::schema::Index {
+                name: i.name,
+                columns: i.columns,
+                unique: i.unique,
+                primary: i.primary,
+                // a UNIQUE constraint is just a unique index to mysql
+                constraint: i.primary,
+                partial: false,
+            })
+            .collect();
+        let foreign_keys = t
+            .foreign_keys
+            .into_iter()
+            .map(|fk| crate::schema::ForeignKey {
+                name: Some(fk.name),
+                columns: fk.columns,
+                referenced_schema: (fk.referenced_schema != t.schema)
+                    .then_some(fk.referenced_schema),
+                referenced_table: fk.referenced_table,
+                referenced_columns: fk.referenced_columns,
+                on_update: fk.on_update,
+                on_delete: fk.on_delete,
+            })
+            .collect();
+        let checks = t
+            .checks
+            .into_iter()
+            .map(|c| crate::schema::Check {
+                name: Some(c.name),
+                expression: c.definition,
+            })
+            .collect();
+        Self {
+            name: t.name,
+            columns,
+            primary_key: t.primary_key,
+            indexes,
+            foreign_keys,
+            checks,
+            comment: t.comment,
+        }
+    }
+}
+
+/// the current database as the one schema of the neutral model
+pub async fn get_metadata<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+) -> Result<crate::schema::Database, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'q> Option<&'q str>: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> (Option<String>,): sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlTableRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlColumnRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlIndexRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlForeignKeyRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> MySqlCheckRow: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let (database,): (Option<String>,) = sqlx::query_as(SQL_GET_DATABASE)
+        .fetch_one(&mut *conn)
+        .await?;
+    let tables = get_tables::<Database>(conn, None).await?;
+    Ok(crate::schema::Database {
+        backend: <sqlx::MySql as sqlx::Database>::NAME.to_string(),
+        schemas: vec![crate::schema::Schema {
+            name: database.unwrap_or_default(),
+            tables: tables.into_iter().map(Into::into).collect(),
+        }],
+    })
+}
+
+#[async_trait::async_trait]
+impl<'a> crate::SchemaInspector<sqlx::MySql> for sqlx::Transaction<'a, sqlx::MySql> {
+    type ColumnInfo = ColumnMetadata;
+    type TableInfo = TableMetadata;
+    type InformationSchema = crate::schema::Database;
+    async fn get_columns(
+        &mut self,
+        for_table_name: &str,
+    ) -> Result<Vec<Self::ColumnInfo>, anyhow::Error> {
+        let table = get_tables::<sqlx::MySql>(&mut **self, Some(for_table_name))
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("no table {for_table_name}"))?;
+        Ok(table.columns)
+    }
+    async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
+        get_tables::<sqlx::MySql>(&mut **self, None).await
+    }
+    async fn get_metadata(&mut self) -> Result<Self::InformationSchema, anyhow::Error> {
+        get_metadata::<sqlx::MySql>(&mut **self).await
+    }
+}
+
+#[test]
+fn mysql_column_types() {
+    use MySqlType::*;
+    assert_eq!(MySqlType::from_column_type("tinyint(1)"), Bool);
+    assert_eq!(
+        MySqlType::from_column_type("tinyint(1) unsigned"),
+        Integer {
+            bytes: 1,
+            unsigned: true
+        }
+    );
+    assert_eq!(
+        MySqlType::from_column_type("int(10) unsigned zerofill"),
+        Integer {
+            bytes: 4,
+            unsigned: true
+        }
+    );
+    assert_eq!(
+        MySqlType::from_column_type("bigint"),
+        Integer {
+            bytes: 8,
+            unsigned: false
+        }
+    );
+    assert_eq!(
+        MySqlType::from_column_type("decimal(12,2)"),
+        Decimal {
+            precision: 12,
+            scale: 2,
+            unsigned: false
+        }
+    );
+    assert_eq!(MySqlType::from_column_type("varchar(40)"), Varchar(40));
+    assert_eq!(
+        MySqlType::from_column_type("enum('asset','it''s','a,b')"),
+        Enum(vec!["asset".into(), "it's".into(), "a,b".into()])
+    );
+    assert_eq!(
+        MySqlType::from_column_type("set('r','w')"),
+        Set(vec!["r".into(), "w".into()])
+    );
+    assert_eq!(
+        MySqlType::from_column_type("longtext"),
+        Text("longtext".into())
+    );
+    assert_eq!(MySqlType::from_column_type("point"), Other("point".into()));
+}
@@ -0,0 +1,530 @@
+// postgres introspection. schemas, tables and the plain column facts come from information_schema,
+// types, constraints and indexes from pg_catalog since information_schema can not describe
+// array elements, enum labels, domains or expression and partial indexes
+
+use std::collections::HashMap;
+
+use sqlx::{Postgres, prelude::FromRow};
+
+/// every schema but the ones postgres keeps for itself
+pub const SQL_GET_SCHEMAS: &str = r#"SELECT "schema_name"::text FROM information_schema.schemata WHERE "schema_name" NOT IN ('pg_catalog', 'information_schema') AND "schema_name" NOT LIKE 'pg\_toast%' AND "schema_name" NOT LIKE 'pg\_temp\_%' ORDER BY "schema_name""#;
+
+/// base tables outside the system schemas, or only the one $1 names when it is not null.
+/// $1 is resolved like any table name in sql, through the search_path unless it is qualified
+pub const SQL_GET_TABLES: &str = r#"SELECT t."table_schema"::text AS "schema", t."table_name"::text AS "name", c.oid::int8 AS "oid", obj_description(c.oid, 'pg_class') AS "comment"
+FROM information_schema.tables t
+JOIN pg_catalog.pg_namespace n ON n.nspname = t."table_schema"
+JOIN pg_catalog.pg_class c ON c.relnamespace = n.oid AND c.relname = t."table_name"
+WHERE t."table_type" = 'BASE TABLE' AND t."table_schema" NOT IN ('pg_catalog', 'information_schema')
+AND ($1::text IS NULL OR c.oid = to_regclass($1))
+ORDER BY 1, 2"#;
+
+pub const SQL_GET_COLUMNS: &str = r#"SELECT c."ordinal_position"::int4 AS "position", c."column_name"::text AS "name",
+a.atttypid::int8 AS "type_oid", a.atttypmod AS "type_mod",
+c."is_nullable" = 'YES' AS "nullable", c."column_default"::text AS "default_value",
+CASE WHEN c."is_identity" = 'YES' THEN c."identity_generation"::text END AS "identity",
+c."generation_expression"::text AS "generated",
+col_description(a.attrelid, a.attnum) AS "comment"
+FROM information_schema.columns c
+JOIN pg_catalog.pg_attribute a ON a.attrelid = $1::oid AND a.attname = c."column_name"
+WHERE c."table_schema" = $2 AND c."table_name" = $3
+ORDER BY c."ordinal_position""#;
+
+pub const SQL_GET_TYPE: &str = r#"SELECT n.nspname::text AS "schema", format_type(t.oid, $2) AS "name",
+t.typtype::text AS "kind", t.typcategory::text AS "category", t.typelem::int8 AS "element",
+t.typbasetype::int8 AS "base", t.typtypmod AS "base_mod", t.typnotnull AS "not_null",
+ARRAY(SELECT e.enumlabel::text FROM pg_catalog.pg_enum e WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder) AS "labels",
+ARRAY(SELECT pg_get_constraintdef(k.oid) FROM pg_catalog.pg_constraint k WHERE k.contypid = t.oid ORDER BY k.conname) AS "checks"
+FROM pg_catalog.pg_type t JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace
+WHERE t.oid = $1::oid"#;
+
+pub const SQL_GET_CONSTRAINTS: &str = r#"SELECT k.conname::text AS "name", k.contype::text AS "kind",
+ARRAY(SELECT a.attname::text FROM unnest(k.conkey) WITH ORDINALITY u(attnum, ord) JOIN pg_catalog.pg_attribute a ON a.attrelid = k.conrelid AND a.attnum = u.attnum ORDER BY u.ord) AS "columns",
+pg_get_constraintdef(k.oid) AS "definition",
+rn.nspname::text AS "referenced_schema", r.relname::text AS "referenced_table",
+ARRAY(SELECT a.attname::text FROM unnest(k.confkey) WITH ORDINALITY u(attnum, ord) JOIN pg_catalog.pg_attribute a ON a.attrelid = k.confrelid AND a.attnum = u.attnum ORDER BY u.ord) AS "referenced_columns",
+k.confupdtype::text AS "on_update", k.confdeltype::text AS "on_delete"
+FROM pg_catalog.pg_constraint k
+LEFT JOIN pg_catalog.pg_class r ON r.oid = k.confrelid
+LEFT JOIN pg_catalog.pg_namespace rn ON rn.oid = r.relnamespace
+WHERE k.conrelid = $1::oid AND k.contype IN ('p', 'u', 'f', 'c', 'x')
+ORDER BY k.conname"#;
+
+pub const SQL_GET_INDEXES: &str = r#"SELECT c.relname::text AS "name", i.indisunique AS "unique", i.indisprimary AS "primary",
+i.indpred IS NOT NULL AS "partial", am.amname::text AS "method",
+ARRAY(SELECT a.attname::text FROM unnest(i.indkey::int2[]) WITH ORDINALITY u(attnum, ord) LEFT JOIN pg_catalog.pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = u.attnum WHERE u.ord <= i.indnkeyatts ORDER BY u.ord) AS "columns",
+(SELECT k.conname::text FROM pg_catalog.pg_constraint k WHERE k.conindid = i.indexrelid AND k.conrelid = i.indrelid AND k.contype IN ('p', 'u', 'x')) AS "constraint",
+pg_get_indexdef(i.indexrelid) AS "definition"
+FROM pg_catalog.pg_index i
+JOIN pg_catalog.pg_class c ON c.oid = i.indexrelid
+JOIN pg_catalog.pg_am am ON am.oid = c.relam
+WHERE i.indrelid = $1::oid
+ORDER BY c.relname"#;
+
+#[derive(FromRow, Debug)]
+pub struct PgTableRow {
+    pub schema: String,
+    pub name: String,
+    pub oid: i64,
+    pub comment: Option<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PgColumnRow {
+    pub position: i32,
+    pub name: String,
+    pub type_oid: i64,
+    pub type_mod: i32,
+    pub nullable: bool,
+    pub default_value: Option<String>,
+    pub identity: Option<String>,
+    pub generated: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PgTypeRow {
+    pub schema: String,
+    pub name: String,
+    pub kind: String,
+    pub category: String,
+    pub element: i64,
+    pub base: i64,
+    pub base_mod: i32,
+    pub not_null: bool,
+    pub labels: Vec<String>,
+    pub checks: Vec<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PgConstraintRow {
+    pub name: String,
+    pub kind: String,
+    pub columns: Vec<String>,
+    pub definition: String,
+    pub referenced_schema: Option<String>,
+    pub referenced_table: Option<String>,
+    pub referenced_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PgIndexRow {
+    pub name: String,
+    pub unique: bool,
+    pub primary: bool,
+    pub partial: bool,
+    pub method: String,
+    pub columns: Vec<Option<String>>,
+    pub constraint: Option<String>,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMetadata {
+    pub schema: String,
+    /// as format_type writes it, modifiers included, e.g. numeric(12,2) or character varying(40)[]
+    pub name: String,
+    pub kind: TypeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeKind {
+    Base,
+    Array(Box<TypeMetadata>),
+    /// the labels in sort order
+    Enum(Vec<String>),
+    Domain {
+        base: Box<TypeMetadata>,
+        not_null: bool,
+        checks: Vec<String>,
+    },
+    Composite,
+    Range,
+    Multirange,
+    Pseudo,
+}
+
+#[derive(Debug)]
+pub struct ColumnMetadata {
+    pub schema: String,
+    pub table_name: String,
+    pub position: i32,
+    pub name: String,
+    pub r#type: TypeMetadata,
+    pub nullable: bool,
+    pub default_value: Option<String>,
+    /// ALWAYS or BY DEFAULT for an identity column
+    pub identity: Option<String>,
+    /// the expression of a generated column
+    pub generated: Option<String>,
+    pub is_pk: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    PrimaryKey,
+    Unique,
+    Check,
+    Exclusion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintMetadata {
+    pub name: String,
+    pub kind: ConstraintKind,
+    pub columns: Vec<String>,
+    /// as pg_get_constraintdef writes it, e.g. CHECK ((amount > 0))
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyMetadata {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_update: String,
+    pub on_delete: String,
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMetadata {
+    pub name: String,
+    pub unique: bool,
+    pub primary: bool,
+    pub partial: bool,
+    /// btree, gin, gist ...
+    pub method: String,
+    /// key columns in index order, None where the index is on an expression
+    pub columns: Vec<Option<String>>,
+    /// the constraint the index was made for, None for CREATE INDEX
+    pub constraint: Option<String>,
+    /// as pg_get_indexdef writes it
+    pub definition: String,
+}
+
+#[derive(Debug)]
+pub struct TableMetadata {
+    pub schema: String,
+    pub name: String,
+    pub columns: Vec<ColumnMetadata>,
+    /// primary key columns in key order, empty without a primary key
+    pub primary_key: Vec<String>,
+    /// primary key, unique, check and exclusion constraints
+    pub constraints: Vec<ConstraintMetadata>,
+    pub foreign_keys: Vec<ForeignKeyMetadata>,
+    pub indexes: Vec<IndexMetadata>,
+    pub comment: Option<String>,
+}
+
+fn referential_action(code: &str) -> String {
+    match code {
+        "r" => "RESTRICT",
+        "c" => "CASCADE",
+        "n" => "SET NULL",
+        "d" => "SET DEFAULT",
+        _ => "NO ACTION",
+    }
+    .to_string()
+}
+
+/// resolves a type and whatever it is built on. types repeat across columns so they are cached
+/// by oid and modifier
+pub async fn get_type(
+    conn: &mut sqlx::PgConnection,
+    cache: &mut HashMap<(i64, i32), TypeMetadata>,
+    oid: i64,
+    type_mod: i32,
+) -> Result<TypeMetadata, anyhow::Error> {
+    if let Some(t) = cache.get(&(oid, type_mod)) {
+        return Ok(t.clone());
+    }
+    let row: PgTypeRow = sqlx::query_as(SQL_GET_TYPE)
+        .bind(oid)
+        .bind(type_mod)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no type with oid {oid}"))?;
+    let kind = match (row.kind.as_str(), row.category.as_str()) {
+        // the modifier of an array column belongs to its elements, varchar(40)[]
+        (_, "A") if row.element != 0 => TypeKind::Array(Box::new(
+            Box::pin(get_type(conn, cache, row.element, type_mod)).await?,
+        )),
+        ("e", _) => TypeKind::Enum(row.labels),
+        ("d", _) => TypeKind::Domain {
+            base: Box::new(Box::pin(get_type(conn, cache, row.base, row.base_mod)).await?),
+            not_null: row.not_null,
+            checks: row.checks,
+        },
+        ("c", _) => TypeKind::Composite,
+        ("r", _) => TypeKind::Range,
+        ("m", _) => TypeKind::Multirange,
+        ("p", _) => TypeKind::Pseudo,
+        _ => TypeKind::Base,
+    };
+    let t = TypeMetadata {
+        schema: row.schema,
+        name: row.name,
+        kind,
+    };
+    cache.insert((oid, type_mod), t.clone());
+    Ok(t)
+}
+
+pub async fn get_schemas(conn: &mut sqlx::PgConnection) -> Result<Vec<String>, anyhow::Error> {
+    sqlx::query_scalar(SQL_GET_SCHEMAS)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| e.into())
+}
+
+pub async fn get_table_metadata(
+    conn: &mut sqlx::PgConnection,
+    cache: &mut HashMap<(i64, i32), TypeMetadata>,
+    table: PgTableRow,
+) -> Result<TableMetadata, anyhow::Error> {
+    let constraints: Vec<PgConstraintRow> = sqlx::query_as(SQL_GET_CONSTRAINTS)
+        .bind(table.oid)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut primary_key = vec![];
+    let mut checks = vec![];
+    let mut foreign_keys = vec![];
+    for c in constraints {
+        let kind = match c.kind.as_str() {
+            "p" => ConstraintKind::PrimaryKey,
+            "u" => ConstraintKind::Unique,
+            "c" => ConstraintKind::Check,
+            "x" => ConstraintKind::Exclusion,
+            _ => {
+                foreign_keys.push(ForeignKeyMetadata {
+                    name: c.name,
+                    columns: c.columns,
+                    referenced_schema: c.referenced_schema.unwrap_or_default(),
+                    referenced_table: c.referenced_table.unwrap_or_default(),
+                    referenced_columns: c.referenced_columns,
+                    on_update: referential_action(&c.on_update),
+                    on_delete: referential_action(&c.on_delete),
+                    definition: c.definition,
+                });
+                continue;
+            }
+        };
+        if kind == ConstraintKind::PrimaryKey {
+            primary_key = c.columns.clone();
+        }
+        checks.push(ConstraintMetadata {
+            name: c.name,
+            kind,
+            columns: c.columns,
+            definition: c.definition,
+        });
+    }
+
+    let rows: Vec<PgColumnRow> = sqlx::query_as(SQL_GET_COLUMNS)
+        .bind(table.oid)
+        .bind(table.schema.as_str())
+        .bind(table.name.as_str())
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut columns = Vec::with_capacity(rows.len());
+    for c in rows {
+        columns.push(ColumnMetadata {
+            schema: table.schema.clone(),
+            table_name: table.name.clone(),
+            position: c.position,
+            r#type: get_type(conn, cache, c.type_oid, c.type_mod).await?,
+            is_pk: primary_key.contains(&c.name),
+            name: c.name,
+            nullable: c.nullable,
+            default_value: c.default_value,
+            identity: c.identity,
+            generated: c.generated,
+            comment: c.comment,
+        });
+    }
+
+    let indexes: Vec<PgIndexRow> = sqlx::query_as(SQL_GET_INDEXES)
+        .bind(table.oid)
+        .fetch_all(&mut *conn)
+        .await?;
+    let indexes = indexes
+        .into_iter()
+        .map(|i| IndexMetadata {
+            name: i.name,
+            unique: i.unique,
+            primary: i.primary,
+            partial: i.partial,
+            method: i.method,
+            columns: i.columns,
+            constraint: i.constraint,
+            definition: i.definition,
+        })
+        .collect();
+
+    Ok(TableMetadata {
+        schema: table.schema,
+        name: table.name,
+        columns,
+        primary_key,
+        constraints: checks,
+        foreign_keys,
+        indexes,
+        comment: table.comment,
+    })
+}
+
+/// the base tables of every schema, or only the one `table_name` resolves to
+pub async fn get_tables(
+    conn: &mut sqlx::PgConnection,
+    table_name: Option<&str>,
+) -> Result<Vec<TableMetadata>, anyhow::Error> {
+    let rows: Vec<PgTableRow> = sqlx::query_as(SQL_GET_TABLES)
+        .bind(table_name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut cache = HashMap::new();
+    let mut tables = Vec::with_capacity(rows.len());
+    for table in rows {
+        tables.push(get_table_metadata(conn, &mut cache, table).await?);
+    }
+    Ok(tables)
+}
+
+impl From<&TypeMetadata> for crate::schema::LogicalType {
+    fn from(t: &TypeMetadata) -> Self {
+        match &t.kind {
+            TypeKind::Base => Self::from_sql_name(&t.name),
+            TypeKind::Array(element) => Self::Array(Box::new(element.as_ref().into())),
+            TypeKind::Enum(labels) => Self::Enum(labels.clone()),
+            // the checks of a domain stay with the native type
+            TypeKind::Domain { base, .. } => base.as_ref().into(),
+            _ => Self::Other(t.name.clone()),
+        }
+    }
+}
+
+impl From<TableMetadata> for crate::schema::Table {
+    fn from(t: TableMetadata) -> Self {
+        let columns = t
+            .columns
+            .into_iter()
+            .map(|c| crate::schema::Column {
+                r#type: (&c.r#type).into(),
+                auto_increment: c.identity.is_some()
+                    || c.default_value
+                        .as_deref()
+                        .is_some_and(|d| d.starts_with("nextval(")),
+                name: c.name,
+                native_type: c.r#type.name,
+                nullable: c.nullable,
+                default: c.default_value,
+                generated: c.generated,
+                comment: c.comment,
+            })
+            .collect();
+        let indexes = t
+            .indexes
+            .into_iter()
+            .map(|i| crate::schema::Index {
+                name: i.name,
+                columns: i.columns,
+                unique: i.unique,
+                primary: i.primary,
+                constraint: i.constraint.is_some(),
+                partial: i.partial,
+            })
+            .collect();
+        let foreign_keys = t
+            .foreign_keys
+            .into_iter()
+            .map(|fk| crate::schema::ForeignKey {
+                name: Some(fk.name),
+                columns: fk.columns,
+                referenced_schema: (fk.referenced_schema != t.schema)
+                    .then_some(fk.referenced_schema),
+                referenced_table: fk.referenced_table,
+                referenced_columns: fk.referenced_columns,
+                on_update: fk.on_update,
+                on_delete: fk.on_delete,
+            })
+            .collect();
+        let checks = t
+            .constraints
+            .into_iter()
+            .filter(|c| c.kind == ConstraintKind::Check)
+            .map(|c| crate::schema::Check {
+                expression: c
+                    .definition
+                    .strip_prefix("CHECK ")
+                    .unwrap_or(&c.definition)
+                    .to_string(),
+                name: Some(c.name),
+            })
+            .collect();
+        Self {
+            name: t.name,
+            columns,
+            primary_key: t.primary_key,
+            indexes,
+            foreign_keys,
+            checks,
+            comment: t.comment,
+        }
+    }
+}
+
+/// every schema with its tables in the neutral model, empty schemas included
+pub async fn get_metadata(
+    conn: &mut sqlx::PgConnection,
+) -> Result<crate::schema::Database, anyhow::Error> {
+    let mut schemas: Vec<crate::schema::Schema> = get_schemas(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|name| crate::schema::Schema {
+            name,
+            tables: vec![],
+        })
+        .collect();
+    for table in get_tables(conn, None).await? {
+        match schemas.iter_mut().find(|s| s.name == table.schema) {
+            Some(schema) => schema.tables.push(table.into()),
+            None => schemas.push(crate::schema::Schema {
+                name: table.schema.clone(),
+                tables: vec![table.into()],
+            }),
+        }
+    }
+    Ok(crate::schema::Database {
+        backend: <Postgres as sqlx::Database>::NAME.to_string(),
+        schemas,
+    })
+}
+
+#[async_trait::async_trait]
+impl<'a> crate::SchemaInspector<Postgres> for sqlx::Transaction<'a, Postgres> {
+    type ColumnInfo = ColumnMetadata;
+    type TableInfo = TableMetadata;
+    type InformationSchema = crate::schema::Database;
+    async fn get_columns(
+        &mut self,
+        for_table_name: &str,
+    ) -> Result<Vec<Self::ColumnInfo>, anyhow::Error> {
+        let table = get_tables(&mut **self, Some(for_table_name))
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("no table {for_table_name}"))?;
+        Ok(table.columns)
+    }
+    async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
+        get_tables(&mut **self, None).await
+    }
+    async fn get_metadata(&mut self) -> Result<Self::InformationSchema, anyhow::Error> {
+        get_metadata(&mut **self).await
+    }
+}
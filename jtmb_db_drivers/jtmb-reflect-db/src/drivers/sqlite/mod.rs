@@ -1,10 +1,9 @@
 pub mod probe;
 
-use std::{collections::HashMap, fmt::format, i64};
+use std::collections::HashMap;
 
-use sqlx::{Connection, Database, Execute, Executor, Row, Transaction, prelude::FromRow};
+use sqlx::prelude::FromRow;
 
-use crate::SchemaInspector;
 /// Unlike other tools. this DOES NOT FILTER OUT INFORMATION SCHEMA
 pub const SQL_GET_ALL_TABLE_METADATA: &str =
     r#"SELECT "name","type",* FROM sqlite_master WHERE type='table'"#;
@@ -28,6 +27,16 @@ macro_rules! query_get_all_table_metadata {
 
 pub const PRAGMA_TABLE_INFO_SQL: &str = r#"SELECT "cid","name","type","notnull","dflt_value","pk" from pragma_table_info(?) ORDER BY "cid" ASC"#;
 
+#[macro_export]
+macro_rules! meta_query_sqlite_pragma_table_info {
+    ($table_name:expr) => {
+        sqlx::query_as::<_, $crate::drivers::sqlite::PragmaTableInfo>(
+            $crate::drivers::sqlite::PRAGMA_TABLE_INFO_SQL,
+        )
+        .bind($table_name)
+    };
+}
+
 #[derive(Debug)]
 pub struct ColumnMetatada {
     pub table_name: String,
@@ -37,6 +46,9 @@ pub struct ColumnMetatada {
     pub nullable: bool,
     pub default_value: Option<String>,
     pub is_pk: bool,
+    /// as pragma_table_xinfo reports it, 0 for an ordinary column, 1 hidden in a virtual table,
+    /// 2 generated virtual, 3 generated stored
+    pub hidden: i32,
 }
 impl ColumnMetatada {
     pub fn from_column_info(value: &PragmaTableInfo, tbl_name: &str) -> Self {
@@ -48,6 +60,19 @@ impl ColumnMetatada {
             nullable: value.notnull == 0,
             default_value: value.dflt_value.to_owned(),
             is_pk: value.pk.unwrap_or(0) != 0,
+            hidden: 0,
+        }
+    }
+    pub fn from_column_xinfo(value: &PragmaTableXInfo, tbl_name: &str) -> Self {
+        Self {
+            table_name: tbl_name.to_string(),
+            column_id: value.cid,
+            name: value.name.to_owned(),
+            r#type: value.r#type.to_owned(),
+            nullable: value.notnull == 0,
+            default_value: value.dflt_value.to_owned(),
+            is_pk: value.pk != 0,
+            hidden: value.hidden,
         }
     }
 }
@@ -67,8 +92,41 @@ impl ColumnMetatada {
 
 #[derive(Debug)]
 pub struct TableMetadata {
-    name: String,
-    // columns:HashMap<String,ColumnMetatada>
+    pub name: String,
+    /// every column including hidden and generated ones, in declaration order
+    pub columns: Vec<ColumnMetatada>,
+    /// primary key columns in key order. empty for a rowid table without a declared key
+    pub primary_key: Vec<String>,
+    pub indexes: Vec<IndexMetadata>,
+    pub foreign_keys: Vec<ForeignKeyMetadata>,
+    pub without_rowid: bool,
+    pub strict: bool,
+    /// the CREATE TABLE statement as sqlite keeps it in sqlite_master
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMetadata {
+    pub name: String,
+    pub unique: bool,
+    /// c for CREATE INDEX, u for a UNIQUE constraint, pk for a PRIMARY KEY
+    pub origin: String,
+    pub partial: bool,
+    /// key columns in index order, None where the index is on an expression
+    pub columns: Vec<Option<String>>,
+    /// None for indexes sqlite made for a constraint
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyMetadata {
+    pub id: i32,
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    /// None where the key points at the primary key of the referenced table without naming it
+    pub referenced_columns: Vec<Option<String>>,
+    pub on_update: String,
+    pub on_delete: String,
 }
 
 #[derive(sqlx::FromRow, Debug)]
@@ -107,7 +165,7 @@ impl std::fmt::Display for Logic {
 ///
 /// this function is only capable of asking for the specific type that pragma_table_xinfo reports,
 /// including but not limited to any custom types that dont match to an actual type
-pub(crate) async fn meta_fn_get_columns_for_table_with_datatype<Executor, Database>(
+pub(crate) async fn meta_fn_get_columns_for_table_with_datatype<'executor, Executor, Database>(
     executor: Executor,
     table_name: String,
     column_name: String,
@@ -120,7 +178,7 @@ where
     // database bounds
     Database: sqlx::Database,
     //executor bounds
-    for<'executor> Executor: sqlx::Executor<'executor, Database = Database>,
+    Executor: sqlx::Executor<'executor, Database = Database>,
     // arguments
     for<'arguments> <Database as sqlx::Database>::Arguments<'arguments>:
         sqlx::IntoArguments<'arguments, Database>,
@@ -135,7 +193,7 @@ where
     for<'row> PragmaTableInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
 {
     let column_name = {
-        if column_name.len() == 0 {
+        if column_name.is_empty() {
             String::new()
         } else {
             format!("%{column_name}%")
@@ -143,7 +201,7 @@ where
     };
 
     let type_pattern = {
-        if r#type.len() == 0 {
+        if r#type.is_empty() {
             String::new()
         } else {
             format!("%{}%", r#type)
@@ -164,7 +222,7 @@ where
     .map_err(|e|e.into())
 }
 /// Asks database if Table exists with Column and datatype. Only valid for sqlx::Sqlite using any other database will result in wrong binds or syntax
-pub async fn sqlite_meta_fn_get_columns_for_table_with_datatype<Executor>(
+pub async fn sqlite_meta_fn_get_columns_for_table_with_datatype<'executor, Executor>(
     executor: Executor,
     table_name: String,
     column_name: String,
@@ -174,7 +232,7 @@ pub async fn sqlite_meta_fn_get_columns_for_table_with_datatype<Executor>(
     offset: i64,
 ) -> Result<Vec<PragmaTableInfo>, anyhow::Error>
 where
-    for<'executor> Executor: sqlx::SqliteExecutor<'executor, Database = sqlx::Sqlite>,
+    Executor: sqlx::SqliteExecutor<'executor>,
     // for<'row> (i32,): sqlx::FromRow<'row, <sqlx::Sqlite as sqlx::Database>::Row>,
     // for<'row> i32: sqlx::FromRow<'row, <sqlx::Sqlite as sqlx::Database>::Row>,
     for<'row> PragmaTableInfo: sqlx::FromRow<'row, <sqlx::Sqlite as sqlx::Database>::Row>,
@@ -203,7 +261,7 @@ pub struct SqliteMasterInfo {
     pub sql: Option<String>,
 }
 
-async fn meta_fn_sqlite_master<Executor, Database>(
+async fn meta_fn_sqlite_master<'executor, Executor, Database>(
     executor: Executor,
     object_type: String,
     name: String,
@@ -214,9 +272,8 @@ where
     // database bounds
     Database: sqlx::Database,
     //executor bounds
-    for<'executor> Executor: sqlx::Executor<'executor, Database = Database>,
+    Executor: sqlx::Executor<'executor, Database = Database>,
     // for<'executor> &'executor mut Executor::Connection: sqlx::Executor<'executor>,
-    for<'c> &'c mut Database::Connection: sqlx::Executor<'c, Database = Database>,
     // for<'c> &'c sqlx::Pool<Database::Connection>: sqlx::Executor<'c, Database = Database>,
 
     // arguments
@@ -245,7 +302,7 @@ where
     .map_err(|e|e.into())
 }
 
-pub async fn sqlite_meta_fn_sqlite_master<Executor>(
+pub async fn sqlite_meta_fn_sqlite_master<'executor, Executor>(
     executor: Executor,
     object_type: String,
     name: String,
@@ -254,7 +311,7 @@ pub async fn sqlite_meta_fn_sqlite_master<Executor>(
 ) -> Result<Vec<SqliteMasterInfo>, anyhow::Error>
 where
     //executor bounds
-    for<'executor> Executor: sqlx::Executor<'executor, Database = sqlx::Sqlite>,
+    Executor: sqlx::Executor<'executor, Database = sqlx::Sqlite>,
     // arguments
     for<'arguments> <sqlx::Sqlite as sqlx::Database>::Arguments<'arguments>:
         sqlx::IntoArguments<'arguments, sqlx::Sqlite>,
@@ -303,7 +360,6 @@ where
     // from row
     for<'row> SqliteMasterInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
 {
-    let c = **transaction;
     meta_fn_sqlite_master::<&mut <Database as sqlx::Database>::Connection, Database>(
         &mut **transaction,
         object_type,
@@ -314,10 +370,308 @@ where
     .await
 }
 
+pub const SQL_GET_TABLES: &str = r#"SELECT "type","name","tbl_name","rootpage","sql" FROM sqlite_master WHERE "type" = 'table' ORDER BY "name""#;
+pub const PRAGMA_TABLE_XINFO_SQL: &str = r#"SELECT "cid","name","type","notnull","dflt_value","pk","hidden" FROM pragma_table_xinfo(?) ORDER BY "cid""#;
+pub const PRAGMA_TABLE_LIST_SQL: &str =
+    r#"SELECT "wr","strict" FROM pragma_table_list(?) WHERE "schema" = 'main'"#;
+pub const PRAGMA_INDEX_LIST_SQL: &str =
+    r#"SELECT "name","unique","origin","partial" FROM pragma_index_list(?) ORDER BY "name""#;
+pub const PRAGMA_INDEX_INFO_SQL: &str =
+    r#"SELECT "seqno","cid","name" FROM pragma_index_info(?) ORDER BY "seqno""#;
+pub const SQL_GET_INDEX_SQL: &str =
+    r#"SELECT "name","sql" FROM sqlite_master WHERE "type" = 'index' AND "tbl_name" = ?"#;
+pub const PRAGMA_FOREIGN_KEY_LIST_SQL: &str = r#"SELECT "id","seq","table","from","to","on_update","on_delete" FROM pragma_foreign_key_list(?) ORDER BY "id","seq""#;
+
+#[derive(FromRow, Debug)]
+pub struct PragmaTableXInfo {
+    pub cid: i32,
+    pub name: String,
+    pub r#type: String,
+    pub notnull: i32,
+    pub dflt_value: Option<String>,
+    pub pk: i32,
+    pub hidden: i32,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PragmaTableList {
+    pub wr: i32,
+    pub strict: i32,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PragmaIndexList {
+    pub name: String,
+    pub unique: i32,
+    pub origin: String,
+    pub partial: i32,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PragmaIndexInfo {
+    pub seqno: i32,
+    pub cid: i32,
+    pub name: Option<String>,
+}
+
+#[derive(FromRow, Debug)]
+pub struct PragmaForeignKeyList {
+    pub id: i32,
+    pub seq: i32,
+    pub table: String,
+    pub from: String,
+    pub to: Option<String>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+/// reads everything sqlite knows about one table. generic over the database so the Any
+/// inspector can share it when the backend is sqlite
+pub async fn get_table_metadata<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+    table: SqliteMasterInfo,
+) -> Result<TableMetadata, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> PragmaTableXInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaTableList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaForeignKeyList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> (String, Option<String>): sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let name = table.name.as_str();
+    let xinfo: Vec<PragmaTableXInfo> = sqlx::query_as(PRAGMA_TABLE_XINFO_SQL)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut primary_key: Vec<&PragmaTableXInfo> = xinfo.iter().filter(|c| c.pk != 0).collect();
+    primary_key.sort_by_key(|c| c.pk);
+    let primary_key = primary_key.into_iter().map(|c| c.name.clone()).collect();
+    let columns = xinfo
+        .iter()
+        .map(|c| ColumnMetatada::from_column_xinfo(c, name))
+        .collect();
+
+    // virtual tables are not in pragma_table_list's wr and strict sense, they read as 0
+    let flags: Option<PragmaTableList> = sqlx::query_as(PRAGMA_TABLE_LIST_SQL)
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let index_sql: HashMap<String, Option<String>> = sqlx::query_as(SQL_GET_INDEX_SQL)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+    let list: Vec<PragmaIndexList> = sqlx::query_as(PRAGMA_INDEX_LIST_SQL)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut indexes = Vec::with_capacity(list.len());
+    for index in list {
+        let info: Vec<PragmaIndexInfo> = sqlx::query_as(PRAGMA_INDEX_INFO_SQL)
+            .bind(index.name.as_str())
+            .fetch_all(&mut *conn)
+            .await?;
+        indexes.push(IndexMetadata {
+            sql: index_sql.get(&index.name).cloned().flatten(),
+            name: index.name,
+            unique: index.unique != 0,
+            origin: index.origin,
+            partial: index.partial != 0,
+            columns: info.into_iter().map(|c| c.name).collect(),
+        });
+    }
+
+    let keys: Vec<PragmaForeignKeyList> = sqlx::query_as(PRAGMA_FOREIGN_KEY_LIST_SQL)
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut foreign_keys: Vec<ForeignKeyMetadata> = vec![];
+    for key in keys {
+        match foreign_keys.last_mut() {
+            Some(fk) if fk.id == key.id => {
+                fk.columns.push(key.from);
+                fk.referenced_columns.push(key.to);
+            }
+            _ => foreign_keys.push(ForeignKeyMetadata {
+                id: key.id,
+                columns: vec![key.from],
+                referenced_table: key.table,
+                referenced_columns: vec![key.to],
+                on_update: key.on_update,
+                on_delete: key.on_delete,
+            }),
+        }
+    }
+
+    Ok(TableMetadata {
+        name: table.name,
+        columns,
+        primary_key,
+        indexes,
+        foreign_keys,
+        without_rowid: flags.as_ref().is_some_and(|f| f.wr != 0),
+        strict: flags.as_ref().is_some_and(|f| f.strict != 0),
+        sql: table.sql,
+    })
+}
+
+/// every table in sqlite_master, sqlite's own included, see SQL_GET_ALL_TABLE_METADATA
+pub async fn get_tables<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+) -> Result<Vec<TableMetadata>, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> SqliteMasterInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaTableXInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaTableList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaForeignKeyList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> (String, Option<String>): sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let master: Vec<SqliteMasterInfo> =
+        sqlx::query_as(SQL_GET_TABLES).fetch_all(&mut *conn).await?;
+    let mut tables = Vec::with_capacity(master.len());
+    for table in master {
+        tables.push(get_table_metadata::<Database>(&mut *conn, table).await?);
+    }
+    Ok(tables)
+}
+
+impl From<&ColumnMetatada> for crate::schema::LogicalType {
+    fn from(c: &ColumnMetatada) -> Self {
+        match Self::from_sql_name(&c.r#type) {
+            // every sqlite integer is stored in up to 8 bytes
+            Self::Integer { bytes: 4, .. } if c.r#type.eq_ignore_ascii_case("integer") => {
+                Self::Integer {
+                    bytes: 8,
+                    unsigned: false,
+                }
+            }
+            t => t,
+        }
+    }
+}
+
+impl From<TableMetadata> for crate::schema::Table {
+    fn from(t: TableMetadata) -> Self {
+        let autoincrement = t
+            .sql
+            .as_deref()
+            .is_some_and(|sql| sql.to_uppercase().contains("AUTOINCREMENT"));
+        let columns = t
+            .columns
+            .iter()
+            // hidden columns of a virtual table are not part of its schema
+            .filter(|c| c.hidden != 1)
+            .map(|c| crate::schema::Column {
+                name: c.name.clone(),
+                r#type: c.into(),
+                native_type: c.r#type.clone(),
+                nullable: c.nullable,
+                default: c.default_value.clone(),
+                auto_increment: autoincrement && c.is_pk && t.primary_key.len() == 1,
+                // sqlite says a column is generated but not how, the expression is left empty
+                generated: (c.hidden >= 2).then(String::new),
+                comment: None,
+            })
+            .collect();
+        let indexes = t
+            .indexes
+            .into_iter()
+            .map(|i| crate::schema::Index {
+                primary: i.origin == "pk",
+                constraint: i.origin != "c",
+                name: i.name,
+                columns: i.columns,
+                unique: i.unique,
+                partial: i.partial,
+            })
+            .collect();
+        let foreign_keys = t
+            .foreign_keys
+            .into_iter()
+            .map(|fk| crate::schema::ForeignKey {
+                name: None,
+                columns: fk.columns,
+                referenced_schema: None,
+                referenced_table: fk.referenced_table,
+                // see get_metadata for keys that leave out the referenced columns
+                referenced_columns: fk.referenced_columns.into_iter().flatten().collect(),
+                on_update: fk.on_update,
+                on_delete: fk.on_delete,
+            })
+            .collect();
+        Self {
+            name: t.name,
+            columns,
+            primary_key: t.primary_key,
+            indexes,
+            foreign_keys,
+            checks: vec![],
+            comment: None,
+        }
+    }
+}
+
+/// the database in the neutral model, without sqlite's own tables
+pub async fn get_metadata<Database>(
+    conn: &mut <Database as sqlx::Database>::Connection,
+) -> Result<crate::schema::Database, anyhow::Error>
+where
+    Database: sqlx::Database,
+    for<'c> &'c mut <Database as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = Database>,
+    for<'q> <Database as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, Database>,
+    for<'q> &'q str: sqlx::Encode<'q, Database> + sqlx::Type<Database>,
+    for<'row> SqliteMasterInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaTableXInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaTableList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaIndexInfo: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> PragmaForeignKeyList: sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+    for<'row> (String, Option<String>): sqlx::FromRow<'row, <Database as sqlx::Database>::Row>,
+{
+    let mut tables = get_tables::<Database>(conn).await?;
+    tables.retain(|t| !t.name.starts_with("sqlite_"));
+    // REFERENCES accounts without columns points at the primary key of accounts
+    let primary_keys: HashMap<String, Vec<String>> = tables
+        .iter()
+        .map(|t| (t.name.clone(), t.primary_key.clone()))
+        .collect();
+    for fk in tables.iter_mut().flat_map(|t| t.foreign_keys.iter_mut()) {
+        if fk.referenced_columns.iter().any(Option::is_none)
+            && let Some(pk) = primary_keys.get(&fk.referenced_table)
+        {
+            fk.referenced_columns = pk.iter().cloned().map(Some).collect();
+        }
+    }
+    Ok(crate::schema::Database {
+        backend: <sqlx::Sqlite as sqlx::Database>::NAME.to_string(),
+        schemas: vec![crate::schema::Schema {
+            name: "main".to_string(),
+            tables: tables.into_iter().map(Into::into).collect(),
+        }],
+    })
+}
+
 #[async_trait::async_trait]
 impl<'a> crate::SchemaInspector<sqlx::Sqlite> for sqlx::Transaction<'a, sqlx::Sqlite> {
     type ColumnInfo = PragmaTableInfo;
-    type TableInfo = ();
+    type TableInfo = TableMetadata;
+    type InformationSchema = crate::schema::Database;
     async fn get_columns(
         &mut self,
         for_table_name: &str,
@@ -336,32 +690,9 @@ impl<'a> crate::SchemaInspector<sqlx::Sqlite> for sqlx::Transaction<'a, sqlx::Sq
         Ok(t)
     }
     async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
-        Ok(vec![])
+        get_tables::<sqlx::Sqlite>(&mut **self).await
+    }
+    async fn get_metadata(&mut self) -> Result<Self::InformationSchema, anyhow::Error> {
+        get_metadata::<sqlx::Sqlite>(&mut **self).await
     }
-}
-
-async fn q<'q, E, DB>(e: E) -> Result<(), anyhow::Error>
-where
-    DB: sqlx::Database,
-    for<'e> E: Executor<'e, Database = DB>,
-    for<'e> &'e mut <DB as sqlx::Database>::Connection: Executor<'e, Database = DB>,
-    for<'n> <DB as sqlx::Database>::Arguments<'n>: sqlx::IntoArguments<'n, DB>,
-{
-    sqlx::query("select 1")
-        .execute(e)
-        .await
-        .map(|r| ())
-        .map_err(|e| e.into())
-}
-
-async fn c() -> Result<sqlx::SqliteConnection, anyhow::Error> {
-    sqlx::SqliteConnection::connect("::memory::")
-        .await
-        .map_err(|e| e.into())
-}
-
-async fn t() {
-    let mut c = c().await.unwrap();
-    let mut t = c.begin().await.unwrap();
-    meta_fn_sqlite_master(&mut *t, "".to_string(), "".to_string(), "".to_string(), 0);
 }
@@ -1,8 +1,8 @@
 #![feature(associated_type_defaults)]
 
 pub mod drivers;
-
-use sqlx::{Database, Row, prelude::FromRow};
+pub mod render;
+pub mod schema;
 
 #[async_trait::async_trait]
 pub trait SchemaInspector<DB: sqlx::Database> {
@@ -14,8 +14,7 @@ pub trait SchemaInspector<DB: sqlx::Database> {
         for_table_name: &str,
     ) -> Result<Vec<Self::ColumnInfo>, anyhow::Error>;
 
-    async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
-        todo!("Implement this function")
-    }
-    // async fn get_metadata(&mut self) -> Result<Self::InformationSchema,anyhow::Error>;
+    async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error>;
+    /// the whole database in the backend neutral model, see schema
+    async fn get_metadata(&mut self) -> Result<Self::InformationSchema, anyhow::Error>;
 }
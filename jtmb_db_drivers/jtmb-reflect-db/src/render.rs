@@ -0,0 +1,803 @@
+// turns a schema::Database into text: a listing for people, ddl to create it again in a chosen
+// dialect, and er diagrams for graphviz and mermaid. json is serde's job, see the serialize feature.
+// ddl for the dialect the schema was read from keeps defaults, generated columns and checks as the
+// database wrote them. for another dialect only what has a portable spelling is kept and the rest
+// is left out with a -- comment saying so
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::schema::{Column, Database, ForeignKey, Index, LogicalType, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+impl Dialect {
+    /// the dialect of the database a schema was read from, by sqlx's backend name
+    pub fn of(database: &Database) -> Option<Self> {
+        match database.backend.as_str() {
+            "SQLite" => Some(Self::Sqlite),
+            "PostgreSQL" => Some(Self::Postgres),
+            "MySQL" => Some(Self::MySql),
+            _ => None,
+        }
+    }
+
+    pub fn quote(self, name: &str) -> String {
+        match self {
+            Self::MySql => format!("`{}`", name.replace('`', "``")),
+            _ => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    pub fn sql_type(self, t: &LogicalType, native_type: &str) -> String {
+        use LogicalType::*;
+        let sized = |name: &str, n: &Option<u32>| match n {
+            Some(n) => format!("{name}({n})"),
+            None => name.to_string(),
+        };
+        let decimal =
+            |name: &str, precision: &Option<u32>, scale: &Option<u32>| match (precision, scale) {
+                (Some(p), Some(s)) => format!("{name}({p},{s})"),
+                (Some(p), None) => format!("{name}({p})"),
+                _ => name.to_string(),
+            };
+        let quoted_list = |values: &[String]| {
+            values
+                .iter()
+                .map(|v| quote_literal(v))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match (self, t) {
+            (_, Other(_)) => native_type.to_string(),
+            (Self::Sqlite, t) => match t {
+                Boolean => "BOOLEAN",
+                Integer { .. } => "INTEGER",
+                Decimal { .. } => "NUMERIC",
+                Float | Double => "REAL",
+                Binary(_) | Varbinary(_) | Blob | Uuid => "BLOB",
+                Date => "DATE",
+                Time { .. } => "TIME",
+                Timestamp { .. } => "DATETIME",
+                _ => "TEXT",
+            }
+            .to_string(),
+            (Self::Postgres, t) => match t {
+                Boolean => "boolean".to_string(),
+                Integer { bytes, unsigned } => match (bytes, unsigned) {
+                    (1 | 2, false) | (1, true) => "smallint",
+                    (3 | 4, false) | (2 | 3, true) => "integer",
+                    (4, true) | (8, false) => "bigint",
+                    _ => "numeric(20)",
+                }
+                .to_string(),
+                Decimal { precision, scale } => decimal("numeric", precision, scale),
+                Float => "real".to_string(),
+                Double => "double precision".to_string(),
+                Char(n) => sized("char", n),
+                Varchar(n) => sized("varchar", n),
+                Text | Enum(_) | Set(_) => "text".to_string(),
+                Binary(_) | Varbinary(_) | Blob => "bytea".to_string(),
+                Date => "date".to_string(),
+                Time { with_time_zone } => if *with_time_zone {
+                    "time with time zone"
+                } else {
+                    "time"
+                }
+                .to_string(),
+                Timestamp { with_time_zone } => if *with_time_zone {
+                    "timestamp with time zone"
+                } else {
+                    "timestamp"
+                }
+                .to_string(),
+                Interval => "interval".to_string(),
+                Json => "jsonb".to_string(),
+                Uuid => "uuid".to_string(),
+                Array(element) => format!("{}[]", self.sql_type(element, native_type)),
+                Other(_) => unreachable!(),
+            },
+            (Self::MySql, t) => match t {
+                Boolean => "tinyint(1)".to_string(),
+                Integer { bytes, unsigned } => {
+                    let name = match bytes {
+                        1 => "tinyint",
+                        2 => "smallint",
+                        3 => "mediumint",
+                        4 => "int",
+                        _ => "bigint",
+                    };
+                    if *unsigned {
+                        format!("{name} unsigned")
+                    } else {
+                        name.to_string()
+                    }
+                }
+                Decimal {
+                    precision: None, ..
+                } => "decimal(65,30)".to_string(),
+                Decimal { precision, scale } => decimal("decimal", precision, scale),
+                Float => "float".to_string(),
+                Double => "double".to_string(),
+                Char(n) => sized("char", n),
+                Varchar(Some(n)) => format!("varchar({n})"),
+                Varchar(None) | Text | Interval => "longtext".to_string(),
+                Binary(n) => sized("binary", n),
+                Varbinary(Some(n)) => format!("varbinary({n})"),
+                Varbinary(None) | Blob => "longblob".to_string(),
+                Date => "date".to_string(),
+                Time { .. } => "time".to_string(),
+                Timestamp {
+                    with_time_zone: false,
+                } => "datetime".to_string(),
+                Timestamp {
+                    with_time_zone: true,
+                } => "timestamp".to_string(),
+                Json | Array(_) => "json".to_string(),
+                Uuid => "char(36)".to_string(),
+                Enum(values) => format!("enum({})", quoted_list(values)),
+                Set(values) => format!("set({})", quoted_list(values)),
+                Other(_) => unreachable!(),
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for Dialect {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sqlite" => Ok(Self::Sqlite),
+            "postgres" | "postgresql" | "pg" => Ok(Self::Postgres),
+            "mysql" | "mariadb" => Ok(Self::MySql),
+            other => anyhow::bail!("unknown dialect {other}, one of sqlite, postgres or mysql"),
+        }
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// a default every dialect reads the same, with postgres' casts taken off: 'asset'::kind is 'asset'
+fn portable_default(expression: &str) -> Option<String> {
+    let mut e = expression.trim();
+    loop {
+        let before = e;
+        if let Some(cast) = e.rfind("::").filter(|i| !e[*i..].contains('\'')) {
+            e = e[..cast].trim();
+        }
+        if e.starts_with('(') && e.ends_with(')') {
+            e = e[1..e.len() - 1].trim();
+        }
+        if e == before {
+            break;
+        }
+    }
+    let upper = e.to_uppercase();
+    let literal_string = e.len() >= 2
+        && e.starts_with('\'')
+        && e.ends_with('\'')
+        && !e[1..e.len() - 1].replace("''", "").contains('\'');
+    match upper.as_str() {
+        _ if literal_string || e.parse::<f64>().is_ok() => Some(e.to_string()),
+        "NULL" | "TRUE" | "FALSE" | "CURRENT_DATE" | "CURRENT_TIME" => Some(upper),
+        "CURRENT_TIMESTAMP" | "CURRENT_TIMESTAMP()" | "NOW()" => {
+            Some("CURRENT_TIMESTAMP".to_string())
+        }
+        _ => None,
+    }
+}
+
+struct Ddl<'d> {
+    dialect: Dialect,
+    /// the schema was read from this dialect, so backend specific sql can be kept
+    same: bool,
+    /// postgres to postgres keeps the schemas, everything else is written into one
+    qualify: bool,
+    out: String,
+    deferred: Vec<String>,
+    database: &'d Database,
+}
+
+impl Ddl<'_> {
+    fn table_name(&self, schema: &str, table: &str) -> String {
+        if self.qualify && schema != "public" {
+            format!(
+                "{}.{}",
+                self.dialect.quote(schema),
+                self.dialect.quote(table)
+            )
+        } else {
+            self.dialect.quote(table)
+        }
+    }
+
+    fn column(&mut self, table: &Table, c: &Column, lines: &mut Vec<String>) {
+        let q = |n: &str| self.dialect.quote(n);
+        // postgres' native types can name enums and domains this ddl does not create
+        let r#type = if self.same && self.dialect != Dialect::Postgres {
+            c.native_type.clone()
+        } else {
+            self.dialect.sql_type(&c.r#type, &c.native_type)
+        };
+        let mut line = format!("{} {}", q(&c.name), r#type);
+        let sqlite_rowid = self.dialect == Dialect::Sqlite
+            && c.auto_increment
+            && table.primary_key == [c.name.as_str()];
+        match self.dialect {
+            _ if !c.auto_increment => {}
+            Dialect::Sqlite if sqlite_rowid => line.push_str(" PRIMARY KEY AUTOINCREMENT"),
+            Dialect::Sqlite => {}
+            Dialect::Postgres => line.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+            Dialect::MySql => line.push_str(" AUTO_INCREMENT"),
+        }
+        if !c.nullable && !sqlite_rowid {
+            line.push_str(" NOT NULL");
+        }
+        match (&c.generated, &c.default) {
+            (Some(expression), _) if self.same && !expression.is_empty() => {
+                let stored = if self.dialect == Dialect::Postgres {
+                    " STORED"
+                } else {
+                    ""
+                };
+                let _ = write!(line, " GENERATED ALWAYS AS ({expression}){stored}");
+            }
+            (Some(_), _) => lines.push(format!(
+                "-- {} is generated, written as a plain column",
+                c.name
+            )),
+            (None, Some(_)) if c.auto_increment => {}
+            // postgres casts defaults to enum types this ddl does not create, drop the cast
+            (None, Some(default)) if self.same => {
+                let default = match self.dialect {
+                    Dialect::Postgres => portable_default(default).unwrap_or(default.clone()),
+                    _ => default.clone(),
+                };
+                let _ = write!(line, " DEFAULT {default}");
+            }
+            (None, Some(default)) => match portable_default(default) {
+                Some(default) => {
+                    let _ = write!(line, " DEFAULT {default}");
+                }
+                None => lines.push(format!("-- default of {} left out: {default}", c.name)),
+            },
+            (None, None) => {}
+        }
+        if let (LogicalType::Enum(values), Dialect::Sqlite | Dialect::Postgres) =
+            (&c.r#type, self.dialect)
+        {
+            let values: Vec<String> = values.iter().map(|v| quote_literal(v)).collect();
+            let _ = write!(line, " CHECK ({} IN ({}))", q(&c.name), values.join(", "));
+        }
+        if let (Some(comment), Dialect::MySql) = (&c.comment, self.dialect) {
+            let _ = write!(line, " COMMENT {}", quote_literal(comment));
+        }
+        lines.push(line);
+    }
+
+    // mysql can only index a prefix of text and blob columns
+    fn key_part(&self, table: &Table, column: &str) -> String {
+        let long = table.columns.iter().any(|c| {
+            c.name == column
+                && matches!(
+                    c.r#type,
+                    LogicalType::Text | LogicalType::Blob | LogicalType::Json
+                )
+        });
+        match self.dialect {
+            Dialect::MySql if long => format!("{}(255)", self.dialect.quote(column)),
+            _ => self.dialect.quote(column),
+        }
+    }
+
+    fn key_parts(&self, table: &Table, columns: &[String]) -> String {
+        columns
+            .iter()
+            .map(|c| self.key_part(table, c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn foreign_key(&self, schema: &str, fk: &ForeignKey) -> String {
+        let q = |n: &str| self.dialect.quote(n);
+        let mut clause = match &fk.name {
+            Some(name) => format!("CONSTRAINT {} FOREIGN KEY", q(name)),
+            None => "FOREIGN KEY".to_string(),
+        };
+        let columns: Vec<String> = fk.columns.iter().map(|c| q(c)).collect();
+        let referenced: Vec<String> = fk.referenced_columns.iter().map(|c| q(c)).collect();
+        let _ = write!(
+            clause,
+            " ({}) REFERENCES {} ({})",
+            columns.join(", "),
+            self.table_name(
+                fk.referenced_schema.as_deref().unwrap_or(schema),
+                &fk.referenced_table
+            ),
+            referenced.join(", ")
+        );
+        for (action, rule) in [("DELETE", &fk.on_delete), ("UPDATE", &fk.on_update)] {
+            if rule != "NO ACTION" {
+                let _ = write!(clause, " ON {action} {rule}");
+            }
+        }
+        clause
+    }
+
+    fn table(&mut self, schema: &str, table: &Table, created: &[(&str, &str)]) {
+        let name = self.table_name(schema, &table.name);
+        let mut lines = vec![];
+        for c in &table.columns {
+            self.column(table, c, &mut lines);
+        }
+        let rowid = table
+            .columns
+            .iter()
+            .any(|c| c.auto_increment && table.primary_key == [c.name.as_str()]);
+        if !table.primary_key.is_empty() && !(self.dialect == Dialect::Sqlite && rowid) {
+            lines.push(format!(
+                "PRIMARY KEY ({})",
+                self.key_parts(table, &table.primary_key)
+            ));
+        }
+        for index in table.indexes.iter().filter(|i| i.constraint && !i.primary) {
+            let Some(columns) = index_columns(index) else {
+                continue;
+            };
+            let columns = self.key_parts(table, &columns);
+            lines.push(if index.name.starts_with("sqlite_autoindex") {
+                format!("UNIQUE ({columns})")
+            } else {
+                format!(
+                    "CONSTRAINT {} UNIQUE ({columns})",
+                    self.dialect.quote(&index.name)
+                )
+            });
+        }
+        for fk in &table.foreign_keys {
+            let referenced = (
+                fk.referenced_schema.as_deref().unwrap_or(schema),
+                fk.referenced_table.as_str(),
+            );
+            let exists =
+                referenced == (schema, table.name.as_str()) || created.contains(&referenced);
+            if exists || self.dialect == Dialect::Sqlite {
+                lines.push(self.foreign_key(schema, fk));
+            } else {
+                // the referenced table comes later, a cycle
+                self.deferred.push(format!(
+                    "ALTER TABLE {name} ADD {};",
+                    self.foreign_key(schema, fk)
+                ));
+            }
+        }
+        for check in &table.checks {
+            if !self.same {
+                lines.push(format!("-- check left out: {}", check.expression));
+                continue;
+            }
+            lines.push(match &check.name {
+                Some(n) => format!(
+                    "CONSTRAINT {} CHECK ({})",
+                    self.dialect.quote(n),
+                    check.expression
+                ),
+                None => format!("CHECK ({})", check.expression),
+            });
+        }
+
+        let _ = writeln!(self.out, "CREATE TABLE {name} (");
+        // a comment line takes no comma and the last definition none either
+        let last = lines.iter().rposition(|l| !l.starts_with("--"));
+        for (i, line) in lines.iter().enumerate() {
+            let comma = if line.starts_with("--") || Some(i) == last {
+                ""
+            } else {
+                ","
+            };
+            let _ = writeln!(self.out, "    {line}{comma}");
+        }
+        match (&table.comment, self.dialect) {
+            (Some(comment), Dialect::MySql) => {
+                let _ = writeln!(self.out, ") COMMENT {};", quote_literal(comment));
+            }
+            _ => self.out.push_str(");\n"),
+        }
+        if self.dialect == Dialect::Postgres {
+            if let Some(comment) = &table.comment {
+                let _ = writeln!(
+                    self.out,
+                    "COMMENT ON TABLE {name} IS {};",
+                    quote_literal(comment)
+                );
+            }
+            for c in &table.columns {
+                if let Some(comment) = &c.comment {
+                    let _ = writeln!(
+                        self.out,
+                        "COMMENT ON COLUMN {name}.{} IS {};",
+                        self.dialect.quote(&c.name),
+                        quote_literal(comment)
+                    );
+                }
+            }
+        }
+
+        for index in table.indexes.iter().filter(|i| !i.constraint) {
+            match index_columns(index) {
+                Some(columns) if !index.partial => {
+                    let _ = writeln!(
+                        self.out,
+                        "CREATE {}INDEX {} ON {name} ({});",
+                        if index.unique { "UNIQUE " } else { "" },
+                        self.dialect.quote(&index.name),
+                        self.key_parts(table, &columns)
+                    );
+                }
+                _ => {
+                    let _ = writeln!(
+                        self.out,
+                        "-- index {} is partial or on an expression, left out",
+                        index.name
+                    );
+                }
+            }
+        }
+        self.out.push('\n');
+    }
+}
+
+fn index_columns(index: &Index) -> Option<Vec<String>> {
+    index.columns.iter().cloned().collect()
+}
+
+// referenced tables first, so a foreign key names a table that exists. cycles fall back on
+// reading order and the keys that close them are added by ALTER TABLE afterwards
+fn creation_order(database: &Database) -> Vec<(&str, &Table)> {
+    let tables: Vec<(&str, &Table)> = database
+        .schemas
+        .iter()
+        .flat_map(|s| s.tables.iter().map(move |t| (s.name.as_str(), t)))
+        .collect();
+    let position: HashMap<(&str, &str), usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, (s, t))| ((*s, t.name.as_str()), i))
+        .collect();
+    fn visit(
+        i: usize,
+        tables: &[(&str, &Table)],
+        position: &HashMap<(&str, &str), usize>,
+        state: &mut Vec<u8>,
+        order: &mut Vec<usize>,
+    ) {
+        if state[i] != 0 {
+            return;
+        }
+        state[i] = 1;
+        let (schema, table) = tables[i];
+        for fk in &table.foreign_keys {
+            let key = (
+                fk.referenced_schema.as_deref().unwrap_or(schema),
+                fk.referenced_table.as_str(),
+            );
+            if let Some(&j) = position.get(&key) {
+                visit(j, tables, position, state, order);
+            }
+        }
+        state[i] = 2;
+        order.push(i);
+    }
+    let mut state = vec![0; tables.len()];
+    let mut order = Vec::with_capacity(tables.len());
+    for i in 0..tables.len() {
+        visit(i, &tables, &position, &mut state, &mut order);
+    }
+    order.into_iter().map(|i| tables[i]).collect()
+}
+
+/// CREATE statements for the whole database in `dialect`, tables ordered so foreign keys resolve
+pub fn ddl(database: &Database, dialect: Dialect) -> String {
+    let source = Dialect::of(database);
+    let mut ddl = Ddl {
+        dialect,
+        same: source == Some(dialect),
+        qualify: dialect == Dialect::Postgres && source == Some(Dialect::Postgres),
+        out: format!("-- {} schema written for {dialect:?}\n\n", database.backend),
+        deferred: vec![],
+        database,
+    };
+    if ddl.qualify {
+        for schema in &ddl.database.schemas {
+            if schema.name != "public" {
+                let _ = writeln!(
+                    ddl.out,
+                    "CREATE SCHEMA IF NOT EXISTS {};\n",
+                    dialect.quote(&schema.name)
+                );
+            }
+        }
+    }
+    let mut created = vec![];
+    for (schema, table) in creation_order(database) {
+        ddl.table(schema, table, &created);
+        created.push((schema, table.name.as_str()));
+    }
+    for statement in std::mem::take(&mut ddl.deferred) {
+        let _ = writeln!(ddl.out, "{statement}");
+    }
+    ddl.out
+}
+
+fn grid(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let rule: String = widths
+        .iter()
+        .map(|w| format!("+{}", "-".repeat(w + 2)))
+        .collect::<String>()
+        + "+\n";
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("| {c:<w$} "))
+            .collect::<String>()
+            + "|\n"
+    };
+    let mut out = rule.clone();
+    out.push_str(&line(headers.to_vec()));
+    out.push_str(&rule);
+    for row in rows {
+        out.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    out.push_str(&rule);
+    out
+}
+
+/// every table as a grid of its columns, followed by its indexes, foreign keys and checks
+pub fn table(database: &Database) -> String {
+    let mut out = String::new();
+    for schema in &database.schemas {
+        for table in &schema.tables {
+            let _ = write!(out, "{}.{}", schema.name, table.name);
+            if let Some(comment) = &table.comment {
+                let _ = write!(out, "  -- {comment}");
+            }
+            out.push('\n');
+            let rows: Vec<Vec<String>> = table
+                .columns
+                .iter()
+                .map(|c| {
+                    let mut key = vec![];
+                    if table.primary_key.contains(&c.name) {
+                        key.push("PK".to_string());
+                    }
+                    for fk in table
+                        .foreign_keys
+                        .iter()
+                        .filter(|fk| fk.columns.contains(&c.name))
+                    {
+                        key.push(format!("FK {}", fk.referenced_table));
+                    }
+                    let mut default = c.default.clone().unwrap_or_default();
+                    if c.auto_increment {
+                        default = "auto increment".to_string();
+                    }
+                    if let Some(generated) = &c.generated {
+                        default = format!("generated {generated}");
+                    }
+                    vec![
+                        c.name.clone(),
+                        c.native_type.clone(),
+                        if c.nullable { "yes" } else { "no" }.to_string(),
+                        default,
+                        key.join(", "),
+                    ]
+                })
+                .collect();
+            out.push_str(&grid(
+                &["column", "type", "nullable", "default", "key"],
+                &rows,
+            ));
+            for index in &table.indexes {
+                let columns: Vec<&str> = index
+                    .columns
+                    .iter()
+                    .map(|c| c.as_deref().unwrap_or("<expression>"))
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    "  {}index {} ({}){}",
+                    if index.unique { "unique " } else { "" },
+                    index.name,
+                    columns.join(", "),
+                    if index.partial { " partial" } else { "" }
+                );
+            }
+            for fk in &table.foreign_keys {
+                let _ = writeln!(
+                    out,
+                    "  foreign key ({}) references {} ({}) on delete {} on update {}",
+                    fk.columns.join(", "),
+                    fk.referenced_table,
+                    fk.referenced_columns.join(", "),
+                    fk.on_delete,
+                    fk.on_update
+                );
+            }
+            for check in &table.checks {
+                let _ = writeln!(out, "  check {}", check.expression);
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// schema.table when the database has tables in more than one schema
+fn entity_names(database: &Database) -> impl Fn(&str, &str) -> String {
+    let qualify = database
+        .schemas
+        .iter()
+        .filter(|s| !s.tables.is_empty())
+        .count()
+        > 1;
+    move |schema, table| {
+        if qualify {
+            format!("{schema}.{table}")
+        } else {
+            table.to_string()
+        }
+    }
+}
+
+/// a graphviz digraph, one record per table and an edge per foreign key
+pub fn dot(database: &Database) -> String {
+    let name = entity_names(database);
+    let escape = |s: &str| {
+        s.chars()
+            .flat_map(|c| match c {
+                '{' | '}' | '|' | '<' | '>' | '"' | '\\' | ' ' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect::<String>()
+    };
+    let mut out = String::from("digraph schema {\n    rankdir=LR;\n    node [shape=record];\n");
+    for schema in &database.schemas {
+        for table in &schema.tables {
+            let entity = name(&schema.name, &table.name);
+            let columns: String = table
+                .columns
+                .iter()
+                .map(|c| {
+                    let pk = if table.primary_key.contains(&c.name) {
+                        "\\ PK"
+                    } else {
+                        ""
+                    };
+                    format!(
+                        "{}\\ :\\ {}{pk}\\l",
+                        escape(&c.name),
+                        escape(&c.native_type)
+                    )
+                })
+                .collect();
+            let _ = writeln!(
+                out,
+                "    \"{entity}\" [label=\"{{{}|{columns}}}\"];",
+                escape(&entity)
+            );
+            for fk in &table.foreign_keys {
+                let referenced = name(
+                    fk.referenced_schema.as_deref().unwrap_or(&schema.name),
+                    &fk.referenced_table,
+                );
+                let _ = writeln!(
+                    out,
+                    "    \"{entity}\" -> \"{referenced}\" [label=\"{}\"];",
+                    fk.columns.join(", ")
+                );
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// a mermaid erDiagram. types and names are reduced to what mermaid accepts
+pub fn mermaid(database: &Database) -> String {
+    let name = entity_names(database);
+    let word = |s: &str| {
+        let w: String = s
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '(' | ')' | '[' | ']' => c,
+                _ => '_',
+            })
+            .collect();
+        if w.is_empty() { "ANY".to_string() } else { w }
+    };
+    let mut out = String::from("erDiagram\n");
+    let mut relations = String::new();
+    for schema in &database.schemas {
+        for table in &schema.tables {
+            let entity = word(&name(&schema.name, &table.name));
+            let _ = writeln!(out, "    {entity} {{");
+            for c in &table.columns {
+                let mut keys = vec![];
+                if table.primary_key.contains(&c.name) {
+                    keys.push("PK");
+                }
+                if table
+                    .foreign_keys
+                    .iter()
+                    .any(|fk| fk.columns.contains(&c.name))
+                {
+                    keys.push("FK");
+                }
+                let unique = table
+                    .indexes
+                    .iter()
+                    .any(|i| i.unique && !i.primary && i.columns == [Some(c.name.clone())]);
+                if unique {
+                    keys.push("UK");
+                }
+                let _ = writeln!(
+                    out,
+                    "        {} {}{}{}",
+                    word(&c.native_type),
+                    word(&c.name),
+                    if keys.is_empty() { "" } else { " " },
+                    keys.join(", ")
+                );
+            }
+            out.push_str("    }\n");
+            for fk in &table.foreign_keys {
+                let referenced = word(&name(
+                    fk.referenced_schema.as_deref().unwrap_or(&schema.name),
+                    &fk.referenced_table,
+                ));
+                let optional = table
+                    .columns
+                    .iter()
+                    .any(|c| c.nullable && fk.columns.contains(&c.name));
+                let _ = writeln!(
+                    relations,
+                    "    {entity} }}o--{} {referenced} : \"{}\"",
+                    if optional { "o|" } else { "||" },
+                    fk.columns.join(", ")
+                );
+            }
+        }
+    }
+    out.push_str(&relations);
+    out
+}
+
+#[test]
+fn portable_defaults() {
+    assert_eq!(
+        portable_default("'asset'::reflect_test.account_kind").as_deref(),
+        Some("'asset'")
+    );
+    assert_eq!(portable_default("(0)::numeric").as_deref(), Some("0"));
+    assert_eq!(
+        portable_default("now()").as_deref(),
+        Some("CURRENT_TIMESTAMP")
+    );
+    assert_eq!(portable_default("'it''s'").as_deref(), Some("'it''s'"));
+    assert_eq!(portable_default("nextval('seq'::regclass)"), None);
+    assert_eq!(portable_default("(amount * 2)"), None);
+}
@@ -0,0 +1,247 @@
+// the schema every inspector maps into through SchemaInspector::get_metadata, so callers can snapshot,
+// compare and print a database without knowing the backend. what only one backend has, sqlite's
+// WITHOUT ROWID, mysql engines, postgres exclusion constraints, is left out here and stays in the
+// backend's own TableMetadata
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Database {
+    /// sqlx's name for the backend, SQLite, PostgreSQL or MySQL
+    pub backend: String,
+    pub schemas: Vec<Schema>,
+}
+
+impl Database {
+    pub fn table(&self, schema: &str, name: &str) -> Option<&Table> {
+        self.schemas
+            .iter()
+            .find(|s| s.name == schema)?
+            .tables
+            .iter()
+            .find(|t| t.name == name)
+    }
+}
+
+/// main for sqlite, the database for mysql
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Schema {
+    pub name: String,
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    /// primary key columns in key order, empty without a primary key
+    pub primary_key: Vec<String>,
+    pub indexes: Vec<Index>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub checks: Vec<Check>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Column {
+    pub name: String,
+    pub r#type: LogicalType,
+    /// the type as the database has it, e.g. character varying(40) or int(10) unsigned
+    pub native_type: String,
+    pub nullable: bool,
+    /// the default expression in the backend's own sql
+    pub default: Option<String>,
+    /// AUTO_INCREMENT or an identity column. sqlite's INTEGER PRIMARY KEY is not counted
+    pub auto_increment: bool,
+    /// the expression of a generated column, in the backend's own sql
+    pub generated: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum LogicalType {
+    Boolean,
+    /// an integer of 1, 2, 3, 4 or 8 bytes
+    Integer {
+        bytes: u8,
+        unsigned: bool,
+    },
+    Decimal {
+        precision: Option<u32>,
+        scale: Option<u32>,
+    },
+    Float,
+    Double,
+    Char(Option<u32>),
+    Varchar(Option<u32>),
+    Text,
+    Binary(Option<u32>),
+    Varbinary(Option<u32>),
+    Blob,
+    Date,
+    Time {
+        with_time_zone: bool,
+    },
+    Timestamp {
+        with_time_zone: bool,
+    },
+    Interval,
+    Json,
+    Uuid,
+    Enum(Vec<String>),
+    Set(Vec<String>),
+    Array(Box<LogicalType>),
+    /// a type the model has no name for, as the database calls it
+    Other(String),
+}
+
+impl LogicalType {
+    /// maps a type by its sql name, numbers in parentheses taken as length or precision.
+    /// shared by backends whose declared types are free text, sqlite and postgres
+    pub fn from_sql_name(declared: &str) -> Self {
+        let (name, numbers) = split_type_name(declared);
+        let n = |i: usize| numbers.get(i).copied();
+        let with_time_zone = name.ends_with("with time zone") || name.ends_with("tz");
+        let integer = |bytes| Self::Integer {
+            bytes,
+            unsigned: false,
+        };
+        match name.as_str() {
+            "boolean" | "bool" => Self::Boolean,
+            "tinyint" | "int1" => integer(1),
+            "smallint" | "int2" | "smallserial" | "serial2" => integer(2),
+            "mediumint" | "int3" => integer(3),
+            "int" | "int4" | "serial" | "serial4" => integer(4),
+            // postgres' integer. sqlite's INTEGER holds 8 bytes, its inspector widens it
+            "integer" => integer(4),
+            "bigint" | "int8" | "bigserial" | "serial8" => integer(8),
+            "unsigned big int" => Self::Integer {
+                bytes: 8,
+                unsigned: true,
+            },
+            "numeric" | "decimal" => Self::Decimal {
+                precision: n(0),
+                scale: n(1),
+            },
+            "real" | "float4" => Self::Float,
+            "double" | "double precision" | "float" | "float8" => Self::Double,
+            "char" | "character" | "nchar" | "native character" | "bpchar" => Self::Char(n(0)),
+            "varchar" | "character varying" | "varying character" | "nvarchar" => {
+                Self::Varchar(n(0))
+            }
+            "text" | "clob" | "citext" => Self::Text,
+            "blob" | "bytea" => Self::Blob,
+            "date" => Self::Date,
+            "time" | "time without time zone" | "time with time zone" | "timetz" => {
+                Self::Time { with_time_zone }
+            }
+            "datetime"
+            | "timestamp"
+            | "timestamp without time zone"
+            | "timestamp with time zone"
+            | "timestamptz" => Self::Timestamp { with_time_zone },
+            "interval" => Self::Interval,
+            "json" | "jsonb" => Self::Json,
+            "uuid" => Self::Uuid,
+            _ => Self::Other(declared.trim().to_string()),
+        }
+    }
+}
+
+// "timestamp(3) without time zone" into ("timestamp without time zone", [3])
+fn split_type_name(declared: &str) -> (String, Vec<u32>) {
+    let mut name = String::new();
+    let mut args = String::new();
+    let mut depth = 0;
+    for c in declared.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if depth > 0 => args.push(c),
+            c => name.push(c),
+        }
+    }
+    let name = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let numbers = args
+        .split(',')
+        .filter_map(|n| n.trim().parse().ok())
+        .collect();
+    (name, numbers)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Index {
+    pub name: String,
+    /// key columns in index order, None where the index is on an expression
+    pub columns: Vec<Option<String>>,
+    pub unique: bool,
+    /// the index behind the primary key
+    pub primary: bool,
+    /// made by the database for a PRIMARY KEY or UNIQUE constraint rather than by CREATE INDEX
+    pub constraint: bool,
+    pub partial: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct ForeignKey {
+    /// sqlite foreign keys have no name
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    /// None when the referenced table is in the same schema
+    pub referenced_schema: Option<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    /// NO ACTION, RESTRICT, CASCADE, SET NULL or SET DEFAULT
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Check {
+    pub name: Option<String>,
+    /// the condition without the CHECK keyword, in the backend's own sql
+    pub expression: String,
+}
+
+#[test]
+fn sql_type_names() {
+    assert_eq!(
+        LogicalType::from_sql_name("timestamp(3) with time zone"),
+        LogicalType::Timestamp {
+            with_time_zone: true
+        }
+    );
+    assert_eq!(
+        LogicalType::from_sql_name("NUMERIC(12, 2)"),
+        LogicalType::Decimal {
+            precision: Some(12),
+            scale: Some(2)
+        }
+    );
+    assert_eq!(
+        LogicalType::from_sql_name("character varying(40)"),
+        LogicalType::Varchar(Some(40))
+    );
+    assert_eq!(
+        LogicalType::from_sql_name("geometry"),
+        LogicalType::Other("geometry".into())
+    );
+}
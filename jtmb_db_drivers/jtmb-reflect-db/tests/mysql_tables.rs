@@ -0,0 +1,95 @@
+// runs against TEST_MYSQL_URL, ignored by default, run with --ignored.
+// mysql ddl commits on its own, so the tables are dropped again
+#![cfg(feature = "mysql")]
+
+use jtmb_reflect_db::SchemaInspector;
+use jtmb_reflect_db::drivers::any::AnyTableInfo;
+use jtmb_reflect_db::drivers::mysql::MySqlType;
+use sqlx::{Connection, Executor};
+
+const SCHEMA: &str = r#"
+CREATE TABLE reflect_accounts (
+    id bigint unsigned NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name varchar(40) NOT NULL,
+    kind enum('asset','liability','equity') NOT NULL DEFAULT 'asset',
+    active tinyint(1) NOT NULL DEFAULT 1,
+    UNIQUE KEY reflect_accounts_name (name(20))
+) COMMENT 'chart of accounts';
+CREATE TABLE reflect_entries (
+    account_id bigint unsigned NOT NULL,
+    line int NOT NULL,
+    amount decimal(12,2) NOT NULL,
+    PRIMARY KEY (account_id, line),
+    CONSTRAINT reflect_entries_account FOREIGN KEY (account_id) REFERENCES reflect_accounts (id) ON DELETE CASCADE,
+    CONSTRAINT reflect_entries_positive CHECK (amount > 0)
+);
+"#;
+
+const DROP: &str = "DROP TABLE IF EXISTS reflect_entries, reflect_accounts";
+
+#[tokio::test]
+#[ignore = "needs TEST_MYSQL_URL"]
+async fn mysql_table_metadata() -> Result<(), anyhow::Error> {
+    let url = std::env::var("TEST_MYSQL_URL")
+        .map_err(|_| anyhow::Error::msg("no mysql to test against, set TEST_MYSQL_URL"))?;
+    let mut c = sqlx::MySqlConnection::connect(&url).await?;
+    c.execute(DROP).await?;
+    c.execute(SCHEMA).await?;
+
+    let mut tx = c.begin().await?;
+    let tables: Vec<_> = tx
+        .get_tables()
+        .await?
+        .into_iter()
+        .filter(|t| t.name.starts_with("reflect_"))
+        .collect();
+    tx.rollback().await?;
+    let accounts = &tables[0];
+    assert_eq!(accounts.comment.as_deref(), Some("chart of accounts"));
+    assert_eq!(accounts.primary_key, ["id"]);
+    assert!(accounts.columns[0].auto_increment);
+    assert_eq!(
+        accounts.columns[0].r#type,
+        MySqlType::Integer {
+            bytes: 8,
+            unsigned: true
+        }
+    );
+    assert_eq!(
+        accounts.columns[2].r#type,
+        MySqlType::Enum(vec!["asset".into(), "liability".into(), "equity".into()])
+    );
+    assert_eq!(accounts.columns[3].r#type, MySqlType::Bool);
+    let name = accounts
+        .indexes
+        .iter()
+        .find(|i| i.name == "reflect_accounts_name")
+        .unwrap();
+    assert!(name.unique && !name.primary);
+    assert_eq!(name.prefix_lengths, [Some(20)]);
+
+    let entries = &tables[1];
+    assert_eq!(entries.primary_key, ["account_id", "line"]);
+    let fk = &entries.foreign_keys[0];
+    assert_eq!(fk.referenced_table, "reflect_accounts");
+    assert_eq!(fk.referenced_columns, ["id"]);
+    assert_eq!(fk.on_delete, "CASCADE");
+    assert_eq!(entries.checks[0].name, "reflect_entries_positive");
+
+    // and the same through Any
+    sqlx::any::install_default_drivers();
+    let mut any = sqlx::AnyConnection::connect(&url).await?;
+    let mut tx = any.begin().await?;
+    let columns = tx.get_columns("reflect_entries").await?;
+    assert_eq!(columns.len(), 3);
+    let found = tx
+        .get_tables()
+        .await?
+        .into_iter()
+        .any(|t| matches!(t, AnyTableInfo::MySql(t) if t.name == "reflect_accounts"));
+    assert!(found);
+    tx.rollback().await?;
+
+    c.execute(DROP).await?;
+    Ok(())
+}
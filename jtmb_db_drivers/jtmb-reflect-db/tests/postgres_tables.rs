@@ -0,0 +1,212 @@
+// runs against TEST_POSTGRES_URL, or a throwaway cluster when initdb and pg_ctl are on the path.
+// ignored by default, run with --ignored. fails when neither works, initdb refuses to run as root for one
+#![cfg(feature = "postgresql")]
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use jtmb_reflect_db::SchemaInspector;
+use jtmb_reflect_db::drivers::postgres::{ConstraintKind, TypeKind};
+use jtmb_reflect_db::schema::LogicalType;
+use sqlx::{Connection, Executor};
+
+struct LocalPostgres {
+    url: String,
+    dir: Option<PathBuf>,
+}
+
+impl LocalPostgres {
+    fn start() -> Option<Self> {
+        if let Ok(url) = std::env::var("TEST_POSTGRES_URL") {
+            return Some(Self { url, dir: None });
+        }
+        let dir = std::env::temp_dir().join(format!("reflect_pg_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let quiet = |c: &mut Command| {
+            c.stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        };
+        let mut initdb = Command::new("initdb");
+        initdb
+            .arg("-D")
+            .arg(&dir)
+            .args(["-U", "postgres", "--auth=trust"]);
+        if !quiet(&mut initdb) {
+            let _ = std::fs::remove_dir_all(&dir);
+            return None;
+        }
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .ok()?
+            .local_addr()
+            .ok()?
+            .port();
+        let started = Self {
+            url: format!("postgres://postgres@127.0.0.1:{port}/postgres"),
+            dir: Some(dir.clone()),
+        };
+        let mut pg_ctl = Command::new("pg_ctl");
+        pg_ctl
+            .arg("-D")
+            .arg(&dir)
+            .arg("-l")
+            .arg(dir.join("log"))
+            .arg("-o")
+            .arg(format!(
+                "-p {port} -k {} -c listen_addresses=127.0.0.1",
+                dir.display()
+            ))
+            .args(["-w", "start"]);
+        quiet(&mut pg_ctl).then_some(started)
+    }
+}
+
+impl Drop for LocalPostgres {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = Command::new("pg_ctl")
+                .arg("-D")
+                .arg(dir)
+                .args(["-m", "immediate", "stop"])
+                .stdout(Stdio::null())
+                .status();
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+// all in one transaction that is rolled back, postgres ddl is transactional
+const SCHEMA: &str = r#"
+CREATE SCHEMA reflect_test;
+CREATE TYPE reflect_test.account_kind AS ENUM ('asset', 'liability', 'equity');
+CREATE DOMAIN reflect_test.positive AS numeric(12,2) NOT NULL CHECK (VALUE > 0);
+CREATE TABLE reflect_test.accounts (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    name varchar(40) NOT NULL UNIQUE,
+    kind reflect_test.account_kind NOT NULL DEFAULT 'asset',
+    tags text[]
+);
+COMMENT ON TABLE reflect_test.accounts IS 'chart of accounts';
+CREATE TABLE reflect_test.entries (
+    account_id bigint NOT NULL REFERENCES reflect_test.accounts (id) ON DELETE CASCADE,
+    line integer NOT NULL,
+    amount reflect_test.positive,
+    doubled numeric GENERATED ALWAYS AS (amount * 2) STORED,
+    PRIMARY KEY (account_id, line),
+    CONSTRAINT small CHECK (amount < 1000000)
+);
+CREATE INDEX entries_by_amount ON reflect_test.entries (amount, abs(line)) WHERE amount > 1;
+"#;
+
+#[tokio::test]
+#[ignore = "needs TEST_POSTGRES_URL, or initdb and pg_ctl"]
+async fn postgres_table_metadata() -> Result<(), anyhow::Error> {
+    let pg = LocalPostgres::start()
+        .ok_or_else(|| anyhow::Error::msg("no postgres to test against, set TEST_POSTGRES_URL"))?;
+    let mut c = sqlx::PgConnection::connect(&pg.url).await?;
+    let mut tx = c.begin().await?;
+    tx.execute(SCHEMA).await?;
+
+    let tables: Vec<_> = tx
+        .get_tables()
+        .await?
+        .into_iter()
+        .filter(|t| t.schema == "reflect_test")
+        .collect();
+    assert_eq!(
+        tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+        ["accounts", "entries"]
+    );
+
+    let accounts = &tables[0];
+    assert_eq!(accounts.comment.as_deref(), Some("chart of accounts"));
+    assert_eq!(accounts.primary_key, ["id"]);
+    let id = &accounts.columns[0];
+    assert!(id.is_pk && !id.nullable);
+    assert_eq!(id.identity.as_deref(), Some("ALWAYS"));
+    assert_eq!(accounts.columns[1].r#type.name, "character varying(40)");
+    let kind = &accounts.columns[2];
+    assert_eq!(kind.r#type.schema, "reflect_test");
+    assert_eq!(
+        kind.r#type.kind,
+        TypeKind::Enum(vec!["asset".into(), "liability".into(), "equity".into()])
+    );
+    assert_eq!(
+        kind.default_value.as_deref(),
+        Some("'asset'::reflect_test.account_kind")
+    );
+    let TypeKind::Array(element) = &accounts.columns[3].r#type.kind else {
+        panic!("tags should be an array");
+    };
+    assert_eq!(element.name, "text");
+    assert!(
+        accounts
+            .constraints
+            .iter()
+            .any(|c| c.kind == ConstraintKind::Unique && c.columns == ["name"])
+    );
+
+    let entries = &tables[1];
+    assert_eq!(entries.primary_key, ["account_id", "line"]);
+    let TypeKind::Domain {
+        base,
+        not_null,
+        checks,
+    } = &entries.columns[2].r#type.kind
+    else {
+        panic!("amount should be a domain");
+    };
+    assert_eq!(base.name, "numeric(12,2)");
+    assert!(*not_null);
+    assert_eq!(checks, &["CHECK ((VALUE > (0)::numeric))"]);
+    // postgres writes the expression back with its own casts
+    assert!(entries.columns[3].generated.is_some());
+    assert!(entries.columns[3].default_value.is_none());
+    let small = entries
+        .constraints
+        .iter()
+        .find(|c| c.name == "small")
+        .unwrap();
+    assert_eq!(small.kind, ConstraintKind::Check);
+    let fk = &entries.foreign_keys[0];
+    assert_eq!(
+        (fk.referenced_table.as_str(), fk.on_delete.as_str()),
+        ("accounts", "CASCADE")
+    );
+    let by_amount = entries
+        .indexes
+        .iter()
+        .find(|i| i.name == "entries_by_amount")
+        .unwrap();
+    assert!(by_amount.partial && by_amount.constraint.is_none());
+    assert_eq!(by_amount.columns, [Some("amount".to_string()), None]);
+    let pkey = entries.indexes.iter().find(|i| i.primary).unwrap();
+    assert_eq!(pkey.constraint.as_deref(), Some("entries_pkey"));
+
+    let columns = tx.get_columns("reflect_test.entries").await?;
+    assert_eq!(columns.len(), 4);
+
+    let database = tx.get_metadata().await?;
+    let accounts = database.table("reflect_test", "accounts").unwrap();
+    assert!(accounts.columns[0].auto_increment);
+    assert_eq!(
+        accounts.columns[3].r#type,
+        LogicalType::Array(Box::new(LogicalType::Text))
+    );
+    let entries = database.table("reflect_test", "entries").unwrap();
+    // a domain reads as the type it is built on
+    assert_eq!(
+        entries.columns[2].r#type,
+        LogicalType::Decimal {
+            precision: Some(12),
+            scale: Some(2)
+        }
+    );
+    assert_eq!(
+        entries.checks[0].expression,
+        "(((amount)::numeric < (1000000)::numeric))"
+    );
+    tx.rollback().await?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use jtmb_reflect_db::SchemaInspector;
+use jtmb_reflect_db::render::{self, Dialect};
+use sqlx::{Connection, Executor};
+
+// entries comes first so the ddl has to reorder it behind accounts
+const SCHEMA: &str = r#"
+CREATE TABLE entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id BLOB NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    amount NUMERIC(12,2) NOT NULL DEFAULT 0,
+    memo varchar(40) DEFAULT 'it''s'
+);
+CREATE TABLE accounts (
+    id BLOB PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    parent_id BLOB REFERENCES accounts (id)
+);
+CREATE INDEX entries_by_account ON entries (account_id, amount);
+"#;
+
+#[tokio::test]
+async fn sqlite_ddl_round_trip() -> Result<(), anyhow::Error> {
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:").await?;
+    c.execute(SCHEMA).await?;
+    let mut tx = c.begin().await?;
+    let database = tx.get_metadata().await?;
+    tx.rollback().await?;
+
+    let ddl = render::ddl(&database, Dialect::Sqlite);
+    let mut copy = sqlx::SqliteConnection::connect("sqlite::memory:").await?;
+    copy.execute(ddl.as_str()).await?;
+    let mut tx = copy.begin().await?;
+    let mut again = tx.get_metadata().await?;
+    tx.rollback().await?;
+    // only the order tables are read in differs
+    again.schemas[0].tables.sort_by_key(|t| {
+        database.schemas[0]
+            .tables
+            .iter()
+            .position(|o| o.name == t.name)
+    });
+    assert_eq!(again, database);
+
+    let postgres = render::ddl(&database, Dialect::Postgres);
+    assert!(postgres.contains("\"id\" bigint GENERATED BY DEFAULT AS IDENTITY,"));
+    assert!(postgres.contains("\"amount\" numeric(12,2) NOT NULL DEFAULT 0"));
+    assert!(postgres.find("TABLE \"accounts\"") < postgres.find("TABLE \"entries\""));
+    let mermaid = render::mermaid(&database);
+    assert!(mermaid.contains("entries }o--|| accounts : \"account_id\""));
+    assert!(mermaid.contains("accounts }o--o| accounts : \"parent_id\""));
+    Ok(())
+}
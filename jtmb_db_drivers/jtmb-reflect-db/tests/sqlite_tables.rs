@@ -0,0 +1,105 @@
+use jtmb_reflect_db::SchemaInspector;
+use jtmb_reflect_db::drivers::any::AnyTableInfo;
+use sqlx::{Connection, Executor};
+
+const SCHEMA: &str = r#"
+CREATE TABLE accounts (id BLOB PRIMARY KEY, name TEXT NOT NULL UNIQUE, parent_id BLOB REFERENCES accounts(id) ON DELETE CASCADE) WITHOUT ROWID;
+CREATE TABLE entries (
+    account_id BLOB NOT NULL,
+    line INTEGER NOT NULL,
+    amount INTEGER NOT NULL DEFAULT 0,
+    doubled INTEGER GENERATED ALWAYS AS (amount * 2) STORED,
+    PRIMARY KEY (line, account_id),
+    FOREIGN KEY (account_id) REFERENCES accounts
+) STRICT;
+CREATE INDEX entries_by_amount ON entries (amount, abs(amount)) WHERE amount > 0;
+"#;
+
+#[tokio::test]
+async fn sqlite_table_metadata() -> Result<(), anyhow::Error> {
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:").await?;
+    c.execute(SCHEMA).await?;
+    let mut tx = c.begin().await?;
+    let tables = tx.get_tables().await?;
+    assert_eq!(
+        tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(),
+        ["accounts", "entries"]
+    );
+
+    let accounts = &tables[0];
+    assert!(accounts.without_rowid && !accounts.strict);
+    assert_eq!(accounts.primary_key, ["id"]);
+    let unique = accounts.indexes.iter().find(|i| i.origin == "u").unwrap();
+    assert!(unique.unique && unique.sql.is_none());
+    assert_eq!(unique.columns, [Some("name".to_string())]);
+    assert_eq!(accounts.foreign_keys[0].referenced_table, "accounts");
+    assert_eq!(accounts.foreign_keys[0].on_delete, "CASCADE");
+
+    let entries = &tables[1];
+    assert!(entries.strict && !entries.without_rowid);
+    assert_eq!(entries.primary_key, ["line", "account_id"]);
+    assert_eq!(entries.columns.len(), 4);
+    assert_eq!(entries.columns[2].default_value.as_deref(), Some("0"));
+    assert_eq!(entries.columns[3].hidden, 3);
+    let by_amount = entries
+        .indexes
+        .iter()
+        .find(|i| i.name == "entries_by_amount")
+        .unwrap();
+    assert!(by_amount.partial && by_amount.sql.is_some());
+    assert_eq!(by_amount.columns, [Some("amount".to_string()), None]);
+    assert_eq!(entries.foreign_keys[0].columns, ["account_id"]);
+    assert_eq!(entries.foreign_keys[0].referenced_columns, [None]);
+    assert!(
+        entries
+            .sql
+            .as_deref()
+            .unwrap()
+            .starts_with("CREATE TABLE entries")
+    );
+    tx.rollback().await?;
+
+    // the Any inspector shares the sqlite queries
+    sqlx::any::install_default_drivers();
+    let mut c = sqlx::AnyConnection::connect("sqlite::memory:").await?;
+    c.execute(SCHEMA).await?;
+    let mut tx = c.begin().await?;
+    // the only variant when built without the server backends
+    #[allow(irrefutable_let_patterns)]
+    let AnyTableInfo::Sqlite(entries) = tx.get_tables().await?.remove(1) else {
+        panic!("sqlite tables come back as sqlite");
+    };
+    assert_eq!(entries.primary_key, ["line", "account_id"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_neutral_schema() -> Result<(), anyhow::Error> {
+    use jtmb_reflect_db::schema::LogicalType;
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:").await?;
+    c.execute(SCHEMA).await?;
+    let mut tx = c.begin().await?;
+    let database = tx.get_metadata().await?;
+    tx.rollback().await?;
+
+    let entries = database.table("main", "entries").unwrap();
+    assert_eq!(
+        entries.columns[1].r#type,
+        LogicalType::Integer {
+            bytes: 8,
+            unsigned: false
+        }
+    );
+    assert_eq!(entries.columns[0].r#type, LogicalType::Blob);
+    assert_eq!(entries.columns[3].generated.as_deref(), Some(""));
+    // REFERENCES accounts names no column, it is the primary key of accounts
+    assert_eq!(entries.foreign_keys[0].referenced_columns, ["id"]);
+
+    #[cfg(all(feature = "serialize", feature = "deserialize"))]
+    {
+        let json = serde_json::to_string(&database)?;
+        let back: jtmb_reflect_db::schema::Database = serde_json::from_str(&json)?;
+        assert_eq!(back, database);
+    }
+    Ok(())
+}
@@ -1,89 +1,278 @@
-use std::path::PathBuf;
+// #[derive(Entity)], table metadata and crud for a struct that maps one to one onto a table.
+// the queries are built here for both placeholder styles and checked by the database at runtime,
+// so nothing needs DATABASE_URL at compile time and one struct works on every enabled backend.
 
-// use proc_macro::{Ident, TokenStream};
+use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{Fields, parse::Parse, parse_macro_input};
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, Type, parse_macro_input, spanned::Spanned};
 
-const SQL_ROOT: &str = "src/scripts";
+/// implements `sql::core::Table`, `sql::core::GetId` and async
+/// `get_one/get_many/insert/update/delete` for a struct with named fields.
+///
+/// ```ignore
+/// #[derive(sqlx::FromRow, sql::core::Entity)]
+/// #[table = "widgets"]
+/// struct Widget {
+///     id: Id<Widget>,
+///     name: String,
+/// }
+/// ```
+/// the id is the field named `id`, or the one marked `#[id]`. it must be a `sql::core::Id<Self>`.
+/// every other field is a column of the same name
+#[proc_macro_derive(Entity, attributes(table, id))]
+pub fn derive_entity(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match entity(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn table_name(input: &DeriveInput) -> syn::Result<LitStr> {
+    let mut table = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("table")) {
+        if table.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "#[table] given more than once",
+            ));
+        }
+        let value = &attr.meta.require_name_value()?.value;
+        match value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) if !s.value().is_empty() => table = Some(s.clone()),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    value,
+                    "expected a table name, ex: #[table = \"users\"]",
+                ));
+            }
+        }
+    }
+    table.ok_or_else(|| {
+        syn::Error::new(
+            input.ident.span(),
+            "#[derive(Entity)] needs the table name, ex: #[table = \"users\"]",
+        )
+    })
+}
 
-fn sqlite_scripts_path() -> PathBuf {
-    std::path::Path::new(SQL_ROOT).join("sqlite")
+struct Column<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
 }
 
-#[proc_macro_attribute]
-pub fn sqlite_impl(
-    attributes: proc_macro::TokenStream,
-    item: proc_macro::TokenStream,
-) -> proc_macro::TokenStream {
-    // let input = proc_macro2::TokenStream::from(input);
-    let input = parse_macro_input!(item as syn::ItemStruct);
-    let ty = &input.ident;
-    let ty_lowercase = ty.to_string().to_ascii_lowercase();
-
-    let ty_sql_path = sqlite_scripts_path().join(ty.to_string().to_lowercase());
-    let ty_get_one_sql_path = ty_sql_path
-        .join("get_one.sql")
-        .to_string_lossy()
-        .to_string();
-    let ty_get_many = ty_sql_path
-        .join("get_many.sql")
-        .to_string_lossy()
-        .to_string();
-    let ty_insert = ty_sql_path.join("insert.sql").to_string_lossy().to_string();
-    let ty_insert_many = ty_sql_path
-        .join("insert_many.sql")
-        .to_string_lossy()
-        .to_string();
-    let ty_insert_with_returning = ty_sql_path
-        .join("insert_with_returning.sql")
-        .to_string_lossy()
-        .to_string();
-    let ty_update = ty_sql_path.join("update.sql").to_string_lossy().to_string();
-
-    // the update query_as
-    let mut query_as = format!("sqlx_query_as!({ty},{ty_insert}");
-    let fields = match &input.fields {
-        Fields::Named(f) => &f.named,
+fn column(f: &syn::Field) -> Column<'_> {
+    Column {
+        ident: f.ident.as_ref().unwrap(),
+        ty: &f.ty,
+    }
+}
+
+fn columns(input: &DeriveInput) -> syn::Result<(Column<'_>, Vec<Column<'_>>)> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(f) => &f.named,
+            other => {
+                return Err(syn::Error::new(
+                    other.span(),
+                    "#[derive(Entity)] only supports structs with named fields",
+                ));
+            }
+        },
         _ => {
-            return quote! {compile_error!("struct #ty only named fields are supported")}.into();
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "#[derive(Entity)] only supports structs with named fields",
+            ));
         }
     };
-    for field in fields {
-        query_as.push_str(&format!(",t.{}", field.ident.as_ref().unwrap()));
+    let marked: Vec<_> = fields
+        .iter()
+        .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("id")))
+        .collect();
+    if let Some(second) = marked.get(1) {
+        return Err(syn::Error::new_spanned(
+            second,
+            "only one field can be marked #[id]",
+        ));
+    }
+    let id = match marked.first() {
+        Some(f) => *f,
+        None => fields
+            .iter()
+            .find(|f| f.ident.as_ref().is_some_and(|i| i == "id"))
+            .ok_or_else(|| {
+                syn::Error::new(
+                    input.ident.span(),
+                    "#[derive(Entity)] needs a field named `id` or a field marked #[id]",
+                )
+            })?,
+    };
+    let rest = fields
+        .iter()
+        .filter(|f| f.ident != id.ident)
+        .map(column)
+        .collect();
+    Ok((column(id), rest))
+}
+
+/// the same statement with `?` and with `$n` placeholders
+fn both_styles(build: impl Fn(&dyn Fn(usize) -> String) -> String) -> TokenStream {
+    let question = build(&|_| "?".to_string());
+    let numbered = build(&|n| format!("${n}"));
+    quote! {
+        if <DB as ::sql::core::EntityBackend>::NUMBERED_PLACEHOLDERS { #numbered } else { #question }
+    }
+}
+
+fn entity(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let table = table_name(input)?;
+    let (id, rest) = columns(input)?;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "#[derive(Entity)] does not support generic structs",
+        ));
     }
-    query_as.push_str(")");
+    let ty = &input.ident;
+
+    let table_name = table.value();
+    let id_column = id.ident.to_string();
+    let id_field = id.ident;
+    let names: Vec<String> = rest.iter().map(|c| c.ident.to_string()).collect();
+    let all: Vec<&str> = std::iter::once(id_column.as_str())
+        .chain(names.iter().map(|n| n.as_str()))
+        .collect();
+    let select = all.join(", ");
+
+    let get_one = both_styles(|p| {
+        format!(
+            "SELECT {select} FROM {table_name} WHERE {id_column} = {}",
+            p(1)
+        )
+    });
+    let get_many = format!("SELECT {select} FROM {table_name}");
+    let insert = both_styles(|p| {
+        let values: Vec<String> = (1..=all.len()).map(p).collect();
+        format!(
+            "INSERT INTO {table_name} ({select}) VALUES ({})",
+            values.join(", ")
+        )
+    });
+    let update = both_styles(|p| {
+        let set: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| format!("{n} = {}", p(i + 1)))
+            .collect();
+        format!(
+            "UPDATE {table_name} SET {} WHERE {id_column} = {}",
+            set.join(", "),
+            p(names.len() + 1)
+        )
+    });
+    let delete = both_styles(|p| format!("DELETE FROM {table_name} WHERE {id_column} = {}", p(1)));
+
+    let fields: Vec<&Ident> = rest.iter().map(|c| c.ident).collect();
+    let field_types: Vec<&Type> = rest.iter().map(|c| c.ty).collect();
+    let sqlx = quote!(::sql::__private::sqlx);
+    let id_ty = quote!(::sql::core::Id<Self>);
+    let bounds = quote! {
+        DB: ::sql::core::EntityBackend,
+        E: #sqlx::Executor<'c, Database = DB>,
+        for<'q> <DB as #sqlx::Database>::Arguments<'q>: #sqlx::IntoArguments<'q, DB>,
+        #id_ty: for<'q> #sqlx::Encode<'q, DB> + #sqlx::Type<DB>,
+    };
+    let row_bounds = quote! {
+        Self: for<'r> #sqlx::FromRow<'r, <DB as #sqlx::Database>::Row> + Send + Unpin,
+    };
+    let field_bounds = quote! {
+        #( #field_types: for<'q> #sqlx::Encode<'q, DB> + #sqlx::Type<DB>, )*
+    };
+    // with no columns besides the id there is nothing to set
+    let update_body = if fields.is_empty() {
+        quote! {
+            Self::get_one(executor, ::sql::core::GetId::id(self)).await.map(|row| row.is_some())
+        }
+    } else {
+        quote! {
+            let sql = #update;
+            let result = #sqlx::query::<DB>(sql)
+                #( .bind(&self.#fields) )*
+                .bind(::sql::core::GetId::id(self).clone())
+                .execute(executor)
+                .await?;
+            Ok(<DB as ::sql::core::EntityBackend>::rows_affected(&result) > 0)
+        }
+    };
+    Ok(quote! {
+        impl ::sql::core::Table for #ty {
+            const TABLE_NAME: &'static str = #table;
+        }
 
-    let e = quote! {
-        #input
+        impl ::sql::core::GetId for #ty {
+            fn id(&self) -> &::sql::core::Id<Self>
+            where
+                Self: Sized,
+            {
+                &self.#id_field
+            }
+        }
 
         impl #ty {
-            pub async fn async_sqlite_get_one<'a,E>(executor:E,id:crate::Id<#ty>) -> Result<#ty,Box<dyn std::error::Error>>
-                where E: sqlx::SqliteExecutor<'a> {
-                let t = sqlx::query_file_as!(#ty,#ty_get_one_sql_path,id).fetch_one(executor).await?;
-                Ok(t)
+            pub async fn get_one<'c, E, DB>(executor: E, id: &#id_ty) -> Result<Option<Self>, #sqlx::Error>
+            where
+                #bounds
+                #row_bounds
+            {
+                let sql = #get_one;
+                #sqlx::query_as::<DB, Self>(sql).bind(id.clone()).fetch_optional(executor).await
             }
 
-            pub async fn async_sqlite_insert<'a,E>(executor:E,#ty_lowercase: &#ty) -> Result<#ty,Box<dyn std::error::Error>>
-                where E: sqlx::SqliteExecutor<'a>,
-                #ty: GetId + Sized {
-                let id = #ty.get_id();
-                // let t = sqlx::query_file_as!(#ty,#ty_insert,id).fetch_many(executor).await?;
-                // Ok(t)
-                todo!()
+            pub async fn get_many<'c, E, DB>(executor: E) -> Result<Vec<Self>, #sqlx::Error>
+            where
+                #bounds
+                #row_bounds
+            {
+                #sqlx::query_as::<DB, Self>(#get_many).fetch_all(executor).await
             }
-            // pub async fn async_sqlite_insert_with_returning<'a,E>(executor:E,id:crate::Id<#ty>) -> Result<#ty,Box<dyn std::error::Error>>
-            //     where E: sqlx::SqliteExecutor<'a> {
-            //     let t = sqlx::query_file_as!(#ty,#ty_insert_with_returning,id).fetch_many(executor).await?;
-            //     Ok(t)
-            // }
-            // pub async fn async_sqlite_update<'a,E>(executor:E,id:crate::Id<#ty>) -> Result<#ty,Box<dyn std::error::Error>>
-            //     where E: sqlx::SqliteExecutor<'a> {
-            //     let t = sqlx::query_file_as!(#ty,#ty_update,id).fetch_many(executor).await?;
-            //     Ok(t)
-            // }
 
-        }
+            pub async fn insert<'c, E, DB>(&self, executor: E) -> Result<(), #sqlx::Error>
+            where
+                #bounds
+                #field_bounds
+            {
+                let sql = #insert;
+                #sqlx::query::<DB>(sql)
+                    .bind(::sql::core::GetId::id(self).clone())
+                    #( .bind(&self.#fields) )*
+                    .execute(executor)
+                    .await?;
+                Ok(())
+            }
 
-    };
-    e.into()
+            /// returns false if there is no row with this id
+            pub async fn update<'c, E, DB>(&self, executor: E) -> Result<bool, #sqlx::Error>
+            where
+                #bounds
+                #field_bounds
+                #row_bounds
+            {
+                #update_body
+            }
+
+            /// returns false if there was no row with this id
+            pub async fn delete<'c, E, DB>(executor: E, id: &#id_ty) -> Result<bool, #sqlx::Error>
+            where
+                #bounds
+            {
+                let sql = #delete;
+                let result = #sqlx::query::<DB>(sql).bind(id.clone()).execute(executor).await?;
+                Ok(<DB as ::sql::core::EntityBackend>::rows_affected(&result) > 0)
+            }
+        }
+    })
 }
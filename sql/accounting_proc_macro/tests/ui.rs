@@ -0,0 +1,6 @@
+// error messages from #[derive(Entity)], regenerate the .stderr files with TRYBUILD=overwrite
+#[test]
+fn entity_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
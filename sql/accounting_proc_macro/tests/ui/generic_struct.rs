@@ -0,0 +1,10 @@
+use sql_proc_macro::Entity;
+
+#[derive(Entity)]
+#[table = "widgets"]
+struct Widget<T> {
+    id: u64,
+    value: T,
+}
+
+fn main() {}
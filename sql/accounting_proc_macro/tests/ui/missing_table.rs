@@ -0,0 +1,8 @@
+use sql_proc_macro::Entity;
+
+#[derive(Entity)]
+struct Widget {
+    id: u64,
+}
+
+fn main() {}
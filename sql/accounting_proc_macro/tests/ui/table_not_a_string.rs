@@ -0,0 +1,9 @@
+use sql_proc_macro::Entity;
+
+#[derive(Entity)]
+#[table = widgets]
+struct Widget {
+    id: u64,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use sql_proc_macro::Entity;
+
+#[derive(Entity)]
+#[table = "widgets"]
+struct Widget(u64);
+
+fn main() {}
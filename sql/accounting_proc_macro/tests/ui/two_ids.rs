@@ -0,0 +1,12 @@
+use sql_proc_macro::Entity;
+
+#[derive(Entity)]
+#[table = "widgets"]
+struct Widget {
+    #[id]
+    key: u64,
+    #[id]
+    other: u64,
+}
+
+fn main() {}
@@ -1,4 +0,0 @@
--- Add migration script here
-CREATE TABLE users(
-    id BLOB PRIMARY KEY NOT NULL UNIQUE
-)
\ No newline at end of file
@@ -0,0 +1,341 @@
+// copies a tenant from one database to another, usually sqlite to postgres once a user outgrows
+// a file. the target schema is brought up first, then every table in desired_schema is streamed
+// across in the order it is listed there, parents before the tables that point at them. a row
+// of a table that points at itself, a sub-account, waits until the row it points at is in.
+// each table is checked afterwards by row count and a checksum of its rows in primary key order,
+// computed the same way on both sides, and nothing is committed unless they match.
+
+use std::collections::HashSet;
+
+use barrel::types::{BaseType, Type};
+use futures_util::TryStreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::{AnyConnection, Connection, Row, any::AnyRow};
+
+use crate::reconcile::DesiredTable;
+use crate::runner::Placeholders;
+use crate::user_multitenant;
+
+/// what was copied for one table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableCopy {
+    pub table: &'static str,
+    pub rows: u64,
+    /// sha256 of the rows in primary key order, hex
+    pub checksum: String,
+}
+
+impl std::fmt::Display for TableCopy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} rows {}", self.table, self.rows, self.checksum)
+    }
+}
+
+// how a column is read and written, every backend can decode these through sqlx::Any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    BigInt,
+    Text,
+    Bytes,
+}
+
+fn kind(column: &str, ty: &Type) -> Result<Kind, anyhow::Error> {
+    Ok(match &ty.inner {
+        BaseType::Integer => Kind::Int,
+        BaseType::Custom("BIGINT") => Kind::BigInt,
+        BaseType::Text | BaseType::Varchar(_) | BaseType::Char(_) => Kind::Text,
+        BaseType::Binary => Kind::Bytes,
+        // mysql byte strings are custom types, see BackendName::binary
+        BaseType::Custom(c) if c.starts_with("VARBINARY") || c.ends_with("BLOB") => Kind::Bytes,
+        other => anyhow::bail!("copy does not know how to move {column} ({other:?})"),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Value {
+    Null,
+    Int(i64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    fn read(row: &AnyRow, i: usize, kind: Kind) -> Result<Self, sqlx::Error> {
+        let value = match kind {
+            Kind::Int => row
+                .try_get::<Option<i32>, _>(i)?
+                .map(|v| Self::Int(v.into())),
+            Kind::BigInt => row.try_get::<Option<i64>, _>(i)?.map(Self::Int),
+            Kind::Text => row.try_get::<Option<String>, _>(i)?.map(Self::Text),
+            Kind::Bytes => row.try_get::<Option<Vec<u8>>, _>(i)?.map(Self::Bytes),
+        };
+        Ok(value.unwrap_or(Self::Null))
+    }
+    // a tag and a length ahead of every value, so no two rows hash the same by accident
+    fn hash(&self, hasher: &mut Sha256) {
+        let (tag, bytes): (u8, &[u8]) = match self {
+            Self::Null => (0, &[]),
+            Self::Int(v) => {
+                hasher.update([1]);
+                hasher.update(v.to_be_bytes());
+                return;
+            }
+            Self::Text(v) => (2, v.as_bytes()),
+            Self::Bytes(v) => (3, v),
+        };
+        hasher.update([tag]);
+        hasher.update((bytes.len() as u64).to_be_bytes());
+        hasher.update(bytes);
+    }
+}
+
+fn bind<'q>(
+    query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    value: Value,
+    kind: Kind,
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+    match (value, kind) {
+        (Value::Null, Kind::Int) => query.bind(None::<i32>),
+        (Value::Null, Kind::BigInt) => query.bind(None::<i64>),
+        (Value::Null, Kind::Text) => query.bind(None::<String>),
+        (Value::Null, Kind::Bytes) => query.bind(None::<Vec<u8>>),
+        (Value::Int(v), Kind::Int) => query.bind(v as i32),
+        (Value::Int(v), _) => query.bind(v),
+        (Value::Text(v), _) => query.bind(v),
+        (Value::Bytes(v), _) => query.bind(v),
+    }
+}
+
+struct Plan {
+    table: &'static str,
+    columns: Vec<&'static str>,
+    kinds: Vec<Kind>,
+    // the column pointing back into this table, and the column it points at
+    parent: Option<(usize, usize)>,
+}
+
+impl Plan {
+    fn new(table: &DesiredTable) -> Result<Self, anyhow::Error> {
+        let kinds = table
+            .columns
+            .iter()
+            .map(|(name, ty)| kind(name, ty))
+            .collect::<Result<_, _>>()?;
+        let columns: Vec<&'static str> = table.columns.iter().map(|(name, _)| *name).collect();
+        let position = |name| {
+            columns
+                .iter()
+                .position(|c| *c == name)
+                .ok_or_else(|| anyhow::anyhow!("{} has no column {name}", table.name))
+        };
+        let parent = match table.foreign_keys.iter().find(|k| k.table == table.name) {
+            Some(key) => Some((position(key.column)?, position(key.references)?)),
+            None => None,
+        };
+        Ok(Self {
+            table: table.name,
+            columns,
+            kinds,
+            parent,
+        })
+    }
+    // the first column is the primary key of every tenant table
+    fn select(&self) -> String {
+        format!(
+            "SELECT {} FROM {} ORDER BY {}",
+            self.columns.join(", "),
+            self.table,
+            self.columns[0]
+        )
+    }
+    fn insert<P: Placeholders>(&self) -> String {
+        let binds: Vec<String> = (1..=self.columns.len()).map(P::bind).collect();
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.table,
+            self.columns.join(", "),
+            binds.join(", ")
+        )
+    }
+    fn row(&self, row: &AnyRow) -> Result<Vec<Value>, sqlx::Error> {
+        self.kinds
+            .iter()
+            .enumerate()
+            .map(|(i, kind)| Value::read(row, i, *kind))
+            .collect()
+    }
+    async fn copy_row(
+        &self,
+        insert: &str,
+        values: Vec<Value>,
+        copied_keys: &mut HashSet<Value>,
+        target: &mut AnyConnection,
+    ) -> Result<(), anyhow::Error> {
+        if let Some((_, key)) = self.parent {
+            copied_keys.insert(values[key].clone());
+        }
+        let mut query = sqlx::query(insert);
+        for (value, kind) in values.into_iter().zip(&self.kinds) {
+            query = bind(query, value, *kind);
+        }
+        query.execute(target).await?;
+        Ok(())
+    }
+    /// row count and checksum of the table as it is now
+    async fn summarize(&self, c: &mut AnyConnection) -> Result<TableCopy, anyhow::Error> {
+        let select = self.select();
+        let mut rows = sqlx::query(&select).fetch(c);
+        let mut hasher = Sha256::new();
+        let mut count = 0;
+        while let Some(row) = rows.try_next().await? {
+            for value in self.row(&row)? {
+                value.hash(&mut hasher);
+            }
+            count += 1;
+        }
+        Ok(self.report(count, hasher))
+    }
+    fn report(&self, rows: u64, hasher: Sha256) -> TableCopy {
+        let checksum = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        TableCopy {
+            table: self.table,
+            rows,
+            checksum,
+        }
+    }
+}
+
+/// copies every tenant table from `source` to `target`, whose schema is brought up first.
+/// the source has to be at the latest tenant migration and the target empty.
+/// the target is written in one transaction, committed only once every table checks out
+pub async fn copy_tenant<TargetBackend>(
+    source: &mut AnyConnection,
+    target: &mut AnyConnection,
+) -> Result<Vec<TableCopy>, anyhow::Error>
+where
+    TargetBackend: barrel::backend::SqlGenerator + Placeholders,
+{
+    let latest = crate::runner::latest(user_multitenant::MIGRATIONS);
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_migrations")
+        .fetch_one(&mut *source)
+        .await?;
+    if version != Some(latest) {
+        anyhow::bail!(
+            "the source is at tenant schema version {}, migrate it to {latest} first",
+            version.unwrap_or(0)
+        );
+    }
+    let plans = user_multitenant::desired_schema(TargetBackend::BACKEND)
+        .iter()
+        .map(Plan::new)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // a read transaction keeps the source still while it is copied
+    let mut read = source.begin().await?;
+    let mut tx = target.begin().await?;
+    user_multitenant::bring_up::<TargetBackend, _>(&mut tx).await?;
+    for plan in &plans {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", plan.table))
+            .fetch_one(&mut *tx)
+            .await?;
+        if count > 0 {
+            anyhow::bail!(
+                "the target already has rows in {}, copy into an empty tenant",
+                plan.table
+            );
+        }
+    }
+
+    let mut copied = Vec::with_capacity(plans.len());
+    for plan in &plans {
+        let (select, insert) = (plan.select(), plan.insert::<TargetBackend>());
+        let mut rows = sqlx::query(&select).fetch(&mut *read);
+        let mut hasher = Sha256::new();
+        let mut count = 0;
+        let (mut copied_keys, mut waiting) = (HashSet::new(), Vec::new());
+        while let Some(row) = rows.try_next().await? {
+            let values = plan.row(&row)?;
+            for value in &values {
+                value.hash(&mut hasher);
+            }
+            count += 1;
+            match plan.parent {
+                Some((column, _)) if !(values[column] == Value::Null || copied_keys.contains(&values[column])) => {
+                    waiting.push(values)
+                }
+                _ => plan.copy_row(&insert, values, &mut copied_keys, &mut tx).await?,
+            }
+        }
+        drop(rows);
+        // rows go in once their parent is, a pass that moves none of them means a parent is missing
+        while !waiting.is_empty() {
+            let (ready, rest): (Vec<_>, Vec<_>) = waiting.into_iter().partition(|values: &Vec<Value>| {
+                plan.parent.is_some_and(|(column, _)| copied_keys.contains(&values[column]))
+            });
+            if ready.is_empty() {
+                anyhow::bail!("{} has {} rows pointing at rows it does not have", plan.table, rest.len());
+            }
+            for values in ready {
+                plan.copy_row(&insert, values, &mut copied_keys, &mut tx).await?;
+            }
+            waiting = rest;
+        }
+        let expected = plan.report(count, hasher);
+        let found = plan.summarize(&mut tx).await?;
+        if found != expected {
+            anyhow::bail!(
+                "{} did not copy cleanly, read {expected}, wrote {found}",
+                plan.table
+            );
+        }
+        copied.push(found);
+    }
+    read.rollback().await?;
+    tx.commit().await?;
+    Ok(copied)
+}
+
+/// copy_tenant between two connection urls. the target database has to exist,
+/// for sqlite add ?mode=rwc to create the file
+#[cfg_attr(
+    not(any(feature = "sqlite", feature = "postgresql", feature = "mysql")),
+    allow(unused_imports, unused_mut) // only the error is left without a backend
+)]
+pub async fn copy_tenant_url(
+    source_url: &str,
+    target_url: &str,
+) -> Result<Vec<TableCopy>, anyhow::Error> {
+    use sqlx::Database;
+    sqlx::any::install_default_drivers();
+    let mut source = AnyConnection::connect(source_url).await?;
+    let mut target = AnyConnection::connect(target_url).await?;
+    let copied = match target.backend_name() {
+        #[cfg(feature = "sqlite")]
+        sqlx::Sqlite::NAME => {
+            copy_tenant::<barrel::backend::Sqlite>(&mut source, &mut target).await
+        }
+        #[cfg(feature = "postgresql")]
+        sqlx::Postgres::NAME => copy_tenant::<barrel::backend::Pg>(&mut source, &mut target).await,
+        #[cfg(feature = "mysql")]
+        sqlx::MySql::NAME => copy_tenant::<barrel::backend::MySql>(&mut source, &mut target).await,
+        other => Err(anyhow::anyhow!("this build can not copy into {other}")),
+    };
+    source.close().await.ok();
+    target.close().await.ok();
+    copied
+}
+
+#[test]
+fn every_tenant_column_can_be_copied() {
+    use crate::BackendName;
+    for backend in [BackendName::Sqlite, BackendName::Postgresql, BackendName::Mysql] {
+        for table in user_multitenant::desired_schema(backend) {
+            Plan::new(&table).unwrap();
+        }
+    }
+}
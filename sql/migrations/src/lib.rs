@@ -1,41 +1,98 @@
-use std::sync::Arc;
-
 use sqlx::Transaction;
 
-use crate::reflect::SchemaInspector;
+use crate::reconcile::DesiredTable;
+use crate::runner::{AppliedRow, Placeholders, VersionedMigration};
 
+pub mod copy;
+pub mod reconcile;
 pub mod reflect;
+pub mod runner;
 pub mod user_multitenant;
 pub mod v1_initdb;
+pub mod v2_tenant_directory;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackendName {
     Postgresql,
     Mysql,
     Sqlite,
 }
 
-// NOTE: advanced logic based on schema versions may not be supported
-// you may have to call down and up yourself
+impl BackendName {
+    /// a byte string column. barrel renders binary() as BYTEA on mysql too, which mysql does
+    /// not have, so mysql gets `mysql` instead. sqlite and postgres keep the sql they
+    /// always had and with it their migration checksums
+    pub fn binary(self, mysql: &'static str) -> barrel::types::Type {
+        match self {
+            Self::Mysql => barrel::types::custom(mysql),
+            Self::Postgresql | Self::Sqlite => barrel::types::binary(),
+        }
+    }
+}
+
+/// mysql type of a uuid column
+pub const MYSQL_ID: &str = "VARBINARY(16)";
+
+/// every primary database migration, oldest first
+pub const MIGRATIONS: &[VersionedMigration] =
+    &[v1_initdb::MIGRATION, v2_tenant_directory::MIGRATION];
+
+/// the primary database as of the latest migration, for reconcile. made of the tables the
+/// migrations create, so the two can not drift apart
+pub fn desired_schema(backend: BackendName) -> Vec<DesiredTable> {
+    vec![
+        v1_initdb::users(backend),
+        v2_tenant_directory::tenant_directory(backend),
+    ]
+}
 
-// pub async fn migrate() {
-//     // version?
-// }
-/// calls up for every module.
-/// uses reflection and migration code to make the database consistent. there may not be a "down" from this
+/// applies every pending migration of the primary database.
+/// use runner::up_to and runner::down_to to move to a specific version
 pub async fn bring_up<'exec, BarrelBackend, SqlxDatabase>(
     tx: &mut Transaction<'_, SqlxDatabase>,
 ) -> Result<(), anyhow::Error>
 where
     SqlxDatabase: sqlx::Database,
-    for<'c> sqlx::Transaction<'c, SqlxDatabase>: SchemaInspector<SqlxDatabase>,
     for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
         sqlx::IntoArguments<'q, SqlxDatabase>,
     for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
         sqlx::Executor<'c, Database = SqlxDatabase>,
-    // Exec: sqlx::Executor<'exec,Database = SqlxDatabase>,
-    // Exec: sqlx::Executor<'exec,Database = SqlxDatabase>,
-    BarrelBackend: barrel::backend::SqlGenerator,
+    for<'r> AppliedRow: sqlx::FromRow<'r, <SqlxDatabase as sqlx::Database>::Row>,
+    for<'q> String: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    for<'q> i64: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
 {
-    v1_initdb::up::<BarrelBackend, _>(&mut *tx).await?;
+    runner::up_to::<BarrelBackend, SqlxDatabase>(tx, MIGRATIONS, runner::latest(MIGRATIONS))
+        .await?;
     Ok(())
 }
+
+#[cfg(all(test, feature = "sqlite"))]
+#[tokio::test]
+async fn desired_schema_matches_migrations() {
+    reconcile::assert_desired_matches(MIGRATIONS, &desired_schema(BackendName::Sqlite)).await;
+}
+
+// no mysql server in ci, so at least keep the ddl to types mysql has
+#[cfg(all(test, feature = "mysql"))]
+#[test]
+fn mysql_ddl_has_no_bytea() {
+    for migration in MIGRATIONS.iter().chain(user_multitenant::MIGRATIONS) {
+        for sql in migration
+            .up_sql::<barrel::backend::MySql>()
+            .into_iter()
+            .chain(migration.down_sql::<barrel::backend::MySql>())
+        {
+            assert!(!sql.contains("BYTEA"), "{} renders {sql}", migration.name);
+        }
+    }
+    let v5 = &user_multitenant::v5_passkey_metadata::MIGRATION;
+    let down = v5.down_sql::<barrel::backend::MySql>();
+    assert!(
+        down[0].starts_with("DROP INDEX passkeys_credential_id ON passkeys"),
+        "{}",
+        down[0]
+    );
+    let v3 = user_multitenant::v3_passkeys::MIGRATION.up_sql::<barrel::backend::MySql>();
+    assert!(v3[0].contains("VARBINARY(1023)"), "{}", v3[0]);
+}
@@ -1,33 +1,139 @@
 use std::str::FromStr;
 
+use sql_migrations::BackendName;
+use sql_migrations::reconcile::{self, DesiredTable};
+use sql_migrations::runner::{self, Placeholders, VersionedMigration};
 use sqlx::{ConnectOptions, Connection, Database};
 
+const USAGE: &str = "usage: sql_migrations <url> [status | up [version] | down <version> | reconcile [--dry-run] | copy <target url>] [--tenant]";
+
+/// what runs inside the migration transaction
+enum Command {
+    Status,
+    Up(Option<i64>),
+    Down(i64),
+    Reconcile { dry_run: bool },
+}
+
+enum Invocation {
+    Migrate { command: Command, tenant: bool },
+    /// copies the tenant at <url> into another database, always a tenant. it opens its own
+    /// connections and transactions
+    Copy { target_url: String },
+}
+
+fn parse_args() -> Result<(String, Invocation), anyhow::Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let tenant = args.iter().any(|a| a == "--tenant");
+    let mut args = args.into_iter().filter(|a| a != "--tenant");
+    let url = args.next().ok_or_else(|| anyhow::Error::msg(USAGE))?;
+    let version = |v: Option<String>| -> Result<Option<i64>, anyhow::Error> {
+        v.map(|v| {
+            v.parse::<i64>()
+                .map_err(|_| anyhow::anyhow!("bad version {v}\n{USAGE}"))
+        })
+        .transpose()
+    };
+    let command = match args.next().as_deref() {
+        None | Some("up") => Command::Up(version(args.next())?),
+        Some("status") => Command::Status,
+        Some("reconcile") => Command::Reconcile {
+            dry_run: args.any(|a| a == "--dry-run"),
+        },
+        Some("copy") => {
+            let target_url = args.next().ok_or_else(|| anyhow::Error::msg(USAGE))?;
+            return Ok((url, Invocation::Copy { target_url }));
+        }
+        Some("down") => {
+            Command::Down(version(args.next())?.ok_or_else(|| anyhow::Error::msg(USAGE))?)
+        }
+        Some(other) => anyhow::bail!("unknown command {other}\n{USAGE}"),
+    };
+    Ok((url, Invocation::Migrate { command, tenant }))
+}
+
+async fn run<BarrelBackend>(
+    tx: &mut sqlx::Transaction<'_, sqlx::Any>,
+    migrations: &[VersionedMigration],
+    desired: fn(BackendName) -> Vec<DesiredTable>,
+    command: &Command,
+) -> Result<(), anyhow::Error>
+where
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
+{
+    match command {
+        Command::Status => {
+            for status in runner::status::<BarrelBackend, _>(tx, migrations).await? {
+                println!("{status}");
+            }
+        }
+        Command::Up(version) => {
+            let version = version.unwrap_or(runner::latest(migrations));
+            let applied = runner::up_to::<BarrelBackend, _>(tx, migrations, version).await?;
+            println!("applied {applied:?}");
+        }
+        Command::Down(version) => {
+            let reverted = runner::down_to::<BarrelBackend, _>(tx, migrations, *version).await?;
+            println!("reverted {reverted:?}");
+        }
+        Command::Reconcile { dry_run } => {
+            let plan = reconcile::reconcile::<BarrelBackend, _>(
+                tx,
+                &desired(BarrelBackend::BACKEND),
+                *dry_run,
+            )
+            .await?;
+            for difference in &plan.differences {
+                println!("-- {difference}");
+            }
+            for sql in &plan.statements {
+                println!("{sql}");
+            }
+            for difference in plan.unresolved() {
+                eprintln!("needs a hand written migration: {difference}");
+            }
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
 pub async fn main() -> Result<(), anyhow::Error> {
     sqlx::any::install_default_drivers();
-    let mut args = std::env::args();
-    let _ = args.next();
-    let primary_url = args.next().unwrap();
-    dbg!(&primary_url);
-    // let secondary_url = args.next().unwrap();
+    let (primary_url, command, tenant) = match parse_args()? {
+        (url, Invocation::Migrate { command, tenant }) => (url, command, tenant),
+        (url, Invocation::Copy { target_url }) => {
+            for table in sql_migrations::copy::copy_tenant_url(&url, &target_url).await? {
+                println!("copied {table}");
+            }
+            return Ok(());
+        }
+    };
+    let (migrations, desired): (_, fn(BackendName) -> Vec<DesiredTable>) = if tenant {
+        (
+            sql_migrations::user_multitenant::MIGRATIONS,
+            sql_migrations::user_multitenant::desired_schema,
+        )
+    } else {
+        (sql_migrations::MIGRATIONS, sql_migrations::desired_schema)
+    };
 
     let options = sqlx::any::AnyConnectOptions::from_str(&primary_url)?;
-    // bring up the user multi db
     let mut c = options.connect().await?;
-    // sql_migrations::user_multitenant::bring_up(tx)
     let name = c.backend_name().to_string();
     let mut tx = c.begin().await?;
     match name.as_str() {
         sqlx::MySql::NAME => {
-            sql_migrations::bring_up::<barrel::backend::MySql, _>(&mut tx).await?;
+            run::<barrel::backend::MySql>(&mut tx, migrations, desired, &command).await?
         }
         sqlx::Postgres::NAME => {
-            sql_migrations::bring_up::<barrel::backend::Pg, _>(&mut tx).await?;
+            run::<barrel::backend::Pg>(&mut tx, migrations, desired, &command).await?
         }
         sqlx::Sqlite::NAME => {
-            sql_migrations::bring_up::<barrel::backend::Sqlite, _>(&mut tx).await?;
+            run::<barrel::backend::Sqlite>(&mut tx, migrations, desired, &command).await?
         }
         _ => return Err(anyhow::Error::msg("Unsupported any backend")),
     }
+    tx.commit().await?;
     Ok(())
 }
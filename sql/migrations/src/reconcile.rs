@@ -0,0 +1,403 @@
+// reconcile a live database with the schema the code expects.
+// the desired schema is described once with barrel types, the live one comes from SchemaInspector.
+// missing tables, columns and indexes are created. type and nullability changes are reported
+// but not applied, barrel can not alter a column and sqlite can not either. foreign keys are
+// only made along with a missing table, sqlite can not add one to a table that exists.
+// the live schema is only read from sqlite so far, other backends are refused, see reflect::any.
+
+use barrel::{Migration, types::Type};
+use sqlx::Transaction;
+
+use crate::reflect::{ColumnDescription, SchemaInspector};
+
+#[derive(Debug, Clone)]
+pub struct DesiredTable {
+    pub name: &'static str,
+    pub columns: Vec<(&'static str, Type)>,
+    pub indexes: Vec<DesiredIndex>,
+    pub foreign_keys: Vec<DesiredForeignKey>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DesiredIndex {
+    pub name: &'static str,
+    pub columns: Vec<&'static str>,
+    pub unique: bool,
+}
+
+/// `column` references the column `references` of `table`
+#[derive(Debug, Clone)]
+pub struct DesiredForeignKey {
+    pub column: &'static str,
+    pub table: &'static str,
+    pub references: &'static str,
+}
+
+impl DesiredTable {
+    /// the CREATE TABLE, foreign keys included. the migrations that make a table go through
+    /// here too, so what they create and what reconcile expects are one description
+    pub fn create_table(&self) -> Migration {
+        let (columns, foreign_keys) = (self.columns.clone(), self.foreign_keys.clone());
+        let mut m = Migration::new();
+        m.create_table_if_not_exists(self.name, move |t| {
+            for (name, ty) in &columns {
+                t.add_column(*name, ty.clone());
+            }
+            for key in &foreign_keys {
+                t.add_foreign_key(&[key.column], key.table, &[key.references]);
+            }
+        });
+        m
+    }
+    /// one CREATE INDEX per index
+    pub fn create_indexes(&self) -> Vec<Migration> {
+        self.indexes.iter().map(|index| index.create(self.name)).collect()
+    }
+}
+
+impl DesiredIndex {
+    // barrel renders an index added in a table as two statements, postgres only takes one
+    pub fn create(&self, table: &str) -> Migration {
+        let mut m = Migration::new();
+        m.inject_custom(format!(
+            "CREATE {}INDEX {} ON {table} ({})",
+            if self.unique { "UNIQUE " } else { "" },
+            self.name,
+            self.columns.join(", ")
+        ));
+        m
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+    },
+    MissingIndex {
+        table: String,
+        index: String,
+    },
+    ColumnType {
+        table: String,
+        column: String,
+        expected: String,
+        found: String,
+    },
+    Nullability {
+        table: String,
+        column: String,
+        expected: bool,
+        found: bool,
+    },
+}
+
+impl Difference {
+    /// true when reconcile emits sql for it, false when it needs a hand written migration
+    pub fn is_resolvable(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingTable { .. } | Self::MissingColumn { .. } | Self::MissingIndex { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingTable { table } => write!(f, "missing table {table}"),
+            Self::MissingColumn { table, column } => write!(f, "missing column {table}.{column}"),
+            Self::MissingIndex { table, index } => write!(f, "missing index {index} on {table}"),
+            Self::ColumnType {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(f, "{table}.{column} is {found}, expected {expected}"),
+            Self::Nullability {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{table}.{column} nullable is {found}, expected {expected}"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReconcilePlan {
+    pub differences: Vec<Difference>,
+    /// one statement per entry, in the order they must run
+    pub statements: Vec<String>,
+}
+
+impl ReconcilePlan {
+    pub fn unresolved(&self) -> impl Iterator<Item = &Difference> {
+        self.differences.iter().filter(|d| !d.is_resolvable())
+    }
+}
+
+/// the type barrel would declare for a column on this backend, upper case
+pub fn declared_type<BarrelBackend: barrel::backend::SqlGenerator>(ty: &Type) -> String {
+    // renders as `"c" TYPE [PRIMARY KEY] [DEFAULT ..] [NOT NULL] [UNIQUE]`
+    let column = BarrelBackend::add_column(false, None, "c", ty);
+    let rest = column.split_once(' ').map(|(_, r)| r).unwrap_or("");
+    let end = [
+        " PRIMARY KEY",
+        " DEFAULT",
+        " NOT NULL",
+        " UNIQUE",
+        " REFERENCES",
+    ]
+    .iter()
+    .filter_map(|suffix| rest.find(suffix))
+    .min()
+    .unwrap_or(rest.len());
+    rest[..end].trim().to_uppercase()
+}
+
+fn normalize(ty: &str) -> String {
+    ty.split_whitespace().collect::<String>().to_uppercase()
+}
+
+/// compares the live schema against `desired` and builds the statements to converge.
+/// nothing is executed
+pub async fn plan<'t, BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'t, SqlxDatabase>,
+    desired: &[DesiredTable],
+) -> Result<ReconcilePlan, anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    Transaction<'t, SqlxDatabase>: SchemaInspector<SqlxDatabase>,
+    <Transaction<'t, SqlxDatabase> as SchemaInspector<SqlxDatabase>>::ColumnInfo: ColumnDescription,
+    BarrelBackend: barrel::backend::SqlGenerator,
+{
+    let mut plan = ReconcilePlan::default();
+    for table in desired {
+        let live = tx.get_columns(table.name).await?;
+        if live.is_empty() {
+            plan.differences.push(Difference::MissingTable {
+                table: table.name.to_string(),
+            });
+            plan.statements.push(table.create_table().make::<BarrelBackend>());
+            for index in table.create_indexes() {
+                plan.statements.push(index.make::<BarrelBackend>());
+            }
+            continue;
+        }
+
+        for (name, ty) in &table.columns {
+            let Some(column) = live.iter().find(|c| c.name() == *name) else {
+                plan.differences.push(Difference::MissingColumn {
+                    table: table.name.to_string(),
+                    column: name.to_string(),
+                });
+                let (name, ty) = (*name, ty.clone());
+                let mut m = Migration::new();
+                m.change_table(table.name, move |t| {
+                    t.add_column(name, ty.clone());
+                });
+                plan.statements.push(m.make::<BarrelBackend>());
+                continue;
+            };
+            let expected = declared_type::<BarrelBackend>(ty);
+            if normalize(&expected) != normalize(column.declared_type()) {
+                plan.differences.push(Difference::ColumnType {
+                    table: table.name.to_string(),
+                    column: name.to_string(),
+                    expected,
+                    found: column.declared_type().to_string(),
+                });
+            }
+            if ty.nullable != column.nullable() {
+                plan.differences.push(Difference::Nullability {
+                    table: table.name.to_string(),
+                    column: name.to_string(),
+                    expected: ty.nullable,
+                    found: column.nullable(),
+                });
+            }
+        }
+
+        let live_indexes = tx.get_indexes(table.name).await?;
+        for index in &table.indexes {
+            if !live_indexes.iter().any(|i| i.name == index.name) {
+                plan.differences.push(Difference::MissingIndex {
+                    table: table.name.to_string(),
+                    index: index.name.to_string(),
+                });
+                plan.statements
+                    .push(index.create(table.name).make::<BarrelBackend>());
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// plans and, unless `dry_run`, runs the statements. the plan is returned either way
+/// so the caller can print the sql and the differences that need a hand written migration
+pub async fn reconcile<'t, BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'t, SqlxDatabase>,
+    desired: &[DesiredTable],
+    dry_run: bool,
+) -> Result<ReconcilePlan, anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    Transaction<'t, SqlxDatabase>: SchemaInspector<SqlxDatabase>,
+    <Transaction<'t, SqlxDatabase> as SchemaInspector<SqlxDatabase>>::ColumnInfo: ColumnDescription,
+    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
+        sqlx::IntoArguments<'q, SqlxDatabase>,
+    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator,
+{
+    let plan = plan::<BarrelBackend, SqlxDatabase>(tx, desired).await?;
+    if !dry_run {
+        for sql in &plan.statements {
+            sqlx::query(sql).execute(&mut **tx).await?;
+        }
+    }
+    Ok(plan)
+}
+
+/// brings an empty sqlite database up with `migrations` and checks that `desired` describes
+/// exactly what they made: nothing for reconcile to do, no table, column or index desired does
+/// not list, and columns in the order they were created since copy reads them in that order
+#[cfg(all(test, feature = "sqlite"))]
+pub(crate) async fn assert_desired_matches(
+    migrations: &[crate::runner::VersionedMigration],
+    desired: &[DesiredTable],
+) {
+    use barrel::backend::Sqlite;
+    use sqlx::Connection;
+
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let mut tx = c.begin().await.unwrap();
+    crate::runner::up_to::<Sqlite, _>(&mut tx, migrations, crate::runner::latest(migrations))
+        .await
+        .unwrap();
+    let plan = reconcile::<Sqlite, _>(&mut tx, desired, true).await.unwrap();
+    assert!(plan.differences.is_empty(), "{:?}", plan.differences);
+
+    let mut live: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name <> ?",
+    )
+    .bind(crate::runner::SCHEMA_MIGRATIONS_TABLE)
+    .fetch_all(&mut *tx)
+    .await
+    .unwrap();
+    live.sort();
+    let mut names: Vec<&str> = desired.iter().map(|t| t.name).collect();
+    names.sort();
+    assert_eq!(live, names);
+    for table in desired {
+        let columns: Vec<String> = tx
+            .get_columns(table.name)
+            .await
+            .unwrap()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+        let expected: Vec<&str> = table.columns.iter().map(|(name, _)| *name).collect();
+        assert_eq!(columns, expected, "columns of {}", table.name);
+        let indexes: Vec<String> = tx
+            .get_indexes(table.name)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|i| i.name)
+            .collect();
+        let expected: Vec<&str> = table.indexes.iter().map(|i| i.name).collect();
+        assert_eq!(indexes, expected, "indexes of {}", table.name);
+        let keys: Vec<(String, String, String)> =
+            sqlx::query_as(r#"SELECT "from", "table", "to" FROM pragma_foreign_key_list(?) ORDER BY id DESC"#)
+                .bind(table.name)
+                .fetch_all(&mut *tx)
+                .await
+                .unwrap();
+        let expected: Vec<(String, String, String)> = table
+            .foreign_keys
+            .iter()
+            .map(|k| (k.column.into(), k.table.into(), k.references.into()))
+            .collect();
+        assert_eq!(keys, expected, "foreign keys of {}", table.name);
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+#[tokio::test]
+async fn reconcile_converges() {
+    use barrel::{backend::Sqlite, types};
+    use sqlx::Connection;
+
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let mut tx = c.begin().await.unwrap();
+    sqlx::query(r#"CREATE TABLE "users" ("id" BINARY NOT NULL UNIQUE)"#)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+
+    let desired = vec![
+        DesiredTable {
+            name: "users",
+            columns: vec![
+                ("id", types::binary().nullable(false).unique(true)),
+                ("email", types::varchar(255).nullable(true)),
+            ],
+            indexes: vec![DesiredIndex {
+                name: "users_email",
+                columns: vec!["email"],
+                unique: false,
+            }],
+            foreign_keys: vec![],
+        },
+        DesiredTable {
+            name: "widgets",
+            columns: vec![("id", types::binary())],
+            indexes: vec![],
+            foreign_keys: vec![],
+        },
+    ];
+
+    let dry = reconcile::<Sqlite, _>(&mut tx, &desired, true)
+        .await
+        .unwrap();
+    assert_eq!(dry.differences.len(), 3);
+    assert_eq!(dry.statements.len(), 3);
+    assert_eq!(
+        plan::<Sqlite, _>(&mut tx, &desired)
+            .await
+            .unwrap()
+            .statements,
+        dry.statements
+    );
+
+    reconcile::<Sqlite, _>(&mut tx, &desired, false)
+        .await
+        .unwrap();
+    assert!(
+        plan::<Sqlite, _>(&mut tx, &desired)
+            .await
+            .unwrap()
+            .differences
+            .is_empty()
+    );
+
+    // a changed type is reported, not applied
+    let mut changed = desired.clone();
+    changed[1].columns[0].1 = types::text();
+    let plan = plan::<Sqlite, _>(&mut tx, &changed).await.unwrap();
+    assert!(plan.statements.is_empty());
+    assert_eq!(plan.unresolved().count(), 1);
+}
@@ -1,6 +1,6 @@
 use sqlx::Database;
 
-use super::sqlite::{PRAGMA_TABLE_INFO_SQL, SqliteColumnInfo};
+use super::sqlite::SqliteColumnInfo;
 
 // #[macro_use]
 use crate::query_sqlite_get_all_column_metdata_for_table;
@@ -10,6 +10,32 @@ pub enum AnyColumnInfo {
     Sqlite(SqliteColumnInfo),
 }
 
+impl super::ColumnDescription for AnyColumnInfo {
+    fn name(&self) -> &str {
+        match self {
+            Self::Sqlite(c) => c.name(),
+        }
+    }
+    fn declared_type(&self) -> &str {
+        match self {
+            Self::Sqlite(c) => c.declared_type(),
+        }
+    }
+    fn nullable(&self) -> bool {
+        match self {
+            Self::Sqlite(c) => c.nullable(),
+        }
+    }
+}
+
+// only sqlite is read through Any so far, reconcile on another backend stops here before it
+// plans anything
+fn unsupported(backend: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "reconcile can only read the live schema of sqlite, not {backend}. bring {backend} databases to the latest version with up"
+    )
+}
+
 #[async_trait::async_trait]
 impl<'t> super::SchemaInspector<sqlx::any::Any> for sqlx::Transaction<'t, sqlx::any::Any> {
     type ColumnInfo = AnyColumnInfo;
@@ -22,13 +48,21 @@ impl<'t> super::SchemaInspector<sqlx::any::Any> for sqlx::Transaction<'t, sqlx::
             sqlx::sqlite::Sqlite::NAME => {
                 let q = query_sqlite_get_all_column_metdata_for_table!(for_table_name);
                 let t = q.fetch_all(&mut **self).await?;
-                let t = t.into_iter().map(|t| AnyColumnInfo::Sqlite(t)).collect();
-                dbg!(&t);
-                Ok(t)
+                Ok(t.into_iter().map(AnyColumnInfo::Sqlite).collect())
             }
-            _ => {
-                todo!("not implemented yet")
+            other => Err(unsupported(other)),
+        }
+    }
+
+    async fn get_indexes(
+        &mut self,
+        for_table_name: &str,
+    ) -> Result<Vec<super::IndexInfo>, anyhow::Error> {
+        match self.backend_name() {
+            sqlx::sqlite::Sqlite::NAME => {
+                super::sqlite::get_indexes::<sqlx::any::Any>(&mut **self, for_table_name).await
             }
+            other => Err(unsupported(other)),
         }
     }
 }
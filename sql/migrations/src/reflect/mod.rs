@@ -1,8 +1,6 @@
 pub mod any;
 pub mod sqlite;
 
-use sqlx::{Database, Row, prelude::FromRow};
-
 #[async_trait::async_trait]
 pub trait SchemaInspector<DB: sqlx::Database> {
     type ColumnInfo: Send + Sync;
@@ -15,4 +13,22 @@ pub trait SchemaInspector<DB: sqlx::Database> {
     async fn get_tables(&mut self) -> Result<Vec<Self::TableInfo>, anyhow::Error> {
         todo!("Implement this function")
     }
+
+    /// indexes created with CREATE INDEX, not the ones backing primary keys or unique columns
+    async fn get_indexes(&mut self, for_table_name: &str) -> Result<Vec<IndexInfo>, anyhow::Error>;
+}
+
+/// the parts of a backend specific column that reconcile compares
+pub trait ColumnDescription {
+    fn name(&self) -> &str;
+    /// the type as declared, VARCHAR(255), BLOB, ...
+    fn declared_type(&self) -> &str;
+    fn nullable(&self) -> bool;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
 }
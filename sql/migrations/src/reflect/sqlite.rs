@@ -1,4 +1,4 @@
-use sqlx::{Database, Executor, Row, prelude::FromRow};
+use sqlx::{Executor, prelude::FromRow};
 
 pub const SQL_GET_ALL_TABLE_METADATA: &str =
     "SELECT name,* FROM sqlite_master WHERE type='table' and name NOT LIKE 'sqlite_%";
@@ -23,6 +23,12 @@ macro_rules! query_sqlite_get_all_column_metdata_for_table {
     };
 }
 
+pub const PRAGMA_INDEX_LIST_SQL: &str =
+    r#"SELECT "name","unique" from pragma_index_list(?) WHERE "origin" = 'c' ORDER BY "name""#;
+
+pub const PRAGMA_INDEX_INFO_SQL: &str =
+    r#"SELECT "name" from pragma_index_info(?) ORDER BY "seqno""#;
+
 #[derive(FromRow, Debug)]
 pub struct SqliteColumnInfo {
     pub cid: i32,
@@ -33,6 +39,50 @@ pub struct SqliteColumnInfo {
     pub pk: Option<i32>,
 }
 
+impl super::ColumnDescription for SqliteColumnInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn declared_type(&self) -> &str {
+        &self.r#type
+    }
+    fn nullable(&self) -> bool {
+        self.notnull == 0
+    }
+}
+
+/// generic over the database so the Any inspector can share it when the backend is sqlite
+pub async fn get_indexes<DB>(
+    conn: &mut <DB as sqlx::Database>::Connection,
+    for_table_name: &str,
+) -> Result<Vec<super::IndexInfo>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> &'q str: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> (String, i32): FromRow<'r, <DB as sqlx::Database>::Row>,
+    for<'r> (String,): FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let list: Vec<(String, i32)> = sqlx::query_as(PRAGMA_INDEX_LIST_SQL)
+        .bind(for_table_name)
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut indexes = Vec::with_capacity(list.len());
+    for (name, unique) in list {
+        let columns: Vec<(String,)> = sqlx::query_as(PRAGMA_INDEX_INFO_SQL)
+            .bind(name.as_str())
+            .fetch_all(&mut *conn)
+            .await?;
+        indexes.push(super::IndexInfo {
+            name,
+            columns: columns.into_iter().map(|(c,)| c).collect(),
+            unique: unique != 0,
+        });
+    }
+    Ok(indexes)
+}
+
 #[async_trait::async_trait]
 impl super::SchemaInspector<sqlx::Sqlite> for sqlx::Transaction<'_, sqlx::Sqlite> {
     type ColumnInfo = SqliteColumnInfo;
@@ -45,4 +95,11 @@ impl super::SchemaInspector<sqlx::Sqlite> for sqlx::Transaction<'_, sqlx::Sqlite
         let t = q.fetch_all(&mut **self).await?;
         Ok(t)
     }
+
+    async fn get_indexes(
+        &mut self,
+        for_table_name: &str,
+    ) -> Result<Vec<super::IndexInfo>, anyhow::Error> {
+        get_indexes::<sqlx::Sqlite>(&mut **self, for_table_name).await
+    }
 }
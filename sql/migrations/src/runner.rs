@@ -0,0 +1,463 @@
+// versioned migrations.
+// every applied version is recorded in schema_migrations together with a checksum of the
+// sql it ran, so editing a migration after it shipped is caught instead of silently ignored.
+
+use barrel::Migration;
+use sha2::Digest;
+use sqlx::Transaction;
+
+use crate::BackendName;
+
+pub const SCHEMA_MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// one step of a migration set. versions must be strictly increasing within a set
+pub struct VersionedMigration {
+    pub version: i64,
+    pub name: &'static str,
+    /// one barrel migration per statement, postgres will not prepare more than one at a time
+    /// gets the backend it is rendered for, for the few types barrel gets wrong on one of them
+    pub up: fn(BackendName) -> Vec<Migration>,
+    pub down: fn(BackendName) -> Vec<Migration>,
+}
+
+impl VersionedMigration {
+    pub fn up_sql<BarrelBackend: barrel::backend::SqlGenerator + Placeholders>(
+        &self,
+    ) -> Vec<String> {
+        (self.up)(BarrelBackend::BACKEND)
+            .iter()
+            .map(|m| m.make::<BarrelBackend>())
+            .collect()
+    }
+    pub fn down_sql<BarrelBackend: barrel::backend::SqlGenerator + Placeholders>(
+        &self,
+    ) -> Vec<String> {
+        (self.down)(BarrelBackend::BACKEND)
+            .iter()
+            .map(|m| m.make::<BarrelBackend>())
+            .collect()
+    }
+    /// sha256 of the up sql as rendered for this backend, hex encoded
+    pub fn checksum<BarrelBackend: barrel::backend::SqlGenerator + Placeholders>(&self) -> String {
+        let mut hasher = sha2::Sha256::new();
+        for sql in self.up_sql::<BarrelBackend>() {
+            hasher.update(sql.as_bytes());
+            hasher.update([0_u8]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+/// bind parameter syntax for the backend the sql is rendered for.
+/// keyed on the barrel backend rather than the sqlx database so it also works over sqlx::Any
+pub trait Placeholders {
+    const BACKEND: BackendName;
+    fn bind(n: usize) -> String;
+}
+
+#[cfg(feature = "sqlite")]
+impl Placeholders for barrel::backend::Sqlite {
+    const BACKEND: BackendName = BackendName::Sqlite;
+    fn bind(_: usize) -> String {
+        "?".to_string()
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl Placeholders for barrel::backend::MySql {
+    const BACKEND: BackendName = BackendName::Mysql;
+    fn bind(_: usize) -> String {
+        "?".to_string()
+    }
+}
+
+#[cfg(feature = "postgresql")]
+impl Placeholders for barrel::backend::Pg {
+    const BACKEND: BackendName = BackendName::Postgresql;
+    fn bind(n: usize) -> String {
+        format!("${n}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationState {
+    Pending,
+    Applied {
+        applied_at: i64,
+    },
+    /// the migration changed after it was applied
+    Drifted {
+        applied_at: i64,
+        expected: String,
+        found: String,
+    },
+    /// recorded in the database but not known to this build, the database is newer than the code
+    Unknown {
+        applied_at: i64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub state: MigrationState,
+}
+
+impl std::fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match &self.state {
+            MigrationState::Pending => "pending".to_string(),
+            MigrationState::Applied { applied_at } => format!("applied at {applied_at}"),
+            MigrationState::Drifted {
+                applied_at,
+                expected,
+                found,
+            } => format!("DRIFTED (applied at {applied_at}, recorded {found}, now {expected})"),
+            MigrationState::Unknown { applied_at } => {
+                format!("UNKNOWN to this build (applied at {applied_at})")
+            }
+        };
+        write!(f, "{:>6} {:<32} {}", self.version, self.name, state)
+    }
+}
+
+/// a row of schema_migrations
+#[derive(sqlx::FromRow)]
+pub struct AppliedRow {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: i64,
+}
+
+fn check_order(migrations: &[VersionedMigration]) -> Result<(), anyhow::Error> {
+    for pair in migrations.windows(2) {
+        if pair[0].version >= pair[1].version {
+            anyhow::bail!(
+                "migration {} ({}) is not ordered after {} ({})",
+                pair[1].version,
+                pair[1].name,
+                pair[0].version,
+                pair[0].name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// compares the bookkeeping rows against the known migrations, in version order
+fn compare<BarrelBackend: barrel::backend::SqlGenerator + Placeholders>(
+    migrations: &[VersionedMigration],
+    applied: Vec<AppliedRow>,
+) -> Vec<MigrationStatus> {
+    let mut statuses: Vec<MigrationStatus> = migrations
+        .iter()
+        .map(|m| {
+            let state = match applied.iter().find(|a| a.version == m.version) {
+                None => MigrationState::Pending,
+                Some(row) => {
+                    let expected = m.checksum::<BarrelBackend>();
+                    if row.checksum == expected {
+                        MigrationState::Applied {
+                            applied_at: row.applied_at,
+                        }
+                    } else {
+                        MigrationState::Drifted {
+                            applied_at: row.applied_at,
+                            expected,
+                            found: row.checksum.clone(),
+                        }
+                    }
+                }
+            };
+            MigrationStatus {
+                version: m.version,
+                name: m.name.to_string(),
+                state,
+            }
+        })
+        .collect();
+    for row in applied {
+        if !migrations.iter().any(|m| m.version == row.version) {
+            statuses.push(MigrationStatus {
+                version: row.version,
+                name: row.name,
+                state: MigrationState::Unknown {
+                    applied_at: row.applied_at,
+                },
+            });
+        }
+    }
+    statuses.sort_by_key(|s| s.version);
+    statuses
+}
+
+/// refuses to move a database whose history does not match the code
+fn ensure_consistent(statuses: &[MigrationStatus]) -> Result<(), anyhow::Error> {
+    let bad: Vec<String> = statuses
+        .iter()
+        .filter(|s| {
+            matches!(
+                s.state,
+                MigrationState::Drifted { .. } | MigrationState::Unknown { .. }
+            )
+        })
+        .map(|s| s.to_string())
+        .collect();
+    if !bad.is_empty() {
+        anyhow::bail!(
+            "schema history does not match this build:\n{}",
+            bad.join("\n")
+        );
+    }
+    Ok(())
+}
+
+async fn create_table<BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'_, SqlxDatabase>,
+) -> Result<(), anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
+        sqlx::IntoArguments<'q, SqlxDatabase>,
+    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator,
+{
+    let mut m = Migration::new();
+    m.create_table_if_not_exists(SCHEMA_MIGRATIONS_TABLE, |table| {
+        table.add_column(
+            "version",
+            barrel::types::custom("BIGINT")
+                .primary(true)
+                .nullable(false),
+        );
+        table.add_column("name", barrel::types::varchar(255).nullable(false));
+        table.add_column("checksum", barrel::types::varchar(64).nullable(false));
+        table.add_column(
+            "applied_at",
+            barrel::types::custom("BIGINT").nullable(false),
+        );
+    });
+    sqlx::query(&m.make::<BarrelBackend>())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// the state of every known migration, plus any the database has that this build does not
+pub async fn status<BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'_, SqlxDatabase>,
+    migrations: &[VersionedMigration],
+) -> Result<Vec<MigrationStatus>, anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
+        sqlx::IntoArguments<'q, SqlxDatabase>,
+    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = SqlxDatabase>,
+    for<'r> AppliedRow: sqlx::FromRow<'r, <SqlxDatabase as sqlx::Database>::Row>,
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
+{
+    check_order(migrations)?;
+    create_table::<BarrelBackend, SqlxDatabase>(tx).await?;
+    let applied: Vec<AppliedRow> = sqlx::query_as(
+        "SELECT version,name,checksum,applied_at FROM schema_migrations ORDER BY version",
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(compare::<BarrelBackend>(migrations, applied))
+}
+
+/// applies every pending migration up to and including `version`, oldest first.
+/// returns the versions that were applied
+pub async fn up_to<BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'_, SqlxDatabase>,
+    migrations: &[VersionedMigration],
+    version: i64,
+) -> Result<Vec<i64>, anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
+        sqlx::IntoArguments<'q, SqlxDatabase>,
+    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = SqlxDatabase>,
+    for<'r> AppliedRow: sqlx::FromRow<'r, <SqlxDatabase as sqlx::Database>::Row>,
+    for<'q> String: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    for<'q> i64: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
+{
+    let statuses = status::<BarrelBackend, SqlxDatabase>(tx, migrations).await?;
+    ensure_consistent(&statuses)?;
+
+    let binds: Vec<String> = (1..=4).map(BarrelBackend::bind).collect();
+    let insert = format!(
+        "INSERT INTO schema_migrations (version,name,checksum,applied_at) VALUES ({})",
+        binds.join(",")
+    );
+    let mut applied = Vec::new();
+    for m in migrations.iter().filter(|m| m.version <= version) {
+        let pending = statuses
+            .iter()
+            .any(|s| s.version == m.version && s.state == MigrationState::Pending);
+        if !pending {
+            continue;
+        }
+        for sql in m.up_sql::<BarrelBackend>() {
+            sqlx::query(&sql).execute(&mut **tx).await?;
+        }
+        sqlx::query(&insert)
+            .bind(m.version)
+            .bind(m.name.to_string())
+            .bind(m.checksum::<BarrelBackend>())
+            .bind(now_millis())
+            .execute(&mut **tx)
+            .await?;
+        applied.push(m.version);
+    }
+    Ok(applied)
+}
+
+/// reverts every applied migration newer than `version`, newest first.
+/// returns the versions that were reverted
+pub async fn down_to<BarrelBackend, SqlxDatabase>(
+    tx: &mut Transaction<'_, SqlxDatabase>,
+    migrations: &[VersionedMigration],
+    version: i64,
+) -> Result<Vec<i64>, anyhow::Error>
+where
+    SqlxDatabase: sqlx::Database,
+    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
+        sqlx::IntoArguments<'q, SqlxDatabase>,
+    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
+        sqlx::Executor<'c, Database = SqlxDatabase>,
+    for<'r> AppliedRow: sqlx::FromRow<'r, <SqlxDatabase as sqlx::Database>::Row>,
+    for<'q> i64: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
+{
+    let statuses = status::<BarrelBackend, SqlxDatabase>(tx, migrations).await?;
+    ensure_consistent(&statuses)?;
+
+    let delete = format!(
+        "DELETE FROM schema_migrations WHERE version = {}",
+        BarrelBackend::bind(1)
+    );
+    let mut reverted = Vec::new();
+    for m in migrations.iter().rev().filter(|m| m.version > version) {
+        let applied = statuses
+            .iter()
+            .any(|s| s.version == m.version && matches!(s.state, MigrationState::Applied { .. }));
+        if !applied {
+            continue;
+        }
+        for sql in m.down_sql::<BarrelBackend>() {
+            sqlx::query(&sql).execute(&mut **tx).await?;
+        }
+        sqlx::query(&delete)
+            .bind(m.version)
+            .execute(&mut **tx)
+            .await?;
+        reverted.push(m.version);
+    }
+    Ok(reverted)
+}
+
+/// the newest version in a set, 0 for an empty set
+pub fn latest(migrations: &[VersionedMigration]) -> i64 {
+    migrations.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+fn test_up(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.create_table_if_not_exists("widgets", |table| {
+        table.add_column("id", barrel::types::binary());
+    });
+    vec![m]
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+fn test_down(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.drop_table_if_exists("widgets");
+    vec![m]
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+#[tokio::test]
+async fn up_down_and_drift() {
+    use barrel::backend::Sqlite;
+    use sqlx::Connection;
+
+    let mut c = sqlx::SqliteConnection::connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let mut tx = c.begin().await.unwrap();
+    let mut migrations = vec![
+        VersionedMigration {
+            version: 1,
+            name: "users",
+            up: crate::v1_initdb::MIGRATION.up,
+            down: crate::v1_initdb::MIGRATION.down,
+        },
+        VersionedMigration {
+            version: 2,
+            name: "widgets",
+            up: test_up,
+            down: test_down,
+        },
+    ];
+
+    assert_eq!(
+        up_to::<Sqlite, _>(&mut tx, &migrations, 1).await.unwrap(),
+        vec![1]
+    );
+    assert_eq!(
+        up_to::<Sqlite, _>(&mut tx, &migrations, 2).await.unwrap(),
+        vec![2]
+    );
+    assert!(
+        up_to::<Sqlite, _>(&mut tx, &migrations, 2)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let statuses = status::<Sqlite, _>(&mut tx, &migrations).await.unwrap();
+    assert!(
+        statuses
+            .iter()
+            .all(|s| matches!(s.state, MigrationState::Applied { .. }))
+    );
+
+    assert_eq!(
+        down_to::<Sqlite, _>(&mut tx, &migrations, 0).await.unwrap(),
+        vec![2, 1]
+    );
+    let statuses = status::<Sqlite, _>(&mut tx, &migrations).await.unwrap();
+    assert!(statuses.iter().all(|s| s.state == MigrationState::Pending));
+
+    // editing an applied migration is refused
+    up_to::<Sqlite, _>(&mut tx, &migrations, 2).await.unwrap();
+    migrations[1].up = crate::v1_initdb::MIGRATION.up;
+    let statuses = status::<Sqlite, _>(&mut tx, &migrations).await.unwrap();
+    assert!(matches!(statuses[1].state, MigrationState::Drifted { .. }));
+    assert!(down_to::<Sqlite, _>(&mut tx, &migrations, 0).await.is_err());
+
+    // a database newer than the code is refused
+    migrations.pop();
+    let statuses = status::<Sqlite, _>(&mut tx, &migrations).await.unwrap();
+    assert!(matches!(statuses[1].state, MigrationState::Unknown { .. }));
+    assert!(up_to::<Sqlite, _>(&mut tx, &migrations, 1).await.is_err());
+}
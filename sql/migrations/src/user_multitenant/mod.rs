@@ -1,19 +1,46 @@
-use sqlx::Transaction;
+use crate::reconcile::DesiredTable;
+use crate::runner::{self, AppliedRow, Placeholders, VersionedMigration};
+use crate::BackendName;
 
 pub mod v1_initdb;
+pub mod v2_audit_log;
+pub mod v3_passkeys;
+pub mod v4_ledger;
+pub mod v5_passkey_metadata;
 
-// pub async fn bring_up_pooled<'exec,Exec,BarrelBackend,SqlxDatabase>(mut executor: Exec) -> Result<(),anyhow::Error>
-//     where SqlxDatabase: sqlx::Database,
-//     for <'q> <SqlxDatabase as sqlx::database::HasArguments<'q>>::Arguments: sqlx::IntoArguments<'q, SqlxDatabase>,
-//     // Exec: sqlx::Executor<'exec,Database = SqlxDatabase>,
-//     for<'e> &'e mut Exec: sqlx::Executor<'e,Database = SqlxDatabase>,
-//     BarrelBackend: barrel::backend::SqlGenerator {
-//     v1_initdb::up::<Exec,BarrelBackend,SqlxDatabase>(&mut executor).await?;
-//     Ok(())
-// }
+/// mysql type of a sha256 column
+pub const MYSQL_HASH: &str = "VARBINARY(32)";
 
+/// mysql type of a webauthn credential id. the spec caps them at 1023 bytes, and mysql
+/// only indexes a BLOB by a prefix, so the unique index in v5 needs it bounded
+pub const MYSQL_CREDENTIAL_ID: &str = "VARBINARY(1023)";
+
+/// every tenant migration, oldest first
+pub const MIGRATIONS: &[VersionedMigration] = &[
+    v1_initdb::MIGRATION,
+    v2_audit_log::MIGRATION,
+    v3_passkeys::MIGRATION,
+    v4_ledger::MIGRATION,
+    v5_passkey_metadata::MIGRATION,
+];
+
+/// a tenant database as of the latest migration, for reconcile. made of the tables the
+/// migrations create, so the two can not drift apart
+pub fn desired_schema(backend: BackendName) -> Vec<DesiredTable> {
+    let mut passkeys = v3_passkeys::passkeys(backend);
+    v5_passkey_metadata::extend_passkeys(&mut passkeys);
+    vec![
+        v1_initdb::users(backend),
+        v2_audit_log::audit_log(backend),
+        passkeys,
+        v4_ledger::accounts(backend),
+        v4_ledger::entries(backend),
+    ]
+}
+
+/// applies every pending tenant migration
 pub async fn bring_up<'exec, BarrelBackend, SqlxDatabase>(
-    mut tx: &mut sqlx::Transaction<'_, SqlxDatabase>,
+    tx: &mut sqlx::Transaction<'_, SqlxDatabase>,
 ) -> Result<(), anyhow::Error>
 where
     SqlxDatabase: sqlx::Database,
@@ -21,10 +48,19 @@ where
         sqlx::IntoArguments<'q, SqlxDatabase>,
     for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
         sqlx::Executor<'c, Database = SqlxDatabase>,
-    // Exec: sqlx::Executor<'exec,Database = SqlxDatabase>,
-    // for<'e> &'e mut Exec: sqlx::Executor<'e,Database = SqlxDatabase>,
-    BarrelBackend: barrel::backend::SqlGenerator,
+    for<'r> AppliedRow: sqlx::FromRow<'r, <SqlxDatabase as sqlx::Database>::Row>,
+    for<'q> String: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    for<'q> i64: sqlx::Encode<'q, SqlxDatabase> + sqlx::Type<SqlxDatabase>,
+    BarrelBackend: barrel::backend::SqlGenerator + Placeholders,
 {
-    v1_initdb::up::<BarrelBackend, SqlxDatabase>(&mut tx).await?;
+    runner::up_to::<BarrelBackend, SqlxDatabase>(tx, MIGRATIONS, runner::latest(MIGRATIONS))
+        .await?;
     Ok(())
 }
+
+#[cfg(all(test, feature = "sqlite"))]
+#[tokio::test]
+async fn desired_schema_matches_migrations() {
+    crate::reconcile::assert_desired_matches(MIGRATIONS, &desired_schema(BackendName::Sqlite))
+        .await;
+}
@@ -1,21 +1,32 @@
-use barrel::{Migration, types};
-use sqlx::Transaction;
+use barrel::Migration;
 
-pub async fn up<'exec, BarrelBackend, SqlxDatabase>(
-    tx: &mut Transaction<'_, SqlxDatabase>,
-) -> Result<(), anyhow::Error>
-where
-    SqlxDatabase: sqlx::Database,
-    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
-        sqlx::IntoArguments<'q, SqlxDatabase>,
-    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
-        sqlx::Executor<'c, Database = SqlxDatabase>,
-    // for<'e> &'e mut Exec: sqlx::Executor<'e,Database = SqlxDatabase>,
-    BarrelBackend: barrel::backend::SqlGenerator,
-{
-    Ok(())
+use crate::reconcile::DesiredTable;
+use crate::runner::VersionedMigration;
+use crate::{BackendName, MYSQL_ID};
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 1,
+    name: "initdb",
+    up,
+    down,
+};
+
+/// the users living in this tenant database
+pub fn users(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "users",
+        columns: vec![("id", backend.binary(MYSQL_ID).primary(true).nullable(false))],
+        indexes: vec![],
+        foreign_keys: vec![],
+    }
+}
+
+fn up(backend: BackendName) -> Vec<Migration> {
+    vec![users(backend).create_table()]
 }
 
-pub fn down() {
-    // Error, cant go lower than this???
+fn down(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.drop_table_if_exists("users");
+    vec![m]
 }
@@ -0,0 +1,67 @@
+use barrel::{Migration, types};
+
+use super::MYSQL_HASH;
+use crate::BackendName;
+use crate::reconcile::{DesiredIndex, DesiredTable};
+use crate::runner::VersionedMigration;
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 2,
+    name: "audit_log",
+    up,
+    down,
+};
+
+/// the append only change log, see sql::audit. history reads it by entity and reverts by request
+pub fn audit_log(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "audit_log",
+        columns: vec![
+            ("seq", types::custom("BIGINT").primary(true).nullable(false)),
+            ("entity_type", types::varchar(64).nullable(false)),
+            ("entity_id", types::varchar(64).nullable(false)),
+            ("kind", types::varchar(16).nullable(false)),
+            ("actor", types::varchar(255).nullable(false)),
+            ("changed_at", types::custom("BIGINT").nullable(false)),
+            ("request_id", types::varchar(255).nullable(true)),
+            ("before_json", types::text().nullable(true)),
+            ("after_json", types::text().nullable(true)),
+            ("prev_hash", backend.binary(MYSQL_HASH).nullable(false)),
+            ("hash", backend.binary(MYSQL_HASH).nullable(false)),
+        ],
+        indexes: vec![
+            DesiredIndex {
+                name: "audit_log_entity",
+                columns: vec!["entity_type", "entity_id"],
+                unique: false,
+            },
+            DesiredIndex {
+                name: "audit_log_request_id",
+                columns: vec!["request_id"],
+                unique: false,
+            },
+        ],
+        foreign_keys: vec![],
+    }
+}
+
+fn up(backend: BackendName) -> Vec<Migration> {
+    let table = audit_log(backend);
+    let mut up = vec![table.create_table()];
+    up.extend(table.create_indexes());
+    up
+}
+
+// the audit log is never dropped, going back up finds it in place. its indexes are made again.
+// mysql indexes belong to their table and have to be dropped through it
+fn down(backend: BackendName) -> Vec<Migration> {
+    let index = |name: &str| {
+        let mut m = Migration::new();
+        m.inject_custom(match backend {
+            BackendName::Mysql => format!("DROP INDEX {name} ON audit_log"),
+            BackendName::Postgresql | BackendName::Sqlite => format!("DROP INDEX {name}"),
+        });
+        m
+    };
+    vec![index("audit_log_request_id"), index("audit_log_entity")]
+}
@@ -0,0 +1,43 @@
+use barrel::{Migration, types};
+
+use super::MYSQL_CREDENTIAL_ID;
+use crate::reconcile::DesiredTable;
+use crate::runner::VersionedMigration;
+use crate::{BackendName, MYSQL_ID};
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 3,
+    name: "passkeys",
+    up,
+    down,
+};
+
+/// webauthn credentials, a user can register more than one. v5 adds to it
+pub fn passkeys(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "passkeys",
+        columns: vec![
+            ("id", backend.binary(MYSQL_ID).primary(true).nullable(false)),
+            ("user_id", backend.binary(MYSQL_ID).nullable(false)),
+            (
+                "credential_id",
+                backend.binary(MYSQL_CREDENTIAL_ID).nullable(false),
+            ),
+            ("public_key", backend.binary("BLOB").nullable(false)),
+            // a u32 on the wire, BIGINT so every backend holds all of it
+            ("sign_count", types::custom("BIGINT").nullable(false)),
+        ],
+        indexes: vec![],
+        foreign_keys: vec![],
+    }
+}
+
+fn up(backend: BackendName) -> Vec<Migration> {
+    vec![passkeys(backend).create_table()]
+}
+
+fn down(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.drop_table_if_exists("passkeys");
+    vec![m]
+}
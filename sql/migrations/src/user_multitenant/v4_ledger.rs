@@ -0,0 +1,78 @@
+use barrel::{Migration, types};
+
+use crate::reconcile::{DesiredForeignKey, DesiredIndex, DesiredTable};
+use crate::runner::VersionedMigration;
+use crate::{BackendName, MYSQL_ID};
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 4,
+    name: "ledger",
+    up,
+    down,
+};
+
+/// accounts, a sub-account points at its parent. indexed by parent, sub-accounts are looked up by it
+pub fn accounts(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "accounts",
+        columns: vec![
+            ("id", backend.binary(MYSQL_ID).primary(true).nullable(false)),
+            ("parent_id", backend.binary(MYSQL_ID).nullable(true)),
+            ("name", types::varchar(255).nullable(false)),
+            ("kind", types::varchar(16).nullable(false)),
+        ],
+        indexes: vec![DesiredIndex {
+            name: "accounts_parent_id",
+            columns: vec!["parent_id"],
+            unique: false,
+        }],
+        foreign_keys: vec![DesiredForeignKey {
+            column: "parent_id",
+            table: "accounts",
+            references: "id",
+        }],
+    }
+}
+
+/// the entries posted against an account, indexed by it
+pub fn entries(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "entries",
+        columns: vec![
+            ("id", backend.binary(MYSQL_ID).primary(true).nullable(false)),
+            ("account_id", backend.binary(MYSQL_ID).nullable(false)),
+            // ISO 8601 date, sorts correctly as text on every backend
+            ("posted_on", types::varchar(10).nullable(false)),
+            ("amount", types::custom("BIGINT").nullable(false)),
+            ("payee", types::varchar(255).nullable(true)),
+            ("memo", types::text().nullable(true)),
+        ],
+        indexes: vec![DesiredIndex {
+            name: "entries_account_id",
+            columns: vec!["account_id"],
+            unique: false,
+        }],
+        foreign_keys: vec![DesiredForeignKey {
+            column: "account_id",
+            table: "accounts",
+            references: "id",
+        }],
+    }
+}
+
+fn up(backend: BackendName) -> Vec<Migration> {
+    let (accounts, entries) = (accounts(backend), entries(backend));
+    let mut up = vec![accounts.create_table(), entries.create_table()];
+    up.extend(accounts.create_indexes());
+    up.extend(entries.create_indexes());
+    up
+}
+
+// the indexes go with their tables
+fn down(_: BackendName) -> Vec<Migration> {
+    let mut entries = Migration::new();
+    entries.drop_table_if_exists("entries");
+    let mut accounts = Migration::new();
+    accounts.drop_table_if_exists("accounts");
+    vec![entries, accounts]
+}
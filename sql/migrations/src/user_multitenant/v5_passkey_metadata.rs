@@ -0,0 +1,68 @@
+use barrel::{Migration, types};
+
+use crate::BackendName;
+use crate::reconcile::{DesiredIndex, DesiredTable};
+use crate::runner::VersionedMigration;
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 5,
+    name: "passkey_metadata",
+    up,
+    down,
+};
+
+/// what v5 adds to passkeys: a name the user picked, when the passkey was last used and when
+/// it was revoked. a credential can only be registered once
+pub fn extend_passkeys(passkeys: &mut DesiredTable) {
+    passkeys.columns.extend([
+        ("nickname", types::varchar(255).nullable(true)),
+        // unix milliseconds
+        ("last_used_at", types::custom("BIGINT").nullable(true)),
+        ("revoked_at", types::custom("BIGINT").nullable(true)),
+    ]);
+    passkeys.indexes.push(DesiredIndex {
+        name: "passkeys_credential_id",
+        columns: vec!["credential_id"],
+        unique: true,
+    });
+}
+
+fn up(_: BackendName) -> Vec<Migration> {
+    let mut added = DesiredTable {
+        name: "passkeys",
+        columns: vec![],
+        indexes: vec![],
+        foreign_keys: vec![],
+    };
+    extend_passkeys(&mut added);
+    let columns = added.columns.iter().map(|(name, ty)| {
+        let (name, ty) = (*name, ty.clone());
+        let mut m = Migration::new();
+        m.change_table("passkeys", move |table| {
+            table.add_column(name, ty.clone());
+        });
+        m
+    });
+    columns.chain(added.create_indexes()).collect()
+}
+
+// barrel refuses to drop columns on sqlite, which has supported it since 3.35.
+// mysql indexes belong to their table and have to be dropped through it
+fn down(backend: BackendName) -> Vec<Migration> {
+    let mut index = Migration::new();
+    index.inject_custom(match backend {
+        BackendName::Mysql => "DROP INDEX passkeys_credential_id ON passkeys",
+        BackendName::Postgresql | BackendName::Sqlite => "DROP INDEX passkeys_credential_id",
+    });
+    let column = |name: &str| {
+        let mut m = Migration::new();
+        m.inject_custom(format!("ALTER TABLE passkeys DROP COLUMN {name}"));
+        m
+    };
+    vec![
+        index,
+        column("revoked_at"),
+        column("last_used_at"),
+        column("nickname"),
+    ]
+}
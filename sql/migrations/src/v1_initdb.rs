@@ -1,46 +1,32 @@
-use crate::reflect::SchemaInspector;
-use barrel::{Migration, types};
-use sea_query::{Alias, Expr, PostgresQueryBuilder, SqliteQueryBuilder};
-use sqlx::{Database, Executor, Transaction};
-// performs the reconciliaton logic for this version of the datbase
-pub async fn up<'exec, BarrelBackend, SqlxDatabase>(
-    tx: &mut Transaction<'_, SqlxDatabase>,
-) -> Result<(), anyhow::Error>
-where
-    SqlxDatabase: sqlx::Database,
-    for<'c> sqlx::Transaction<'c, SqlxDatabase>: SchemaInspector<SqlxDatabase>,
-    for<'q> <SqlxDatabase as sqlx::database::Database>::Arguments<'q>:
-        sqlx::IntoArguments<'q, SqlxDatabase>,
-    for<'c> &'c mut <SqlxDatabase as sqlx::Database>::Connection:
-        sqlx::Executor<'c, Database = SqlxDatabase>,
-    BarrelBackend: barrel::backend::SqlGenerator,
-{
-    // the master user table, for the app database
-    let mut m = Migration::new();
-    m.create_table_if_not_exists("users", |table| {
-        table.add_column("id", types::binary().nullable(false).unique(true));
-    });
+use barrel::Migration;
+
+use crate::reconcile::DesiredTable;
+use crate::runner::VersionedMigration;
+use crate::{BackendName, MYSQL_ID};
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 1,
+    name: "initdb",
+    up,
+    down,
+};
 
-    let sql = m.make::<BarrelBackend>();
-    let q = sqlx::query(&sql).execute(&mut **tx).await?;
+/// the master user table, for the app database
+pub fn users(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "users",
+        columns: vec![("id", backend.binary(MYSQL_ID).nullable(false).unique(true))],
+        indexes: vec![],
+        foreign_keys: vec![],
+    }
+}
 
-    let q = tx.get_columns("users").await?;
-    //     match SqlxDatabase::NAME {
-    //     sqlx::Sqlite::NAME => {
-    //         // tx.fetch_all(q).await?;
-    //         // let m = ;
-    //     }
-    //     sqlx::Postgres::NAME => {
-    //         // i_q.to_string(PostgresQueryBuilder)
-    //         todo!()
-    //     }
-    //     sqlx::MySql::NAME => {
-    //         // i_q.to_string(sea_query::MysqlQueryBuilder)
-    //         todo!()
-    //     }
-    //     _=> {panic!("{}",SqlxDatabase::NAME)}
-    // };
+fn up(backend: BackendName) -> Vec<Migration> {
+    vec![users(backend).create_table()]
+}
 
-    let m = Migration::new().change_table("users", |table| {});
-    Ok(())
+fn down(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.drop_table_if_exists("users");
+    vec![m]
 }
@@ -0,0 +1,43 @@
+use barrel::{Migration, types};
+
+use crate::reconcile::DesiredTable;
+use crate::runner::VersionedMigration;
+use crate::{BackendName, MYSQL_ID};
+
+pub const MIGRATION: VersionedMigration = VersionedMigration {
+    version: 2,
+    name: "tenant_directory",
+    up,
+    down,
+};
+
+/// where each user's tenant database lives, see sql::directory
+pub fn tenant_directory(backend: BackendName) -> DesiredTable {
+    DesiredTable {
+        name: "tenant_directory",
+        columns: vec![
+            (
+                "user_id",
+                backend.binary(MYSQL_ID).primary(true).nullable(false),
+            ),
+            ("backend", types::varchar(16).nullable(false)),
+            ("url", types::text().nullable(false)),
+            ("schema_version", types::custom("BIGINT").nullable(false)),
+            ("created_at", types::custom("BIGINT").nullable(false)),
+            ("last_access", types::custom("BIGINT").nullable(false)),
+            ("status", types::varchar(16).nullable(false)),
+        ],
+        indexes: vec![],
+        foreign_keys: vec![],
+    }
+}
+
+fn up(backend: BackendName) -> Vec<Migration> {
+    vec![tenant_directory(backend).create_table()]
+}
+
+fn down(_: BackendName) -> Vec<Migration> {
+    let mut m = Migration::new();
+    m.drop_table_if_exists("tenant_directory");
+    vec![m]
+}
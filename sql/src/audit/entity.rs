@@ -0,0 +1,133 @@
+// the json the audit log keeps as the before and after image of an entity.
+// written out field by field instead of through the serde derives, so the log does not
+// depend on the serialize feature and its shape only changes when this file does
+
+use serde_json::{Value, json};
+
+use crate::core::models::{Account, Entry, Passkey};
+use crate::user::User;
+
+/// an entity whose changes are recorded in the audit log
+pub trait Audited {
+    /// the table it lives in, recorded as entity_type
+    const ENTITY_TYPE: &'static str;
+    fn audit_id(&self) -> String;
+    fn audit_json(&self) -> Value;
+}
+
+impl Audited for User {
+    const ENTITY_TYPE: &'static str = "users";
+    fn audit_id(&self) -> String {
+        self.id.to_string()
+    }
+    fn audit_json(&self) -> Value {
+        json!({ "id": self.id.to_string() })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// the public key is left out, history only needs to tell the passkeys apart
+impl Audited for Passkey {
+    const ENTITY_TYPE: &'static str = "passkeys";
+    fn audit_id(&self) -> String {
+        self.id.to_string()
+    }
+    fn audit_json(&self) -> Value {
+        json!({
+            "id": self.id.to_string(),
+            "credential_id": hex(&self.credential_id),
+            "sign_count": self.sign_count,
+            "nickname": self.nickname,
+        })
+    }
+}
+
+impl Audited for Account {
+    const ENTITY_TYPE: &'static str = "accounts";
+    fn audit_id(&self) -> String {
+        self.id.to_string()
+    }
+    fn audit_json(&self) -> Value {
+        json!({
+            "id": self.id.to_string(),
+            "parent_id": self.parent_id.as_ref().map(|p| p.to_string()),
+            "name": self.name,
+            "kind": self.kind.as_str(),
+        })
+    }
+}
+
+impl Audited for Entry {
+    const ENTITY_TYPE: &'static str = "entries";
+    fn audit_id(&self) -> String {
+        self.id.to_string()
+    }
+    fn audit_json(&self) -> Value {
+        json!({
+            "id": self.id.to_string(),
+            "account_id": self.account_id.to_string(),
+            "posted_on": self.posted_on.to_string(),
+            "amount": self.amount,
+            "payee": self.payee,
+            "memo": self.memo,
+        })
+    }
+}
+
+/// an entity a revert can write back from its audit image
+pub trait Restorable: Audited + Sized {
+    fn from_audit_json(json: &str) -> Result<Self, anyhow::Error>;
+}
+
+fn parse(json: &str, entity_type: &str) -> Result<serde_json::Map<String, Value>, anyhow::Error> {
+    match serde_json::from_str(json)? {
+        Value::Object(fields) => Ok(fields),
+        _ => anyhow::bail!("the audit image of a {entity_type} row is not an object"),
+    }
+}
+
+fn text(fields: &serde_json::Map<String, Value>, name: &str) -> Result<String, anyhow::Error> {
+    fields
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("the audit image has no {name}"))
+}
+
+fn optional_text(fields: &serde_json::Map<String, Value>, name: &str) -> Option<String> {
+    fields.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+impl Restorable for Account {
+    fn from_audit_json(json: &str) -> Result<Self, anyhow::Error> {
+        let fields = parse(json, Self::ENTITY_TYPE)?;
+        Ok(Self {
+            id: text(&fields, "id")?.parse()?,
+            parent_id: optional_text(&fields, "parent_id")
+                .map(|p| p.parse())
+                .transpose()?,
+            name: text(&fields, "name")?,
+            kind: text(&fields, "kind")?.parse()?,
+        })
+    }
+}
+
+impl Restorable for Entry {
+    fn from_audit_json(json: &str) -> Result<Self, anyhow::Error> {
+        let fields = parse(json, Self::ENTITY_TYPE)?;
+        Ok(Self {
+            id: text(&fields, "id")?.parse()?,
+            account_id: text(&fields, "account_id")?.parse()?,
+            posted_on: text(&fields, "posted_on")?.parse()?,
+            amount: fields
+                .get("amount")
+                .and_then(Value::as_i64)
+                .ok_or_else(|| anyhow::anyhow!("the audit image has no amount"))?,
+            payee: optional_text(&fields, "payee"),
+            memo: optional_text(&fields, "memo"),
+        })
+    }
+}
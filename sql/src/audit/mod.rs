@@ -0,0 +1,584 @@
+// append only change log for financial data.
+// every record carries the MAC of the record before it, so editing or deleting
+// a row in the middle of the log breaks the chain and verify_chain will find it.
+// the MACs are keyed and the newest one is kept outside the database, see seal
+
+use hmac::Mac;
+use sqlx::Transaction;
+
+use crate::core::Id;
+
+pub mod entity;
+pub mod revert;
+pub mod seal;
+
+use entity::Audited;
+pub use seal::{AuditHead, AuditKey, AuditSeal, TenantAudit};
+
+pub const AUDIT_LOG_TABLE: &str = "audit_log";
+
+/// prev_hash of the first record in the chain
+pub const GENESIS_HASH: [u8; 32] = [0_u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl std::str::FromStr for ChangeKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(Self::Insert),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            other => Err(anyhow::Error::msg(format!(
+                "unknown change kind '{other}' in the audit log"
+            ))),
+        }
+    }
+}
+
+/// the actor recorded for changes made straight on a store, outside begin_as
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// who the tenant store records as making its changes, see UserTenantStore::begin_as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditContext {
+    /// a user id, or a name for background jobs like "ofx-import"
+    pub actor: String,
+    /// shared by every change in one request or import run, so they can be reverted together
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    pub fn new<S: AsRef<str>>(actor: S) -> Self {
+        Self {
+            actor: actor.as_ref().to_string(),
+            request_id: None,
+        }
+    }
+    pub fn system() -> Self {
+        Self::new(SYSTEM_ACTOR)
+    }
+    pub fn request_id<S: AsRef<str>>(mut self, request_id: S) -> Self {
+        self.request_id = Some(request_id.as_ref().to_string());
+        self
+    }
+}
+
+/// a change that has not been written yet. before and after are the json form of the entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewChange {
+    /// the table name of the entity, see crate::core::Table
+    pub entity_type: String,
+    pub entity_id: String,
+    pub kind: ChangeKind,
+    /// who made the change. a user id, or a name for background jobs like "ofx-import"
+    pub actor: String,
+    pub request_id: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl NewChange {
+    pub fn new<T, S: AsRef<str>>(entity_type: &str, entity_id: &Id<T>, kind: ChangeKind, actor: S) -> Self {
+        Self {
+            entity_type: entity_type.to_string(),
+            entity_id: entity_id.to_string(),
+            kind,
+            actor: actor.as_ref().to_string(),
+            request_id: None,
+            before: None,
+            after: None,
+        }
+    }
+    pub fn request_id<S: AsRef<str>>(mut self, request_id: S) -> Self {
+        self.request_id = Some(request_id.as_ref().to_string());
+        self
+    }
+    fn of<E: Audited>(entity: &E, kind: ChangeKind, context: &AuditContext) -> Self {
+        Self {
+            entity_type: E::ENTITY_TYPE.to_string(),
+            entity_id: entity.audit_id(),
+            kind,
+            actor: context.actor.clone(),
+            request_id: context.request_id.clone(),
+            before: None,
+            after: None,
+        }
+    }
+    pub fn insert<E: Audited>(after: &E, context: &AuditContext) -> Self {
+        Self::of(after, ChangeKind::Insert, context).after(after.audit_json().to_string())
+    }
+    pub fn update<E: Audited>(before: &E, after: &E, context: &AuditContext) -> Self {
+        Self::of(after, ChangeKind::Update, context)
+            .before(before.audit_json().to_string())
+            .after(after.audit_json().to_string())
+    }
+    pub fn delete<E: Audited>(before: &E, context: &AuditContext) -> Self {
+        Self::of(before, ChangeKind::Delete, context).before(before.audit_json().to_string())
+    }
+    pub fn before<S: AsRef<str>>(mut self, json: S) -> Self {
+        self.before = Some(json.as_ref().to_string());
+        self
+    }
+    pub fn after<S: AsRef<str>>(mut self, json: S) -> Self {
+        self.after = Some(json.as_ref().to_string());
+        self
+    }
+}
+
+/// a record read back from the log
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct ChangeRecord {
+    pub seq: i64,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub kind: ChangeKind,
+    pub actor: String,
+    /// unix time in milliseconds
+    pub changed_at: i64,
+    pub request_id: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub prev_hash: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl ChangeRecord {
+    pub fn changed_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp_millis(self.changed_at)
+    }
+}
+
+/// the row as the database stores it. use ChangeRecord
+#[derive(sqlx::FromRow)]
+pub struct ChangeRow {
+    seq: i64,
+    entity_type: String,
+    entity_id: String,
+    kind: String,
+    actor: String,
+    changed_at: i64,
+    request_id: Option<String>,
+    before_json: Option<String>,
+    after_json: Option<String>,
+    prev_hash: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl TryFrom<ChangeRow> for ChangeRecord {
+    type Error = anyhow::Error;
+    fn try_from(r: ChangeRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            seq: r.seq,
+            entity_type: r.entity_type,
+            entity_id: r.entity_id,
+            kind: r.kind.parse()?,
+            actor: r.actor,
+            changed_at: r.changed_at,
+            request_id: r.request_id,
+            before: r.before_json,
+            after: r.after_json,
+            prev_hash: r.prev_hash,
+            hash: r.hash,
+        })
+    }
+}
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+fn hash_field(hasher: &mut HmacSha256, field: Option<&[u8]>) {
+    // length prefix every field so moving bytes between fields changes the hash
+    match field {
+        Some(bytes) => {
+            hasher.update(&[1_u8]);
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+        None => hasher.update(&[0_u8]),
+    }
+}
+
+/// the HMAC-SHA256 of a record under `key`, covering every column plus the previous record's hash
+pub fn chain_hash(key: &AuditKey, prev_hash: &[u8], seq: i64, changed_at: i64, change: &NewChange) -> [u8; 32] {
+    let mut hasher = HmacSha256::new_from_slice(key.as_bytes()).expect("hmac takes keys of any length");
+    hash_field(&mut hasher, Some(prev_hash));
+    hash_field(&mut hasher, Some(&seq.to_le_bytes()));
+    hash_field(&mut hasher, Some(&changed_at.to_le_bytes()));
+    hash_field(&mut hasher, Some(change.entity_type.as_bytes()));
+    hash_field(&mut hasher, Some(change.entity_id.as_bytes()));
+    hash_field(&mut hasher, Some(change.kind.as_str().as_bytes()));
+    hash_field(&mut hasher, Some(change.actor.as_bytes()));
+    hash_field(&mut hasher, change.request_id.as_ref().map(|s| s.as_bytes()));
+    hash_field(&mut hasher, change.before.as_ref().map(|s| s.as_bytes()));
+    hash_field(&mut hasher, change.after.as_ref().map(|s| s.as_bytes()));
+    hasher.finalize().into_bytes().into()
+}
+
+impl ChangeRecord {
+    fn as_new_change(&self) -> NewChange {
+        NewChange {
+            entity_type: self.entity_type.clone(),
+            entity_id: self.entity_id.clone(),
+            kind: self.kind,
+            actor: self.actor.clone(),
+            request_id: self.request_id.clone(),
+            before: self.before.clone(),
+            after: self.after.clone(),
+        }
+    }
+    /// recomputes the hash of this record from its columns
+    pub fn compute_hash(&self, key: &AuditKey) -> [u8; 32] {
+        chain_hash(key, &self.prev_hash, self.seq, self.changed_at, &self.as_new_change())
+    }
+}
+
+/// Walks records in seq order. the error names the first record that was removed or tampered with
+pub fn verify_chain<'a, I>(key: &AuditKey, records: I) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = &'a ChangeRecord>,
+{
+    let mut prev_hash = GENESIS_HASH.to_vec();
+    let mut prev_seq = 0_i64;
+    for r in records {
+        if r.seq != prev_seq + 1 {
+            return Err(anyhow::Error::msg(format!(
+                "audit log is missing records between seq {prev_seq} and {}",
+                r.seq
+            )));
+        }
+        if r.prev_hash != prev_hash {
+            return Err(anyhow::Error::msg(format!(
+                "audit log record {} does not link to the record before it",
+                r.seq
+            )));
+        }
+        if r.compute_hash(key).as_slice() != r.hash.as_slice() {
+            return Err(anyhow::Error::msg(format!(
+                "audit log record {} was modified after it was written",
+                r.seq
+            )));
+        }
+        prev_hash = r.hash.clone();
+        prev_seq = r.seq;
+    }
+    Ok(())
+}
+
+/// Checks a verified chain still reaches the head recorded outside the database. records
+/// after the head are fine, their commit can finish before the head is written
+pub fn verify_head(records: &[ChangeRecord], head: Option<&AuditHead>) -> Result<(), anyhow::Error> {
+    let Some(head) = head else {
+        return Ok(())
+    };
+    let at_head = usize::try_from(head.seq - 1).ok().and_then(|i| records.get(i));
+    match at_head {
+        Some(r) if r.seq == head.seq && r.hash == head.hash => Ok(()),
+        Some(_) => Err(anyhow::Error::msg(format!(
+            "audit log record {} is not the one recorded as its head",
+            head.seq
+        ))),
+        None => Err(anyhow::Error::msg(format!(
+            "audit log ends at seq {} but its head is at seq {}, records were removed from its end",
+            records.last().map_or(0, |r| r.seq),
+            head.seq
+        ))),
+    }
+}
+
+// identifiers are left unquoted since mysql reads "name" as a string.
+// postgres numbers its binds, everyone else uses ?
+fn bind<DB: sqlx::Database>(n: usize) -> String {
+    if DB::NAME == "PostgreSQL" {
+        format!("${n}")
+    } else {
+        "?".to_string()
+    }
+}
+
+const SELECT_COLUMNS: &str = "SELECT seq,entity_type,entity_id,kind,actor,changed_at,request_id,before_json,after_json,prev_hash,hash FROM audit_log";
+
+/// Appends a change to the log. Call this inside the same transaction as the change itself
+/// so the log and the data can never disagree.
+pub async fn append<DB>(
+    tx: &mut Transaction<'_, DB>,
+    key: &AuditKey,
+    change: NewChange,
+) -> Result<ChangeRecord, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Option<String>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Vec<u8>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> (i64, Vec<u8>): sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let last: Option<(i64, Vec<u8>)> = sqlx::query_as(
+        "SELECT seq,hash FROM audit_log ORDER BY seq DESC LIMIT 1",
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    let (prev_seq, prev_hash) = last.unwrap_or((0, GENESIS_HASH.to_vec()));
+
+    let seq = prev_seq + 1;
+    let changed_at = chrono::Utc::now().timestamp_millis();
+    let hash = chain_hash(key, &prev_hash, seq, changed_at, &change);
+    let record = ChangeRecord {
+        seq,
+        entity_type: change.entity_type,
+        entity_id: change.entity_id,
+        kind: change.kind,
+        actor: change.actor,
+        changed_at,
+        request_id: change.request_id,
+        before: change.before,
+        after: change.after,
+        prev_hash,
+        hash: hash.to_vec(),
+    };
+    // the primary key on seq makes a concurrent append fail instead of forking the chain
+    insert_record(tx, &record).await?;
+    Ok(record)
+}
+
+async fn insert_record<DB>(tx: &mut Transaction<'_, DB>, record: &ChangeRecord) -> Result<(), anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Option<String>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Vec<u8>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+{
+    let binds: Vec<String> = (1..=11).map(bind::<DB>).collect();
+    let sql = format!(
+        "INSERT INTO audit_log (seq,entity_type,entity_id,kind,actor,changed_at,request_id,before_json,after_json,prev_hash,hash) VALUES ({})",
+        binds.join(",")
+    );
+    sqlx::query(&sql)
+        .bind(record.seq)
+        .bind(record.entity_type.clone())
+        .bind(record.entity_id.clone())
+        .bind(record.kind.as_str().to_string())
+        .bind(record.actor.clone())
+        .bind(record.changed_at)
+        .bind(record.request_id.clone())
+        .bind(record.before.clone())
+        .bind(record.after.clone())
+        .bind(record.prev_hash.clone())
+        .bind(record.hash.clone())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Copies records read from another log, ex: out of a backup, into an empty log as they are.
+/// their chain is checked first, so the copy verifies exactly like the original
+pub async fn restore_log<DB>(
+    tx: &mut Transaction<'_, DB>,
+    key: &AuditKey,
+    records: &[ChangeRecord],
+) -> Result<(), anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Option<String>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> Vec<u8>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'q> i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> (i64, Vec<u8>): sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    verify_chain(key, records)?;
+    let last: Option<(i64, Vec<u8>)> = sqlx::query_as(
+        "SELECT seq,hash FROM audit_log ORDER BY seq DESC LIMIT 1",
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    if last.is_some() {
+        anyhow::bail!("the audit log already has records, it can only be restored into an empty one");
+    }
+    for record in records {
+        insert_record(tx, record).await?;
+    }
+    Ok(())
+}
+
+/// Every change to one entity, oldest first
+pub async fn history<T, DB>(
+    tx: &mut Transaction<'_, DB>,
+    entity_type: &str,
+    entity_id: &Id<T>,
+) -> Result<Vec<ChangeRecord>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    history_of(tx, entity_type, &entity_id.to_string()).await
+}
+
+/// history with the entity id as the log stores it, for callers without a typed id
+pub async fn history_of<DB>(
+    tx: &mut Transaction<'_, DB>,
+    entity_type: &str,
+    entity_id: &str,
+) -> Result<Vec<ChangeRecord>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let sql = format!(
+        "{SELECT_COLUMNS} WHERE entity_type = {} AND entity_id = {} ORDER BY seq ASC",
+        bind::<DB>(1),
+        bind::<DB>(2)
+    );
+    let rows: Vec<ChangeRow> = sqlx::query_as(&sql)
+        .bind(entity_type.to_string())
+        .bind(entity_id.to_string())
+        .fetch_all(&mut **tx)
+        .await?;
+    rows.into_iter().map(ChangeRecord::try_from).collect()
+}
+
+/// Every change recorded under one request id, oldest first. an import run or a form post
+/// shares one request id, so this is the unit that "undo the import" works on
+pub async fn changes_for_request<DB>(
+    tx: &mut Transaction<'_, DB>,
+    request_id: &str,
+) -> Result<Vec<ChangeRecord>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> String: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let sql = format!(
+        "{SELECT_COLUMNS} WHERE request_id = {} ORDER BY seq ASC",
+        bind::<DB>(1)
+    );
+    let rows: Vec<ChangeRow> = sqlx::query_as(&sql)
+        .bind(request_id.to_string())
+        .fetch_all(&mut **tx)
+        .await?;
+    rows.into_iter().map(ChangeRecord::try_from).collect()
+}
+
+/// The record at seq, if the log has one
+pub async fn record_at<DB>(tx: &mut Transaction<'_, DB>, seq: i64) -> Result<Option<ChangeRecord>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'q> i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let sql = format!("{SELECT_COLUMNS} WHERE seq = {}", bind::<DB>(1));
+    let row: Option<ChangeRow> = sqlx::query_as(&sql).bind(seq).fetch_optional(&mut **tx).await?;
+    row.map(ChangeRecord::try_from).transpose()
+}
+
+/// The whole log, oldest first
+pub async fn read_log<DB>(tx: &mut Transaction<'_, DB>) -> Result<Vec<ChangeRecord>, anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let sql = format!("{SELECT_COLUMNS} ORDER BY seq ASC");
+    let rows: Vec<ChangeRow> = sqlx::query_as(&sql).fetch_all(&mut **tx).await?;
+    rows.into_iter().map(ChangeRecord::try_from).collect()
+}
+
+/// Reads the whole log and checks the hash chain under `key`, and that it reaches `head`
+pub async fn verify<DB>(
+    tx: &mut Transaction<'_, DB>,
+    key: &AuditKey,
+    head: Option<&AuditHead>,
+) -> Result<(), anyhow::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut <DB as sqlx::Database>::Connection: sqlx::Executor<'c, Database = DB>,
+    for<'q> <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+    for<'r> ChangeRow: sqlx::FromRow<'r, <DB as sqlx::Database>::Row>,
+{
+    let records = read_log(tx).await?;
+    verify_chain(key, &records)?;
+    verify_head(&records, head)
+}
+
+#[test]
+pub fn test_audit_hash_chain() -> Result<(), anyhow::Error> {
+    let id = Id::<crate::core::models::Account>::new_v7();
+    let key = AuditKey::from_bytes([7_u8; 32]);
+    let mut records: Vec<ChangeRecord> = Vec::new();
+    let mut prev_hash = GENESIS_HASH.to_vec();
+    for (seq, kind) in [ChangeKind::Insert, ChangeKind::Update, ChangeKind::Delete]
+        .into_iter()
+        .enumerate()
+    {
+        let seq = seq as i64 + 1;
+        let change = NewChange::new("accounts", &id, kind, "test").after(format!("{{\"v\":{seq}}}"));
+        let hash = chain_hash(&key, &prev_hash, seq, 1_000 + seq, &change);
+        records.push(ChangeRecord {
+            seq,
+            entity_type: change.entity_type,
+            entity_id: change.entity_id,
+            kind,
+            actor: change.actor,
+            changed_at: 1_000 + seq,
+            request_id: None,
+            before: None,
+            after: change.after,
+            prev_hash: prev_hash.clone(),
+            hash: hash.to_vec(),
+        });
+        prev_hash = hash.to_vec();
+    }
+    verify_chain(&key, &records)?;
+    // the chain is only as good as the key
+    assert!(verify_chain(&AuditKey::unsealed(), &records).is_err());
+
+    let mut tampered = records.clone();
+    tampered[1].after = Some("{\"v\":100}".to_string());
+    assert!(verify_chain(&key, &tampered).is_err());
+
+    let mut removed = records.clone();
+    removed.remove(1);
+    assert!(verify_chain(&key, &removed).is_err());
+
+    // cutting the end off leaves a valid chain, only the head catches it
+    let head = AuditHead::of(&records[2]);
+    verify_head(&records, Some(&head))?;
+    verify_chain(&key, &records[..2])?;
+    assert!(verify_head(&records[..2], Some(&head)).is_err());
+    Ok(())
+}
@@ -0,0 +1,281 @@
+// undo and redo built on the change log.
+// a revert never deletes log records. it produces new compensating changes that are
+// written like any other edit, so undoing a revert (redo) is just another revert.
+
+use chrono::NaiveDate;
+
+use super::entity::{Audited, Restorable};
+use super::{AuditContext, ChangeKind, ChangeRecord, NewChange};
+use crate::core::models::{Account, Entry};
+use crate::drivers::{TenantTransaction, UserTenantStore};
+
+/// request id given to the compensating changes for a reverted request
+pub fn revert_request_id(request_id: &str) -> String {
+    format!("revert:{request_id}")
+}
+
+/// request id given to the compensating change for a single reverted record
+pub fn revert_change_id(seq: i64) -> String {
+    format!("revert:seq:{seq}")
+}
+
+/// The inverse of a single change. inserts become deletes, deletes re-insert the old state
+/// and updates swap before and after.
+pub fn compensating_change<S: AsRef<str>>(
+    record: &ChangeRecord,
+    actor: S,
+) -> Result<NewChange, anyhow::Error> {
+    let (kind, before, after) = match record.kind {
+        ChangeKind::Insert => (ChangeKind::Delete, record.after.clone(), None),
+        ChangeKind::Update => (
+            ChangeKind::Update,
+            record.after.clone(),
+            record.before.clone(),
+        ),
+        ChangeKind::Delete => (ChangeKind::Insert, None, record.before.clone()),
+    };
+    if kind != ChangeKind::Delete && after.is_none() {
+        return Err(anyhow::Error::msg(format!(
+            "audit log record {} has no prior state, it cannot be reverted",
+            record.seq
+        )));
+    }
+    Ok(NewChange {
+        entity_type: record.entity_type.clone(),
+        entity_id: record.entity_id.clone(),
+        kind,
+        actor: actor.as_ref().to_string(),
+        request_id: record
+            .request_id
+            .as_deref()
+            .map(revert_request_id)
+            .or_else(|| Some(revert_change_id(record.seq))),
+        before,
+        after,
+    })
+}
+
+/// Builds the compensating changes for a group of records, newest first, so they can be
+/// applied in order inside one transaction.
+///
+/// latest maps each touched entity to the newest record in the log for it. if an entity was
+/// edited again after the group, reverting would silently throw that edit away, so it is refused.
+pub fn compensating_changes<S: AsRef<str>>(
+    records: &[ChangeRecord],
+    latest: &[ChangeRecord],
+    actor: S,
+) -> Result<Vec<NewChange>, anyhow::Error> {
+    let mut records: Vec<&ChangeRecord> = records.iter().collect();
+    records.sort_by_key(|r| std::cmp::Reverse(r.seq));
+    for r in &records {
+        let newest = latest
+            .iter()
+            .filter(|l| l.entity_type == r.entity_type && l.entity_id == r.entity_id)
+            .map(|l| l.seq)
+            .max()
+            .unwrap_or(r.seq);
+        if !records.iter().any(|o| o.seq == newest) {
+            return Err(anyhow::Error::msg(format!(
+                "{} {} was changed again by audit log record {newest}. revert that change first",
+                r.entity_type, r.entity_id
+            )));
+        }
+    }
+    records
+        .into_iter()
+        .map(|r| compensating_change(r, actor.as_ref()))
+        .collect()
+}
+
+/// Undoes a whole request through the tenant store, in one transaction recorded as actor
+/// under revert_request_id(request_id). nothing is written if any change is refused.
+///
+/// entries posted on or before closed_through are in a closed period and are never touched,
+/// and neither is a row that no longer matches what the log says it should be.
+pub async fn revert_request<S: AsRef<str>>(
+    store: &dyn UserTenantStore,
+    request_id: &str,
+    actor: S,
+    closed_through: Option<NaiveDate>,
+) -> Result<Vec<NewChange>, anyhow::Error> {
+    let context = AuditContext::new(actor.as_ref()).request_id(revert_request_id(request_id));
+    let tx = store.begin_as(context).await?;
+    let records = tx.request_changes(request_id).await?;
+    if records.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "no changes were recorded for request {request_id}"
+        )));
+    }
+    let mut latest: Vec<ChangeRecord> = Vec::new();
+    for r in &records {
+        if !latest
+            .iter()
+            .any(|l| l.entity_type == r.entity_type && l.entity_id == r.entity_id)
+        {
+            latest.extend(tx.history(&r.entity_type, &r.entity_id).await?.pop());
+        }
+    }
+    let plan = compensating_changes(&records, &latest, actor)?;
+    apply(tx.as_ref(), &plan, closed_through).await?;
+    tx.commit().await?;
+    Ok(plan)
+}
+
+/// Undoes the one change at seq, recorded as actor under revert_change_id(seq), with the same
+/// checks as revert_request. refused if its entity was changed again after it
+pub async fn revert_change<S: AsRef<str>>(
+    store: &dyn UserTenantStore,
+    seq: i64,
+    actor: S,
+    closed_through: Option<NaiveDate>,
+) -> Result<NewChange, anyhow::Error> {
+    let context = AuditContext::new(actor.as_ref()).request_id(revert_change_id(seq));
+    let tx = store.begin_as(context).await?;
+    let record = tx
+        .audit_record(seq)
+        .await?
+        .ok_or_else(|| anyhow::Error::msg(format!("the audit log has no record {seq}")))?;
+    let latest: Vec<ChangeRecord> = tx
+        .history(&record.entity_type, &record.entity_id)
+        .await?
+        .pop()
+        .into_iter()
+        .collect();
+    let mut plan = compensating_changes(std::slice::from_ref(&record), &latest, actor)?;
+    apply(tx.as_ref(), &plan, closed_through).await?;
+    tx.commit().await?;
+    Ok(plan.remove(0))
+}
+
+/// Writes compensating changes to the entity tables in order, each through the store so it is
+/// logged like any other edit. only accounts and entries can be reverted.
+pub async fn apply(
+    tx: &dyn TenantTransaction,
+    plan: &[NewChange],
+    closed_through: Option<NaiveDate>,
+) -> Result<(), anyhow::Error> {
+    for change in plan {
+        match change.entity_type.as_str() {
+            Account::ENTITY_TYPE => {
+                let current = tx.get_account(&change.entity_id.parse()?).await?;
+                check_current(change, current.as_ref())?;
+                let applied = match change.kind {
+                    ChangeKind::Insert => {
+                        tx.insert_account(&restored(change)?).await?;
+                        true
+                    }
+                    ChangeKind::Update => tx.update_account(&restored(change)?).await?,
+                    ChangeKind::Delete => tx.delete_account(&change.entity_id.parse()?).await?,
+                };
+                if !applied {
+                    return Err(anyhow::Error::msg(format!(
+                        "account {} could not be reverted, entries are still posted to it",
+                        change.entity_id
+                    )));
+                }
+            }
+            Entry::ENTITY_TYPE => {
+                let current = tx.get_entry(&change.entity_id.parse()?).await?;
+                check_current(change, current.as_ref())?;
+                let images = [change.before.as_deref(), change.after.as_deref()];
+                for image in images.into_iter().flatten() {
+                    let entry = Entry::from_audit_json(image)?;
+                    if closed_through.is_some_and(|closed| entry.posted_on <= closed) {
+                        return Err(anyhow::Error::msg(format!(
+                            "entry {} is posted on {}, in a closed period",
+                            change.entity_id, entry.posted_on
+                        )));
+                    }
+                }
+                match change.kind {
+                    ChangeKind::Insert => tx.insert_entry(&restored(change)?).await?,
+                    ChangeKind::Update => {
+                        tx.update_entry(&restored(change)?).await?;
+                    }
+                    ChangeKind::Delete => {
+                        tx.delete_entry(&change.entity_id.parse()?).await?;
+                    }
+                }
+            }
+            other => {
+                return Err(anyhow::Error::msg(format!(
+                    "changes to {other} can not be reverted"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restored<E: Restorable>(change: &NewChange) -> Result<E, anyhow::Error> {
+    let after = change
+        .after
+        .as_deref()
+        .ok_or_else(|| anyhow::Error::msg("a compensating change has no state to write"))?;
+    E::from_audit_json(after)
+}
+
+/// the row has to be exactly what the change expects to replace, or gone if it re-inserts
+fn check_current<E: Audited>(change: &NewChange, current: Option<&E>) -> Result<(), anyhow::Error> {
+    let expected = change
+        .before
+        .as_deref()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .transpose()?;
+    if expected != current.map(Audited::audit_json) {
+        return Err(anyhow::Error::msg(format!(
+            "{} {} was changed since the audit log recorded it",
+            change.entity_type, change.entity_id
+        )));
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_audit_compensating_changes() -> Result<(), anyhow::Error> {
+    let record =
+        |seq, entity_id: &str, kind, before: Option<&str>, after: Option<&str>| ChangeRecord {
+            seq,
+            entity_type: "entries".to_string(),
+            entity_id: entity_id.to_string(),
+            kind,
+            actor: "ofx-import".to_string(),
+            changed_at: 0,
+            request_id: Some("import-1".to_string()),
+            before: before.map(|s| s.to_string()),
+            after: after.map(|s| s.to_string()),
+            prev_hash: vec![],
+            hash: vec![],
+        };
+    let batch = vec![
+        record(1, "a", ChangeKind::Insert, None, Some("{\"amount\":1}")),
+        record(
+            2,
+            "a",
+            ChangeKind::Update,
+            Some("{\"amount\":1}"),
+            Some("{\"amount\":2}"),
+        ),
+        record(3, "b", ChangeKind::Delete, Some("{\"amount\":3}"), None),
+    ];
+    let plan = compensating_changes(&batch, &batch, "me")?;
+    assert_eq!(plan.len(), 3);
+    // newest first
+    assert_eq!(plan[0].kind, ChangeKind::Insert);
+    assert_eq!(plan[0].after.as_deref(), Some("{\"amount\":3}"));
+    assert_eq!(plan[1].after.as_deref(), Some("{\"amount\":1}"));
+    assert_eq!(plan[2].kind, ChangeKind::Delete);
+    assert_eq!(plan[2].request_id.as_deref(), Some("revert:import-1"));
+
+    // a later edit outside the batch blocks the revert
+    let mut latest = batch.clone();
+    latest.push(record(
+        9,
+        "a",
+        ChangeKind::Update,
+        Some("{\"amount\":2}"),
+        Some("{\"amount\":5}"),
+    ));
+    assert!(compensating_changes(&batch, &latest, "me").is_err());
+    Ok(())
+}
@@ -0,0 +1,270 @@
+// what keeps an audit log honest against someone who can write to the tenant database.
+// every record is MACed under a key per tenant that never goes into the database, so the log
+// can not be rewritten and re-chained without it. the seq and MAC of the newest record, the head,
+// is kept in a file outside the database, so cutting records off the end of the log is noticed too
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::core::models::UserId;
+
+use super::ChangeRecord;
+
+/// a 256 bit HMAC-SHA256 key for one tenant's audit log. zeroed on drop and never printed
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuditKey(zeroize::Zeroizing<[u8; 32]>);
+
+impl AuditKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(zeroize::Zeroizing::new(bytes))
+    }
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+    /// the key of stores opened without an AuditSeal. it is no secret, so their log only
+    /// catches damage done by accident
+    pub fn unsealed() -> Self {
+        Self::from_bytes([0_u8; 32])
+    }
+}
+impl From<[u8; 32]> for AuditKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+impl From<zeroize::Zeroizing<[u8; 32]>> for AuditKey {
+    fn from(bytes: zeroize::Zeroizing<[u8; 32]>) -> Self {
+        Self(bytes)
+    }
+}
+impl std::fmt::Debug for AuditKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuditKey(..)")
+    }
+}
+
+/// the seq and MAC of the newest record in a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditHead {
+    pub seq: i64,
+    pub hash: Vec<u8>,
+}
+
+impl AuditHead {
+    pub fn of(record: &ChangeRecord) -> Self {
+        Self {
+            seq: record.seq,
+            hash: record.hash.clone(),
+        }
+    }
+}
+
+/// the file a tenant's audit head is kept in, as `<seq> <hex MAC>`. it belongs somewhere the
+/// tenant database's users can not write, or it proves nothing
+#[derive(Debug)]
+pub struct AuditHeadFile {
+    path: PathBuf,
+    // commits finish in any order, the head only moves forward under this
+    lock: std::sync::Mutex<()>,
+}
+
+impl AuditHeadFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            lock: std::sync::Mutex::new(()),
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// none until the first record was written
+    pub fn read(&self) -> Result<Option<AuditHead>, anyhow::Error> {
+        let s = match std::fs::read_to_string(&self.path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("while reading {}", self.path.display())),
+        };
+        let head = s
+            .trim()
+            .split_once(' ')
+            .and_then(|(seq, hash)| Some(AuditHead { seq: seq.parse().ok()?, hash: from_hex(hash)? }));
+        head.map(Some)
+            .ok_or_else(|| anyhow::Error::msg(format!("{} does not hold an audit head", self.path.display())))
+    }
+    /// moves the head to `head`, unless a newer record is already recorded
+    pub fn advance(&self, head: &AuditHead) -> Result<(), anyhow::Error> {
+        let _held = self.lock.lock().unwrap();
+        if self.read()?.is_some_and(|current| current.seq >= head.seq) {
+            return Ok(())
+        }
+        self.write(Some(head))
+    }
+    /// replaces the head, ex: after a restore put an older log back. none forgets it
+    pub fn reset(&self, head: Option<&AuditHead>) -> Result<(), anyhow::Error> {
+        let _held = self.lock.lock().unwrap();
+        self.write(head)
+    }
+    // through a temporary file so a crash leaves the old head or the new one, never half of it
+    fn write(&self, head: Option<&AuditHead>) -> Result<(), anyhow::Error> {
+        let Some(head) = head else {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("while removing {}", self.path.display()))
+                }
+                _ => Ok(()),
+            }
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("while creating {}", parent.display()))?;
+        }
+        let partial = crate::encryption::side_file(&self.path, ".partial");
+        let hex: String = head.hash.iter().map(|b| format!("{b:02x}")).collect();
+        std::fs::write(&partial, format!("{} {hex}\n", head.seq))
+            .with_context(|| format!("while writing {}", partial.display()))?;
+        std::fs::rename(&partial, &self.path).with_context(|| format!("while writing {}", self.path.display()))
+    }
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// how one tenant's audit log is sealed: the key its records are MACed with, and the file its
+/// head is kept in. without a head file the MACs are still checked but truncation is not
+#[derive(Debug, Clone)]
+pub struct AuditSeal {
+    key: AuditKey,
+    head: Option<Arc<AuditHeadFile>>,
+}
+
+impl AuditSeal {
+    pub fn new(key: AuditKey, head: Option<AuditHeadFile>) -> Self {
+        Self {
+            key,
+            head: head.map(Arc::new),
+        }
+    }
+    /// see AuditKey::unsealed
+    pub fn unsealed() -> Self {
+        Self::new(AuditKey::unsealed(), None)
+    }
+    pub fn key(&self) -> &AuditKey {
+        &self.key
+    }
+    /// the recorded head, none without a head file or before the first record
+    pub fn read_head(&self) -> Result<Option<AuditHead>, anyhow::Error> {
+        match &self.head {
+            Some(head) => head.read(),
+            None => Ok(None),
+        }
+    }
+    /// call once `record` is committed
+    pub fn committed(&self, record: &ChangeRecord) -> Result<(), anyhow::Error> {
+        match &self.head {
+            Some(head) => head.advance(&AuditHead::of(record)),
+            None => Ok(()),
+        }
+    }
+    /// call once a restored log is committed, with its last record. the head follows the log
+    /// back, a restore is the one time it may go backwards
+    pub fn restored(&self, last: Option<&ChangeRecord>) -> Result<(), anyhow::Error> {
+        match &self.head {
+            Some(head) => head.reset(last.map(AuditHead::of).as_ref()),
+            None => Ok(()),
+        }
+    }
+}
+impl PartialEq for AuditSeal {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.head.as_ref().map(|h| h.path()) == other.head.as_ref().map(|h| h.path())
+    }
+}
+impl Eq for AuditSeal {}
+
+/// the seal of the store a transaction was begun on. what the transaction writes only moves
+/// the head once the whole transaction commits
+pub(crate) struct PendingSeal {
+    seal: AuditSeal,
+    // the newest record written, and whether a restore came before it
+    newest: std::sync::Mutex<Option<(Option<ChangeRecord>, bool)>>,
+}
+
+impl PendingSeal {
+    pub fn new(seal: AuditSeal) -> Self {
+        Self {
+            seal,
+            newest: std::sync::Mutex::new(None),
+        }
+    }
+    pub fn key(&self) -> &AuditKey {
+        self.seal.key()
+    }
+    pub fn read_head(&self) -> Result<Option<AuditHead>, anyhow::Error> {
+        self.seal.read_head()
+    }
+    pub fn committed(&self, record: &ChangeRecord) -> Result<(), anyhow::Error> {
+        let mut newest = self.newest.lock().unwrap();
+        let restored = newest.as_ref().is_some_and(|(_, restored)| *restored);
+        *newest = Some((Some(record.clone()), restored));
+        Ok(())
+    }
+    pub fn restored(&self, last: Option<&ChangeRecord>) -> Result<(), anyhow::Error> {
+        *self.newest.lock().unwrap() = Some((last.cloned(), true));
+        Ok(())
+    }
+    /// call once the transaction committed
+    pub fn commit(self) -> Result<(), anyhow::Error> {
+        match self.newest.into_inner().unwrap() {
+            Some((last, true)) => self.seal.restored(last.as_ref()),
+            Some((Some(record), false)) => self.seal.committed(&record),
+            _ => Ok(()),
+        }
+    }
+}
+
+type AuditKeyFn = dyn Fn(&UserId) -> Result<AuditKey, anyhow::Error> + Send + Sync;
+
+/// seals the audit logs of every tenant a factory opens. the key for a user has to be the same
+/// every time, and the heads go in `head_directory` as `<user id>.audit-head`
+#[derive(Clone)]
+pub struct TenantAudit {
+    key_for: Arc<AuditKeyFn>,
+    head_directory: PathBuf,
+}
+
+impl TenantAudit {
+    pub fn new(
+        key_for: impl Fn(&UserId) -> Result<AuditKey, anyhow::Error> + Send + Sync + 'static,
+        head_directory: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            key_for: Arc::new(key_for),
+            head_directory: head_directory.into(),
+        }
+    }
+    /// keys derived from the vault master key, see vault::crypto::derive_tenant_audit_key
+    #[cfg(feature = "vault")]
+    pub fn from_master_key(master_key: zeroize::Zeroizing<[u8; 32]>, head_directory: impl Into<PathBuf>) -> Self {
+        Self::new(
+            move |id| Ok(vault::crypto::derive_tenant_audit_key(&master_key, id.as_bytes())?.into()),
+            head_directory,
+        )
+    }
+    pub fn head_file(&self, id: &UserId) -> PathBuf {
+        self.head_directory.join(format!("{}.audit-head", id.value))
+    }
+    pub fn seal_for(&self, id: &UserId) -> Result<AuditSeal, anyhow::Error> {
+        Ok(AuditSeal::new((self.key_for)(id)?, Some(AuditHeadFile::new(self.head_file(id)))))
+    }
+}
+impl std::fmt::Debug for TenantAudit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TenantAudit").field("head_directory", &self.head_directory).finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,380 @@
+// backups of tenant databases.
+// sqlite tenants are copied whole with VACUUM INTO, one consistent snapshot taken while the app
+// keeps writing. postgres and mysql tenants are exported through TenantOperations into a json
+// bundle, which restores onto any backend. either is written behind a header saying which it is,
+// and can be sealed with AES-256-GCM under a vault key
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::audit::ChangeRecord;
+use crate::core::models::{Account, AccountId, Entry, Passkey, UserId};
+use crate::drivers::{DatabaseUrl, UserTenantStore};
+use crate::encryption::DatabaseKey;
+use crate::user::User;
+
+/// bumped when the layout of TenantBundle changes
+pub const BUNDLE_FORMAT: u32 = 2;
+
+/// everything in one tenant database, in a form any backend can import
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TenantBundle {
+    pub format: u32,
+    /// the tenant migration the data was exported at
+    pub schema_version: i64,
+    pub user_id: UserId,
+    /// unix milliseconds
+    pub exported_at: i64,
+    /// revoked passkeys are left out, and last_used_at is not restored
+    pub passkeys: Vec<Passkey>,
+    pub accounts: Vec<Account>,
+    pub entries: Vec<Entry>,
+    /// the whole audit log, restored as it was so its hash chain still verifies.
+    /// it keeps the history of the revoked passkeys left out above
+    pub audit: Vec<ChangeRecord>,
+}
+
+/// reads the whole tenant inside one transaction
+pub async fn export_tenant(
+    store: &dyn UserTenantStore,
+    user_id: &UserId,
+) -> Result<TenantBundle, anyhow::Error> {
+    let tx = store.begin().await?;
+    if tx.get_user(user_id).await?.is_none() {
+        anyhow::bail!("the tenant has no user {user_id}");
+    }
+    let accounts = tx.list_accounts().await?;
+    let mut entries = Vec::new();
+    for account in &accounts {
+        entries.extend(tx.list_entries(&account.id).await?);
+    }
+    let bundle = TenantBundle {
+        format: BUNDLE_FORMAT,
+        schema_version: tx.schema_version().await?,
+        user_id: user_id.clone(),
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        passkeys: tx.get_passkeys(user_id).await?,
+        accounts,
+        entries,
+        audit: tx.audit_log().await?,
+    };
+    tx.rollback().await?;
+    Ok(bundle)
+}
+
+/// writes a bundle into an empty tenant, all or nothing. the tenant has to be at the
+/// schema version the bundle was exported at. the rows are written without being logged,
+/// the audit log comes from the bundle
+pub async fn import_tenant(
+    store: &dyn UserTenantStore,
+    bundle: &TenantBundle,
+) -> Result<(), anyhow::Error> {
+    check_bundle(bundle, store.schema_version().await?)?;
+    if store.get_user(&bundle.user_id).await?.is_some() {
+        anyhow::bail!(
+            "the tenant already has user {}, restore into an empty tenant",
+            bundle.user_id
+        );
+    }
+    let tx = store.begin_restore().await?;
+    tx.insert_user(&User {
+        id: bundle.user_id.clone(),
+    })
+    .await?;
+    for account in parents_first(&bundle.accounts)? {
+        tx.insert_account(account).await?;
+    }
+    for entry in &bundle.entries {
+        tx.insert_entry(entry).await?;
+    }
+    for passkey in &bundle.passkeys {
+        tx.save_passkey(&bundle.user_id, passkey).await?;
+    }
+    tx.restore_audit_log(&bundle.audit).await?;
+    tx.commit().await
+}
+
+/// fails unless this build can import the bundle into a tenant at `schema_version`
+pub fn check_bundle(bundle: &TenantBundle, schema_version: i64) -> Result<(), anyhow::Error> {
+    if bundle.format != BUNDLE_FORMAT {
+        anyhow::bail!(
+            "the bundle is format {}, this build reads format {BUNDLE_FORMAT}",
+            bundle.format
+        );
+    }
+    if bundle.schema_version != schema_version {
+        anyhow::bail!(
+            "the bundle was exported at schema version {}, the tenant is at {schema_version}",
+            bundle.schema_version
+        );
+    }
+    Ok(())
+}
+
+// accounts reference their parent, so a parent has to be inserted before its children
+fn parents_first(accounts: &[Account]) -> Result<Vec<&Account>, anyhow::Error> {
+    let mut done: std::collections::HashSet<&AccountId> = Default::default();
+    let mut ordered = Vec::with_capacity(accounts.len());
+    while ordered.len() < accounts.len() {
+        let before = ordered.len();
+        for account in accounts {
+            let ready = account.parent_id.as_ref().is_none_or(|p| done.contains(p));
+            if ready && !done.contains(&account.id) {
+                done.insert(&account.id);
+                ordered.push(account);
+            }
+        }
+        if ordered.len() == before {
+            anyhow::bail!("the bundle has accounts whose parent is missing or circular");
+        }
+    }
+    Ok(ordered)
+}
+
+const SEALED_MAGIC: &[u8] = b"JTMBSEAL\x01";
+
+/// AES-256-GCM, with a random nonce stored ahead of the ciphertext
+pub fn seal(key: &DatabaseKey, plain: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    use aws_lc_rs::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+    let sealing = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, key.as_bytes())
+            .map_err(|_| anyhow::Error::msg("invalid archive key"))?,
+    );
+    let mut nonce = [0_u8; NONCE_LEN];
+    aws_lc_rs::rand::fill(&mut nonce)
+        .map_err(|_| anyhow::Error::msg("could not generate a nonce"))?;
+    let mut body = plain.to_vec();
+    sealing
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(SEALED_MAGIC),
+            &mut body,
+        )
+        .map_err(|_| anyhow::Error::msg("could not seal the archive"))?;
+    Ok([SEALED_MAGIC, &nonce, &body].concat())
+}
+
+/// the reverse of seal. fails on the wrong key or a modified archive
+pub fn unseal(key: &DatabaseKey, sealed: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    use aws_lc_rs::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+    let rest = sealed
+        .strip_prefix(SEALED_MAGIC)
+        .ok_or_else(|| anyhow::Error::msg("the archive is not sealed"))?;
+    if rest.len() < NONCE_LEN {
+        anyhow::bail!("the sealed archive is truncated");
+    }
+    let (nonce, body) = rest.split_at(NONCE_LEN);
+    let opening = LessSafeKey::new(
+        UnboundKey::new(&AES_256_GCM, key.as_bytes())
+            .map_err(|_| anyhow::Error::msg("invalid archive key"))?,
+    );
+    let mut body = body.to_vec();
+    let plain = opening
+        .open_in_place(
+            Nonce::try_assume_unique_for_key(nonce)
+                .map_err(|_| anyhow::Error::msg("invalid nonce"))?,
+            Aad::from(SEALED_MAGIC),
+            &mut body,
+        )
+        .map_err(|_| anyhow::Error::msg("the archive key is wrong or the archive was modified"))?
+        .len();
+    body.truncate(plain);
+    Ok(body)
+}
+
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(SEALED_MAGIC)
+}
+
+const ARCHIVE_MAGIC: &[u8] = b"JTMBARCH\x01";
+
+/// what an archive holds, recorded in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// a TenantBundle as json
+    Bundle,
+    /// a copy of a sqlite tenant file
+    SqliteFile,
+}
+impl ArchiveKind {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Bundle => b'B',
+            Self::SqliteFile => b'S',
+        }
+    }
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'B' => Some(Self::Bundle),
+            b'S' => Some(Self::SqliteFile),
+            _ => None,
+        }
+    }
+}
+
+/// the header and payload, sealed if there is a key
+pub fn write_archive(
+    kind: ArchiveKind,
+    payload: &[u8],
+    key: Option<&DatabaseKey>,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let plain = [ARCHIVE_MAGIC, &[kind.tag()], payload].concat();
+    match key {
+        Some(key) => seal(key, &plain),
+        None => Ok(plain),
+    }
+}
+
+/// the reverse of write_archive. a key is only needed for a sealed archive
+pub fn open_archive(
+    bytes: &[u8],
+    key: Option<&DatabaseKey>,
+) -> Result<(ArchiveKind, Vec<u8>), anyhow::Error> {
+    let plain = match (is_sealed(bytes), key) {
+        (true, Some(key)) => unseal(key, bytes)?,
+        (true, None) => anyhow::bail!("the archive is sealed, an archive key is needed"),
+        (false, _) => bytes.to_vec(),
+    };
+    let Some([tag, payload @ ..]) = plain.strip_prefix(ARCHIVE_MAGIC) else {
+        anyhow::bail!("not a tenant backup, the archive header is missing");
+    };
+    let kind = ArchiveKind::from_tag(*tag)
+        .ok_or_else(|| anyhow::Error::msg("the archive holds a kind of backup this build does not know"))?;
+    Ok((kind, payload.to_vec()))
+}
+
+/// the bundle as an archive, sealed if there is a key
+pub fn bundle_to_bytes(
+    bundle: &TenantBundle,
+    key: Option<&DatabaseKey>,
+) -> Result<Vec<u8>, anyhow::Error> {
+    write_archive(ArchiveKind::Bundle, &serde_json::to_vec(bundle)?, key)
+}
+
+pub fn bundle_from_bytes(
+    bytes: &[u8],
+    key: Option<&DatabaseKey>,
+) -> Result<TenantBundle, anyhow::Error> {
+    match open_archive(bytes, key)? {
+        (ArchiveKind::Bundle, json) => bundle_from_json(&json),
+        (kind, _) => anyhow::bail!("the archive holds a {kind:?}, not a bundle"),
+    }
+}
+
+/// the payload of an opened bundle archive
+pub fn bundle_from_json(json: &[u8]) -> Result<TenantBundle, anyhow::Error> {
+    serde_json::from_slice(json).context("while reading a tenant bundle")
+}
+
+/// backs up a tenant into `directory` and returns the file: a copy of a sqlite file, or a bundle
+/// for the other backends. `tenant_key` opens an encrypted sqlite tenant, whose copy stays encrypted
+pub async fn backup_tenant(
+    database_url: &str,
+    store: &dyn UserTenantStore,
+    user_id: &UserId,
+    tenant_key: Option<&DatabaseKey>,
+    archive_key: Option<&DatabaseKey>,
+    directory: &Path,
+) -> Result<PathBuf, anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url)?;
+    std::fs::create_dir_all(directory).with_context(|| {
+        format!(
+            "while creating the backup directory {}",
+            directory.display()
+        )
+    })?;
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ");
+    let sealed = if archive_key.is_some() { ".sealed" } else { "" };
+    match url {
+        #[cfg(feature = "sqlite")]
+        DatabaseUrl::SqliteFile { .. } => {
+            let destination = directory.join(format!("{}-{stamp}.db{sealed}", user_id.value));
+            let partial = destination.with_extension("partial");
+            let _ = std::fs::remove_file(&partial);
+            crate::drivers::sqlite::backup_url(url.as_str(), tenant_key, &partial).await?;
+            let finished = std::fs::read(&partial)
+                .map_err(anyhow::Error::from)
+                .and_then(|copy| write_archive(ArchiveKind::SqliteFile, &copy, archive_key))
+                .and_then(|archive| Ok(std::fs::write(&destination, archive)?))
+                .and_then(|_| Ok(std::fs::remove_file(&partial)?));
+            if finished.is_err() {
+                let _ = std::fs::remove_file(&partial);
+            }
+            finished
+                .with_context(|| format!("while writing the backup {}", destination.display()))?;
+            Ok(destination)
+        }
+        _ => {
+            let destination = directory.join(format!("{}-{stamp}.json{sealed}", user_id.value));
+            let bundle = export_tenant(store, user_id).await?;
+            std::fs::write(&destination, bundle_to_bytes(&bundle, archive_key)?)
+                .with_context(|| format!("while writing the backup {}", destination.display()))?;
+            Ok(destination)
+        }
+    }
+}
+
+/// what restore_sqlite_file found in the backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoredFile {
+    pub schema_version: i64,
+}
+
+/// replaces a sqlite tenant file with the payload of an opened file backup, once the backup has been checked for damage,
+/// for a schema newer than this build, and that `user_id` is the user in it. close every store on the tenant first.
+/// an older schema is brought up to date the next time the tenant is opened
+#[cfg(feature = "sqlite")]
+pub async fn restore_sqlite_file(
+    plain: &[u8],
+    database_url: &str,
+    user_id: &UserId,
+    tenant_key: Option<&DatabaseKey>,
+) -> Result<RestoredFile, anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url)?;
+    let DatabaseUrl::SqliteFile { path, .. } = &url else {
+        anyhow::bail!("a file backup can only be restored onto a sqlite file tenant");
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let restoring = path.with_extension("restoring");
+    std::fs::write(&restoring, plain)?;
+    let checked = crate::drivers::sqlite::check_backup_url(
+        format!("sqlite://{}", restoring.display()),
+        tenant_key,
+    )
+    .await
+    .and_then(|(version, users)| {
+        let latest = crate::drivers::migrations::runner::latest(
+            crate::drivers::migrations::user_multitenant::MIGRATIONS,
+        );
+        if version > latest {
+            anyhow::bail!(
+                "the backup is at schema version {version}, newer than this build ({latest})"
+            );
+        }
+        match users.as_slice() {
+            [owner] if owner == user_id => Ok(version),
+            [] => anyhow::bail!("the backup has no user in it, so it can not be matched to user {user_id}"),
+            [owner] => anyhow::bail!("the backup belongs to user {owner}"),
+            _ => anyhow::bail!("the backup holds {} users, a tenant holds one", users.len()),
+        }
+    });
+    let schema_version = match checked {
+        Ok(version) => version,
+        Err(e) => {
+            let _ = std::fs::remove_file(&restoring);
+            return Err(e);
+        }
+    };
+    for suffix in ["-wal", "-shm"] {
+        let mut side = path.clone().into_os_string();
+        side.push(suffix);
+        let _ = std::fs::remove_file(side);
+    }
+    std::fs::rename(&restoring, path)
+        .with_context(|| format!("while moving the restored database into {}", path.display()))?;
+    Ok(RestoredFile { schema_version })
+}
+
@@ -1,101 +1,212 @@
-use std::{marker::PhantomData, ops::Deref};
+use std::marker::PhantomData;
 
-use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
 pub mod models;
 
 
-// where T: ParialEq
 /// the primary ID type for the entire library.
-#[derive(FromRow, PartialEq, Eq, Hash, Debug,Clone)]
-#[sqlx(transparent)]
-pub struct Id {
+/// T is the entity the id belongs to, so an Id<Account> can not be passed where an Id<Entry> is expected
+pub struct Id<T> {
     pub value: uuid::Uuid,
-    // #[sqlx(skip)]
-    // _marker: std::marker::PhantomData<T>,
+    // fn() -> T keeps Id Send, Sync and covariant whatever T is
+    _marker: PhantomData<fn() -> T>,
 }
-impl Id {
+impl<T> Id<T> {
     pub fn new_v7() -> Self {
+        Self::from_uuid(uuid::Uuid::now_v7())
+    }
+    pub fn from_uuid(value: Uuid) -> Self {
         Self {
-            value: uuid::Uuid::now_v7(),
-            // _marker: PhantomData,
+            value,
+            _marker: PhantomData,
+        }
+    }
+    /// when the id was generated, from the timestamp in a UUIDv7. None for other versions
+    pub fn created_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let (secs, nanos) = self.value.get_timestamp()?.to_unix();
+        chrono::DateTime::from_timestamp(secs as i64, nanos)
+    }
+}
+
+/// why bytes or text could not be read as an id
+#[derive(Debug)]
+pub enum IdError {
+    /// neither 16 bytes nor 36 characters
+    Length(usize),
+    Text(uuid::Error),
+}
+impl std::fmt::Display for IdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length(n) => write!(f, "expected a 16 byte or 36 character id, found {n} bytes"),
+            Self::Text(e) => write!(f, "invalid id: {e}"),
+        }
+    }
+}
+impl std::error::Error for IdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Length(_) => None,
+            Self::Text(e) => Some(e),
+        }
+    }
+}
+
+// derive would require T to implement these too
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        Self::from_uuid(self.value)
+    }
+}
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl<T> Eq for Id<T> {}
+impl<T> std::hash::Hash for Id<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+impl<T> std::fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Id").field(&self.value).finish()
+    }
+}
+/// the hyphenated form, ex: 0190a6f4-2b1c-7d3e-8f00-123456789abc
+impl<T> std::fmt::Display for Id<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.value.hyphenated(), f)
+    }
+}
+impl<T> std::str::FromStr for Id<T> {
+    type Err = IdError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self::from_uuid).map_err(IdError::Text)
+    }
+}
+impl<T> TryFrom<&str> for Id<T> {
+    type Error = IdError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+/// the 16 byte binary form, or the 36 character text form stored as bytes
+impl<T> TryFrom<&[u8]> for Id<T> {
+    type Error = IdError;
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if let Ok(bytes) = <[u8; 16]>::try_from(value) {
+            return Ok(Self::from_uuid(Uuid::from_bytes(bytes)));
+        }
+        if value.len() != 36 {
+            return Err(IdError::Length(value.len()));
         }
+        Uuid::try_parse_ascii(value)
+            .map(Self::from_uuid)
+            .map_err(IdError::Text)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<T> serde::Serialize for Id<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[cfg(feature = "deserialize")]
+impl<'de, T> serde::Deserialize<'de> for Id<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
 
 // enables us to use the id type in multiple databases
 
-impl<DB: sqlx::Database> sqlx::Type<DB> for Id
+impl<T, DB: sqlx::Database> sqlx::Type<DB> for Id<T>
 where
     uuid::Uuid: sqlx::Type<DB>,
+    Vec<u8>: sqlx::Type<DB>,
+    String: sqlx::Type<DB>,
 {
     fn type_info() -> <DB as sqlx::Database>::TypeInfo {
         <uuid::Uuid as sqlx::Type<DB>>::type_info()
     }
+    // ids may be stored as a native uuid, 16 bytes or hyphenated text
+    fn compatible(ty: &<DB as sqlx::Database>::TypeInfo) -> bool {
+        <uuid::Uuid as sqlx::Type<DB>>::compatible(ty)
+            || <Vec<u8> as sqlx::Type<DB>>::compatible(ty)
+            || <String as sqlx::Type<DB>>::compatible(ty)
+    }
 }
 
-impl<'q, DB: sqlx::Database> sqlx::Encode<'q, DB> for Id
+impl<'q, T, DB: sqlx::Database> sqlx::Encode<'q, DB> for Id<T>
 where
     uuid::Uuid: sqlx::Encode<'q, DB>,
 {
     fn encode_by_ref(
         &self,
-        buf: &mut <DB as sqlx::database::HasArguments<'q>>::ArgumentBuffer,
-    ) -> sqlx::encode::IsNull {
+        buf: &mut <DB as sqlx::Database>::ArgumentBuffer<'q>,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
         <uuid::Uuid as sqlx::Encode<'q, DB>>::encode(self.value, buf)
     }
 }
-impl<'r, DB: sqlx::Database> sqlx::Decode<'r, DB> for Id
+impl<'r, T, DB: sqlx::Database> sqlx::Decode<'r, DB> for Id<T>
 where
-    uuid::Uuid: sqlx::Decode<'r, DB>,
+    uuid::Uuid: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+    &'r [u8]: sqlx::Decode<'r, DB> + sqlx::Type<DB>,
+    String: sqlx::Type<DB>,
 {
     fn decode(
-        value: <DB as sqlx::database::HasValueRef<'r>>::ValueRef,
+        value: <DB as sqlx::Database>::ValueRef<'r>,
     ) -> Result<Self, sqlx::error::BoxDynError> {
-        let uuid = <uuid::Uuid as sqlx::Decode<'r, DB>>::decode(value)?;
-        Ok(Self {
-            value: uuid,
-            // _marker: PhantomData,
-        })
+        use sqlx::ValueRef;
+        let ty = value.type_info();
+        // a native uuid column (postgres), anything else is read as 16 bytes or 36 characters
+        let native = <uuid::Uuid as sqlx::Type<DB>>::compatible(&ty)
+            && !<&[u8] as sqlx::Type<DB>>::compatible(&ty)
+            && !<String as sqlx::Type<DB>>::compatible(&ty);
+        if native {
+            let uuid = <uuid::Uuid as sqlx::Decode<'r, DB>>::decode(value)?;
+            return Ok(Self::from_uuid(uuid));
+        }
+        let bytes = <&'r [u8] as sqlx::Decode<'r, DB>>::decode(value)?;
+        Ok(Self::try_from(bytes)?)
     }
 }
 
-impl std::ops::Deref for Id {
+impl<T> std::ops::Deref for Id<T> {
     type Target = Uuid;
     fn deref(&self) -> &Self::Target {
         &self.value
     }
 }
 
-impl std::convert::From<Vec<u8>> for Id {
-    fn from(value: Vec<u8>) -> Self {
-        let value = value.as_array().unwrap();
-        Self {
-            value: uuid::Uuid::from_bytes(*value),
-        }
+impl<T> TryFrom<Vec<u8>> for Id<T> {
+    type Error = IdError;
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_slice())
     }
 }
 
-impl Default for Id {
+impl<T> Default for Id<T> {
     fn default() -> Self {
-        Self {
-            value: uuid::Uuid::now_v7(),
-            
-        }
+        Self::new_v7()
     }
 }
 
 
 
 pub trait GetId {
-    fn id(&self) -> &Id
+    fn id(&self) -> &Id<Self>
     where
         Self: Sized;
 }
 
 pub trait Entity: GetId {
-    fn primary_key(&self) -> &Id
+    fn primary_key(&self) -> &Id<Self>
     where
         Self: Sized,
     {
@@ -113,15 +224,91 @@ pub trait Table {
     const TABLE_NAME: &'static str;
 }
 
+/// derives Table, GetId and crud queries, see sql_proc_macro::Entity
+pub use sql_proc_macro::Entity;
+
+/// what the queries generated by #[derive(Entity)] need to know about a backend
+pub trait EntityBackend: sqlx::Database {
+    /// $1, $2 .. instead of ?
+    const NUMBERED_PLACEHOLDERS: bool;
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+#[cfg(feature = "sqlite")]
+impl EntityBackend for sqlx::Sqlite {
+    const NUMBERED_PLACEHOLDERS: bool = false;
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+#[cfg(feature = "postgresql")]
+impl EntityBackend for sqlx::Postgres {
+    const NUMBERED_PLACEHOLDERS: bool = true;
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+#[cfg(feature = "mysql")]
+impl EntityBackend for sqlx::MySql {
+    const NUMBERED_PLACEHOLDERS: bool = false;
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
 
 impl<T> Entity for T
 where
     T: GetId,
 {
-    fn primary_key(&self) -> &Id
+    fn primary_key(&self) -> &Id<Self>
     where
         Self: Sized,
     {
         self.id()
     }
 }
+
+#[test]
+fn id_round_trips_through_text() {
+    let id = models::AccountId::new_v7();
+    let text = id.to_string();
+    assert_eq!(text.len(), 36);
+    assert_eq!(text.parse::<models::AccountId>().unwrap(), id);
+    assert!("not an id".parse::<models::AccountId>().is_err());
+}
+
+#[test]
+fn id_from_bytes_and_text() {
+    let id = models::AccountId::new_v7();
+    assert_eq!(models::AccountId::try_from(&id.as_bytes()[..]).unwrap(), id);
+    assert_eq!(models::AccountId::try_from(id.to_string().as_bytes()).unwrap(), id);
+    assert_eq!(models::AccountId::try_from(id.to_string().as_str()).unwrap(), id);
+    assert!(matches!(models::AccountId::try_from(&[0u8; 15][..]), Err(IdError::Length(15))));
+    assert!(matches!(models::AccountId::try_from(&[b'x'; 36][..]), Err(IdError::Text(_))));
+
+    let created = id.created_at().expect("v7 ids carry a timestamp");
+    assert!((chrono::Utc::now() - created).num_seconds().abs() < 5);
+    assert!(models::AccountId::from_uuid(Uuid::nil()).created_at().is_none());
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn id_decodes_from_blob_or_text() -> Result<(), anyhow::Error> {
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await?;
+    let id = models::AccountId::new_v7();
+    let blob: models::AccountId = sqlx::query_scalar("SELECT ?")
+        .bind(id.as_bytes().to_vec())
+        .fetch_one(&pool)
+        .await?;
+    let text: models::AccountId = sqlx::query_scalar("SELECT ?")
+        .bind(id.to_string())
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!((blob, text), (id.clone(), id));
+    let short = sqlx::query_scalar::<_, models::AccountId>("SELECT x'0102'")
+        .fetch_one(&pool)
+        .await;
+    assert!(short.is_err());
+    Ok(())
+}
@@ -1,35 +1,101 @@
-#[derive(Debug,Clone,PartialEq,Eq,Hash)]
-pub struct PasskeyId(crate::core::Id);
-
-
-impl std::ops::Deref for PasskeyId {
-    type Target = crate::core::Id;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
+pub type PasskeyId = crate::core::Id<Passkey>;
 
 
 #[derive(Debug,Clone,PartialEq,Eq)]
 #[cfg_attr(feature = "serialize",derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize",derive(serde::Deserialize))]
-
 pub struct Passkey {
     pub id: PasskeyId,
     pub credential_id: Vec<u8>,
     pub public_key: Vec<u8>,
-    pub sign_count: i32
+    /// the authenticator's signature counter, a u32 in webauthn
+    pub sign_count: u32,
+    /// a name the user picked, ex: "work laptop"
+    pub nickname: Option<String>,
+    /// unix milliseconds
+    pub last_used_at: Option<i64>
+}
+
+/// the outcome of presenting a passkey's signature counter at login
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+#[must_use]
+pub enum PasskeyUse {
+    /// the counter moved forward, or the authenticator does not keep one. stored and last_used_at updated
+    Accepted,
+    /// no passkey with this credential id
+    Unknown,
+    Revoked,
+    /// the counter did not move forward, the credential may have been cloned. nothing was updated
+    CounterRegressed { stored: u32, presented: u32 }
 }
 
 
 
 // the external user structure
-#[derive(Debug,Clone,PartialEq,Eq,Hash)]
-pub struct UserId(crate::core::Id);
+pub type UserId = crate::core::Id<crate::user::User>;
 
-impl std::ops::Deref for UserId {
-    type Target = crate::core::Id;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+
+pub type AccountId = crate::core::Id<Account>;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serialize",derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize",derive(serde::Deserialize))]
+pub enum AccountKind {
+    Asset,
+    Liability,
+    Equity,
+    Income,
+    Expense
+}
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asset => "asset",
+            Self::Liability => "liability",
+            Self::Equity => "equity",
+            Self::Income => "income",
+            Self::Expense => "expense",
+        }
+    }
+}
+impl std::str::FromStr for AccountKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asset" => Ok(Self::Asset),
+            "liability" => Ok(Self::Liability),
+            "equity" => Ok(Self::Equity),
+            "income" => Ok(Self::Income),
+            "expense" => Ok(Self::Expense),
+            other => Err(anyhow::Error::msg(format!("unknown account kind '{other}'")))
+        }
     }
 }
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serialize",derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize",derive(serde::Deserialize))]
+pub struct Account {
+    pub id: AccountId,
+    /// accounts form a tree, ex: Expenses -> Home -> Repairs
+    pub parent_id: Option<AccountId>,
+    pub name: String,
+    pub kind: AccountKind
+}
+
+
+pub type EntryId = crate::core::Id<Entry>;
+
+/// a single ledger line against one account
+#[derive(Debug,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serialize",derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize",derive(serde::Deserialize))]
+pub struct Entry {
+    pub id: EntryId,
+    pub account_id: AccountId,
+    pub posted_on: chrono::NaiveDate,
+    /// amount in cents. negative values are withdrawals
+    pub amount: i64,
+    pub payee: Option<String>,
+    pub memo: Option<String>
+}
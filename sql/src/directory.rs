@@ -0,0 +1,140 @@
+// the tenant directory in the primary database.
+// maps a user to where their tenant database lives, so the server can go from a
+// logged in user to a store without a hard coded url
+
+use std::sync::Arc;
+
+use crate::core::models::UserId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum TenantStatus {
+    Active,
+    /// kept, but not opened until it is made active again
+    Suspended,
+    /// the database was moved to the archive directory, url points at the archived copy
+    Archived,
+    /// the database was destroyed. the row stays as a record that the tenant existed
+    Deprovisioned,
+}
+
+impl TenantStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Suspended => "suspended",
+            Self::Archived => "archived",
+            Self::Deprovisioned => "deprovisioned",
+        }
+    }
+}
+
+impl std::str::FromStr for TenantStatus {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "suspended" => Ok(Self::Suspended),
+            "archived" => Ok(Self::Archived),
+            "deprovisioned" => Ok(Self::Deprovisioned),
+            other => Err(anyhow::Error::msg(format!("unknown tenant status {other}"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantRecord {
+    pub user_id: UserId,
+    /// sqlite, postgres or mysql
+    pub backend: String,
+    /// connection url, or the archived file for archived tenants
+    pub url: String,
+    /// the newest tenant migration applied
+    pub schema_version: i64,
+    /// unix milliseconds
+    pub created_at: i64,
+    /// unix milliseconds, updated when the store is opened rather than on every request
+    pub last_access: i64,
+    pub status: TenantStatus,
+}
+
+impl TenantRecord {
+    /// a new active tenant at `url`, created now
+    pub fn new(user_id: UserId, url: impl Into<String>, schema_version: i64) -> Self {
+        let url = url.into();
+        let now = chrono::Utc::now().timestamp_millis();
+        Self {
+            user_id,
+            backend: backend_name(&url).to_string(),
+            url,
+            schema_version,
+            created_at: now,
+            last_access: now,
+            status: TenantStatus::Active,
+        }
+    }
+}
+
+/// the backend name stored in the directory for a url
+pub fn backend_name(url: &str) -> &'static str {
+    crate::drivers::Backend::from_url(url).map_or("unknown", |b| b.as_str())
+}
+
+#[async_trait::async_trait]
+/// the tenant directory, implemented for the primary database pools in crate::drivers
+pub trait TenantDirectory: Send + Sync {
+    async fn register(&self, record: &TenantRecord) -> Result<(), anyhow::Error>;
+    async fn lookup(&self, user_id: &UserId) -> Result<Option<TenantRecord>, anyhow::Error>;
+    /// every tenant, oldest first
+    async fn list(&self) -> Result<Vec<TenantRecord>, anyhow::Error>;
+    /// returns false if the tenant is not registered
+    async fn update(&self, record: &TenantRecord) -> Result<bool, anyhow::Error>;
+    /// sets last_access to now. returns false if the tenant is not registered
+    async fn touch(&self, user_id: &UserId) -> Result<bool, anyhow::Error>;
+}
+
+/// connects to the primary database and, if asked, brings its schema up to date
+pub async fn connect_directory_any_url<S: AsRef<str>>(
+    database_url: S,
+    migrate: bool,
+) -> Result<Arc<dyn TenantDirectory>, anyhow::Error> {
+    use crate::drivers::DatabaseUrl;
+    use crate::user::UserDatabasePool;
+    let (url, settings) = DatabaseUrl::parse(database_url.as_ref())?;
+    // the primary database is created on first use, tenants are provisioned explicitly
+    if let DatabaseUrl::SqliteFile { .. } = url {
+        crate::drivers::provision_tenant_url(url.as_str()).await?;
+    }
+    match UserDatabasePool::connect_with(&url, &settings).await? {
+        #[cfg(feature = "sqlite")]
+        UserDatabasePool::Sqlite(p) => {
+            use sqlx::Acquire;
+            if migrate {
+                let mut c = p.acquire().await?;
+                let mut tx = c.begin().await?;
+                sql_migrations::bring_up::<barrel::backend::Sqlite, _>(&mut tx).await?;
+                tx.commit().await?;
+            }
+            Ok(Arc::new(p) as Arc<dyn TenantDirectory>)
+        }
+        #[cfg(feature = "postgresql")]
+        UserDatabasePool::Postgres(p) => {
+            if migrate {
+                let mut tx = p.begin().await?;
+                sql_migrations::bring_up::<barrel::backend::Pg, _>(&mut tx).await?;
+                tx.commit().await?;
+            }
+            Ok(Arc::new(p) as Arc<dyn TenantDirectory>)
+        }
+        #[cfg(feature = "mysql")]
+        UserDatabasePool::MySql(p) => {
+            if migrate {
+                let mut tx = p.begin().await?;
+                sql_migrations::bring_up::<barrel::backend::MySql, _>(&mut tx).await?;
+                tx.commit().await?;
+            }
+            Ok(Arc::new(p) as Arc<dyn TenantDirectory>)
+        }
+    }
+}
@@ -1,6 +1,566 @@
 use std::sync::Arc;
 
-pub mod migrations;
+use crate::audit::seal::PendingSeal;
+use crate::audit::{AuditContext, AuditSeal, ChangeRecord};
+use crate::core::models::{Account, AccountId, Entry, EntryId, Passkey, PasskeyId, PasskeyUse, UserId};
+use crate::user::User;
+
+/// implements TenantOperations for $ty using the sql in the $queries module of a backend.
+/// $exec turns $s (self) into an executor, so the same bodies work for pools and
+/// for open transactions. every write runs in the transaction $begin opens on what $hold
+/// holds, its own on a pool and a savepoint in an open transaction, and appends to the
+/// audit log there as the $context it unwraps to before it commits. the records are MACed
+/// under the key of $seal, which is told about each one once it is committed
+macro_rules! impl_tenant_operations {
+    ($ty:ty, $queries:ident, |$s:ident| $exec:expr, |$held:ident| $hold:expr => $begin:expr, $context:expr, $seal:expr) => {
+        #[async_trait::async_trait]
+        impl $crate::drivers::TenantOperations for $ty {
+            async fn insert_user(&self, user: &$crate::user::User) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                sqlx::query($queries::INSERT_USER)
+                    .bind($crate::drivers::rows::id_to_bytes(&user.id))
+                    .execute(&mut *write)
+                    .await?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::insert(user, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(())
+            }
+            async fn get_user(
+                &self,
+                id: &$crate::core::models::UserId,
+            ) -> Result<Option<$crate::user::User>, anyhow::Error> {
+                let $s = self;
+                let row: Option<$crate::drivers::rows::IdRow> = sqlx::query_as($queries::SELECT_USER)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional($exec)
+                    .await?;
+                row.map(|r| {
+                    Ok($crate::user::User {
+                        id: $crate::drivers::rows::id_from_bytes(r.id)?,
+                    })
+                })
+                .transpose()
+            }
+            async fn delete_user(&self, id: &$crate::core::models::UserId) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let r = sqlx::query($queries::DELETE_USER)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .execute(&mut *write)
+                    .await?;
+                if r.rows_affected() == 0 {
+                    return Ok(false);
+                }
+                let before = $crate::user::User { id: id.clone() };
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::delete(&before, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+            async fn schema_version(&self) -> Result<i64, anyhow::Error> {
+                let $s = self;
+                let version: Option<i64> = sqlx::query_scalar($crate::drivers::SELECT_SCHEMA_VERSION)
+                    .fetch_one($exec)
+                    .await?;
+                Ok(version.unwrap_or(0))
+            }
+
+            async fn save_passkey(
+                &self,
+                user_id: &$crate::core::models::UserId,
+                passkey: &$crate::core::models::Passkey,
+            ) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                sqlx::query($queries::INSERT_PASSKEY)
+                    .bind($crate::drivers::rows::id_to_bytes(&passkey.id))
+                    .bind($crate::drivers::rows::id_to_bytes(user_id))
+                    .bind(passkey.credential_id.clone())
+                    .bind(passkey.public_key.clone())
+                    .bind(i64::from(passkey.sign_count))
+                    .bind(passkey.nickname.clone())
+                    .execute(&mut *write)
+                    .await?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::insert(passkey, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(())
+            }
+            async fn get_passkeys(
+                &self,
+                user_id: &$crate::core::models::UserId,
+            ) -> Result<Vec<$crate::core::models::Passkey>, anyhow::Error> {
+                let $s = self;
+                let rows: Vec<$crate::drivers::rows::PasskeyRow> = sqlx::query_as($queries::SELECT_PASSKEYS_FOR_USER)
+                    .bind($crate::drivers::rows::id_to_bytes(user_id))
+                    .fetch_all($exec)
+                    .await?;
+                rows.into_iter().map(TryInto::try_into).collect()
+            }
+            async fn find_passkey(
+                &self,
+                credential_id: &[u8],
+            ) -> Result<Option<$crate::core::models::Passkey>, anyhow::Error> {
+                let $s = self;
+                let row: Option<$crate::drivers::rows::PasskeyRow> = sqlx::query_as($queries::SELECT_PASSKEY_BY_CREDENTIAL)
+                    .bind(credential_id.to_vec())
+                    .fetch_optional($exec)
+                    .await?;
+                row.map(TryInto::try_into).transpose()
+            }
+            async fn use_passkey(
+                &self,
+                credential_id: &[u8],
+                sign_count: u32,
+            ) -> Result<$crate::core::models::PasskeyUse, anyhow::Error> {
+                use $crate::core::models::PasskeyUse;
+                let $s = self;
+                let presented = i64::from(sign_count);
+                // checked and written in one statement so two logins racing with the same counter can not both pass
+                let r = sqlx::query($queries::USE_PASSKEY)
+                    .bind(presented)
+                    .bind(chrono::Utc::now().timestamp_millis())
+                    .bind(credential_id.to_vec())
+                    .bind(presented)
+                    .bind(presented)
+                    .execute($exec)
+                    .await?;
+                if r.rows_affected() > 0 {
+                    return Ok(PasskeyUse::Accepted);
+                }
+                let state: Option<$crate::drivers::rows::PasskeyStateRow> = sqlx::query_as($queries::SELECT_PASSKEY_STATE)
+                    .bind(credential_id.to_vec())
+                    .fetch_optional($exec)
+                    .await?;
+                Ok(match state {
+                    None => PasskeyUse::Unknown,
+                    Some(state) if state.revoked_at.is_some() => PasskeyUse::Revoked,
+                    Some(state) => PasskeyUse::CounterRegressed {
+                        stored: state.sign_count.try_into()?,
+                        presented: sign_count,
+                    },
+                })
+            }
+            async fn rename_passkey(
+                &self,
+                id: &$crate::core::models::PasskeyId,
+                nickname: Option<&str>,
+            ) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::PasskeyRow> = sqlx::query_as($queries::SELECT_PASSKEY)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                let Some(before) = before else {
+                    return Ok(false);
+                };
+                let before: $crate::core::models::Passkey = before.try_into()?;
+                sqlx::query($queries::RENAME_PASSKEY)
+                    .bind(nickname.map(str::to_string))
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .execute(&mut *write)
+                    .await?;
+                let after = $crate::core::models::Passkey {
+                    nickname: nickname.map(str::to_string),
+                    ..before.clone()
+                };
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::update(&before, &after, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+            async fn revoke_passkey(&self, id: &$crate::core::models::PasskeyId) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::PasskeyRow> = sqlx::query_as($queries::SELECT_PASSKEY)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                let r = sqlx::query($queries::REVOKE_PASSKEY)
+                    .bind(chrono::Utc::now().timestamp_millis())
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .execute(&mut *write)
+                    .await?;
+                let (Some(before), true) = (before, r.rows_affected() > 0) else {
+                    return Ok(false);
+                };
+                // the row stays for its counter, but to everything that reads passkeys it is gone
+                let before: $crate::core::models::Passkey = before.try_into()?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::delete(&before, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+
+            async fn insert_account(&self, account: &$crate::core::models::Account) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                sqlx::query($queries::INSERT_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(&account.id))
+                    .bind(account.parent_id.as_ref().map(|p| $crate::drivers::rows::id_to_bytes(p)))
+                    .bind(account.name.clone())
+                    .bind(account.kind.as_str())
+                    .execute(&mut *write)
+                    .await?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::insert(account, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(())
+            }
+            async fn get_account(
+                &self,
+                id: &$crate::core::models::AccountId,
+            ) -> Result<Option<$crate::core::models::Account>, anyhow::Error> {
+                let $s = self;
+                let row: Option<$crate::drivers::rows::AccountRow> = sqlx::query_as($queries::SELECT_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional($exec)
+                    .await?;
+                row.map(TryInto::try_into).transpose()
+            }
+            async fn list_accounts(&self) -> Result<Vec<$crate::core::models::Account>, anyhow::Error> {
+                let $s = self;
+                let rows: Vec<$crate::drivers::rows::AccountRow> = sqlx::query_as($queries::SELECT_ACCOUNTS)
+                    .fetch_all($exec)
+                    .await?;
+                rows.into_iter().map(TryInto::try_into).collect()
+            }
+            async fn update_account(&self, account: &$crate::core::models::Account) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::AccountRow> = sqlx::query_as($queries::SELECT_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(&account.id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                let r = sqlx::query($queries::UPDATE_ACCOUNT)
+                    .bind(account.parent_id.as_ref().map(|p| $crate::drivers::rows::id_to_bytes(p)))
+                    .bind(account.name.clone())
+                    .bind(account.kind.as_str())
+                    .bind($crate::drivers::rows::id_to_bytes(&account.id))
+                    .execute(&mut *write)
+                    .await?;
+                let (Some(before), true) = (before, r.rows_affected() > 0) else {
+                    return Ok(false);
+                };
+                let before: $crate::core::models::Account = before.try_into()?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::update(&before, account, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+            async fn delete_account(&self, id: &$crate::core::models::AccountId) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::AccountRow> = sqlx::query_as($queries::SELECT_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                // sub-accounts and entries would be left pointing at nothing, the foreign keys refuse it
+                let sub_account: Option<Vec<u8>> = sqlx::query_scalar($queries::SELECT_SUB_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                if sub_account.is_some() {
+                    return Ok(false);
+                }
+                let r = sqlx::query($queries::DELETE_ACCOUNT_WITHOUT_ENTRIES)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .execute(&mut *write)
+                    .await?;
+                let (Some(before), true) = (before, r.rows_affected() > 0) else {
+                    return Ok(false);
+                };
+                let before: $crate::core::models::Account = before.try_into()?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::delete(&before, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+
+            async fn insert_entry(&self, entry: &$crate::core::models::Entry) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                sqlx::query($queries::INSERT_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(&entry.id))
+                    .bind($crate::drivers::rows::id_to_bytes(&entry.account_id))
+                    .bind(entry.posted_on.to_string())
+                    .bind(entry.amount)
+                    .bind(entry.payee.clone())
+                    .bind(entry.memo.clone())
+                    .execute(&mut *write)
+                    .await?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::insert(entry, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(())
+            }
+            async fn get_entry(
+                &self,
+                id: &$crate::core::models::EntryId,
+            ) -> Result<Option<$crate::core::models::Entry>, anyhow::Error> {
+                let $s = self;
+                let row: Option<$crate::drivers::rows::EntryRow> = sqlx::query_as($queries::SELECT_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional($exec)
+                    .await?;
+                row.map(TryInto::try_into).transpose()
+            }
+            async fn list_entries(
+                &self,
+                account_id: &$crate::core::models::AccountId,
+            ) -> Result<Vec<$crate::core::models::Entry>, anyhow::Error> {
+                let $s = self;
+                let rows: Vec<$crate::drivers::rows::EntryRow> = sqlx::query_as($queries::SELECT_ENTRIES_FOR_ACCOUNT)
+                    .bind($crate::drivers::rows::id_to_bytes(account_id))
+                    .fetch_all($exec)
+                    .await?;
+                rows.into_iter().map(TryInto::try_into).collect()
+            }
+            async fn update_entry(&self, entry: &$crate::core::models::Entry) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::EntryRow> = sqlx::query_as($queries::SELECT_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(&entry.id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                let r = sqlx::query($queries::UPDATE_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(&entry.account_id))
+                    .bind(entry.posted_on.to_string())
+                    .bind(entry.amount)
+                    .bind(entry.payee.clone())
+                    .bind(entry.memo.clone())
+                    .bind($crate::drivers::rows::id_to_bytes(&entry.id))
+                    .execute(&mut *write)
+                    .await?;
+                let (Some(before), true) = (before, r.rows_affected() > 0) else {
+                    return Ok(false);
+                };
+                let before: $crate::core::models::Entry = before.try_into()?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::update(&before, entry, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+            async fn delete_entry(&self, id: &$crate::core::models::EntryId) -> Result<bool, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                let before: Option<$crate::drivers::rows::EntryRow> = sqlx::query_as($queries::SELECT_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .fetch_optional(&mut *write)
+                    .await?;
+                let r = sqlx::query($queries::DELETE_ENTRY)
+                    .bind($crate::drivers::rows::id_to_bytes(id))
+                    .execute(&mut *write)
+                    .await?;
+                let (Some(before), true) = (before, r.rows_affected() > 0) else {
+                    return Ok(false);
+                };
+                let before: $crate::core::models::Entry = before.try_into()?;
+                let appended = match $context {
+                    Some(context) => Some($crate::audit::append(&mut write, $seal.key(), $crate::audit::NewChange::delete(&before, context)).await?),
+                    None => None,
+                };
+                write.commit().await?;
+                if let Some(record) = appended {
+                    $seal.committed(&record)?;
+                }
+                Ok(true)
+            }
+
+            async fn history(
+                &self,
+                entity_type: &str,
+                entity_id: &str,
+            ) -> Result<Vec<$crate::audit::ChangeRecord>, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut read = $begin.await?;
+                $crate::audit::history_of(&mut read, entity_type, entity_id).await
+            }
+            async fn request_changes(&self, request_id: &str) -> Result<Vec<$crate::audit::ChangeRecord>, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut read = $begin.await?;
+                $crate::audit::changes_for_request(&mut read, request_id).await
+            }
+            async fn audit_record(&self, seq: i64) -> Result<Option<$crate::audit::ChangeRecord>, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut read = $begin.await?;
+                $crate::audit::record_at(&mut read, seq).await
+            }
+            async fn audit_log(&self) -> Result<Vec<$crate::audit::ChangeRecord>, anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut read = $begin.await?;
+                $crate::audit::read_log(&mut read).await
+            }
+            async fn restore_audit_log(&self, records: &[$crate::audit::ChangeRecord]) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut write = $begin.await?;
+                $crate::audit::restore_log(&mut write, $seal.key(), records).await?;
+                write.commit().await?;
+                $seal.restored(records.last())?;
+                Ok(())
+            }
+            async fn verify_audit_log(&self) -> Result<(), anyhow::Error> {
+                let $s = self;
+                #[allow(unused_mut)]
+                let mut $held = $hold;
+                let mut read = $begin.await?;
+                $crate::audit::verify(&mut read, $seal.key(), $seal.read_head()?.as_ref()).await
+            }
+        }
+    };
+}
+
+/// implements TenantDirectory for a primary database pool using the sql in the $queries module of a backend
+macro_rules! impl_tenant_directory {
+    ($ty:ty, $queries:ident) => {
+        #[async_trait::async_trait]
+        impl $crate::directory::TenantDirectory for $ty {
+            async fn register(&self, record: &$crate::directory::TenantRecord) -> Result<(), anyhow::Error> {
+                sqlx::query($queries::INSERT_TENANT)
+                    .bind($crate::drivers::rows::id_to_bytes(&record.user_id))
+                    .bind(record.backend.clone())
+                    .bind(record.url.clone())
+                    .bind(record.schema_version)
+                    .bind(record.created_at)
+                    .bind(record.last_access)
+                    .bind(record.status.as_str())
+                    .execute(self)
+                    .await?;
+                Ok(())
+            }
+            async fn lookup(
+                &self,
+                user_id: &$crate::core::models::UserId,
+            ) -> Result<Option<$crate::directory::TenantRecord>, anyhow::Error> {
+                let row: Option<$crate::drivers::rows::TenantRow> = sqlx::query_as($queries::SELECT_TENANT)
+                    .bind($crate::drivers::rows::id_to_bytes(user_id))
+                    .fetch_optional(self)
+                    .await?;
+                row.map(TryInto::try_into).transpose()
+            }
+            async fn list(&self) -> Result<Vec<$crate::directory::TenantRecord>, anyhow::Error> {
+                let rows: Vec<$crate::drivers::rows::TenantRow> = sqlx::query_as($queries::SELECT_TENANTS)
+                    .fetch_all(self)
+                    .await?;
+                rows.into_iter().map(TryInto::try_into).collect()
+            }
+            async fn update(&self, record: &$crate::directory::TenantRecord) -> Result<bool, anyhow::Error> {
+                let r = sqlx::query($queries::UPDATE_TENANT)
+                    .bind(record.backend.clone())
+                    .bind(record.url.clone())
+                    .bind(record.schema_version)
+                    .bind(record.last_access)
+                    .bind(record.status.as_str())
+                    .bind($crate::drivers::rows::id_to_bytes(&record.user_id))
+                    .execute(self)
+                    .await?;
+                Ok(r.rows_affected() > 0)
+            }
+            async fn touch(&self, user_id: &$crate::core::models::UserId) -> Result<bool, anyhow::Error> {
+                let r = sqlx::query($queries::TOUCH_TENANT)
+                    .bind(chrono::Utc::now().timestamp_millis())
+                    .bind($crate::drivers::rows::id_to_bytes(user_id))
+                    .execute(self)
+                    .await?;
+                Ok(r.rows_affected() > 0)
+            }
+        }
+    };
+}
+
+// the tenant schema lives in the sql_migrations crate, shared with its binary
+pub use sql_migrations as migrations;
+// the same on every backend, schema_migrations is written by the migration runner
+pub(crate) const SELECT_SCHEMA_VERSION: &str = "SELECT MAX(version) FROM schema_migrations";
+pub(crate) mod rows;
+mod url;
+pub use url::{Backend, DatabaseUrl, PoolSettings, sibling_url};
 #[cfg(feature = "mysql")]
 pub(super) mod mysql;
 #[cfg(feature = "postgresql")]
@@ -35,136 +595,333 @@ pub(super) mod sqlite;
     otherwise the compiler will complain that your trait object cannot be sent accross threads safely
 */
 
+#[async_trait::async_trait]
+/// the operations available on a tenant database, both directly on the store
+/// and inside a transaction from UserTenantStore::begin. every write to users, passkeys,
+/// accounts and entries appends to the audit log in the same transaction, except use_passkey,
+/// which only moves the signature counter, and writes inside UserTenantStore::begin_restore
+pub trait TenantOperations: Send + Sync {
+    async fn insert_user(&self, user: &User) -> Result<(), anyhow::Error>;
+    async fn get_user(&self, id: &UserId) -> Result<Option<User>, anyhow::Error>;
+    /// returns false if the user did not exist
+    async fn delete_user(&self, id: &UserId) -> Result<bool, anyhow::Error>;
+    /// the newest tenant migration applied to this database, 0 if none
+    async fn schema_version(&self) -> Result<i64, anyhow::Error>;
+
+    /// fails if the credential is already registered
+    async fn save_passkey(&self, user_id: &UserId, passkey: &Passkey) -> Result<(), anyhow::Error>;
+    /// every passkey the user has not revoked
+    async fn get_passkeys(&self, user_id: &UserId) -> Result<Vec<Passkey>, anyhow::Error>;
+    /// the passkey registered for a webauthn credential id, unless it was revoked
+    async fn find_passkey(&self, credential_id: &[u8]) -> Result<Option<Passkey>, anyhow::Error>;
+    /// records a login with the signature counter from the authenticator. the login must be
+    /// refused unless this returns PasskeyUse::Accepted
+    async fn use_passkey(&self, credential_id: &[u8], sign_count: u32) -> Result<PasskeyUse, anyhow::Error>;
+    /// returns false if the passkey did not exist
+    async fn rename_passkey(&self, id: &PasskeyId, nickname: Option<&str>) -> Result<bool, anyhow::Error>;
+    /// the passkey stops working but its row is kept. returns false if it did not exist or was already revoked
+    async fn revoke_passkey(&self, id: &PasskeyId) -> Result<bool, anyhow::Error>;
+
+    async fn insert_account(&self, account: &Account) -> Result<(), anyhow::Error>;
+    async fn get_account(&self, id: &AccountId) -> Result<Option<Account>, anyhow::Error>;
+    /// every account, ordered by name
+    async fn list_accounts(&self) -> Result<Vec<Account>, anyhow::Error>;
+    /// returns false if the account did not exist
+    async fn update_account(&self, account: &Account) -> Result<bool, anyhow::Error>;
+    /// returns false if the account did not exist, still has sub-accounts or has entries posted to it
+    async fn delete_account(&self, id: &AccountId) -> Result<bool, anyhow::Error>;
+
+    async fn insert_entry(&self, entry: &Entry) -> Result<(), anyhow::Error>;
+    async fn get_entry(&self, id: &EntryId) -> Result<Option<Entry>, anyhow::Error>;
+    /// entries for one account, oldest first
+    async fn list_entries(&self, account_id: &AccountId) -> Result<Vec<Entry>, anyhow::Error>;
+    /// returns false if the entry did not exist
+    async fn update_entry(&self, entry: &Entry) -> Result<bool, anyhow::Error>;
+    /// returns false if the entry did not exist
+    async fn delete_entry(&self, id: &EntryId) -> Result<bool, anyhow::Error>;
+
+    /// every change recorded for one entity, oldest first
+    async fn history(&self, entity_type: &str, entity_id: &str) -> Result<Vec<ChangeRecord>, anyhow::Error>;
+    /// every change recorded under one request id, oldest first
+    async fn request_changes(&self, request_id: &str) -> Result<Vec<ChangeRecord>, anyhow::Error>;
+    /// the audit log record at seq, none if there is no such record
+    async fn audit_record(&self, seq: i64) -> Result<Option<ChangeRecord>, anyhow::Error>;
+    /// the whole audit log, oldest first
+    async fn audit_log(&self) -> Result<Vec<ChangeRecord>, anyhow::Error>;
+    /// copies the log of a backup into an empty audit log, see UserTenantStore::begin_restore
+    async fn restore_audit_log(&self, records: &[ChangeRecord]) -> Result<(), anyhow::Error>;
+    /// fails if a record in the audit log was altered, removed or reordered
+    async fn verify_audit_log(&self) -> Result<(), anyhow::Error>;
+}
+
 #[async_trait::async_trait]
 /// the user implementation in this database crate is
 /// is implemented via multiple databases. all user interactions go through
 /// this trait.
-pub trait UserTenantStore: Send + Sync {
-    // async fn save_passkey(&self){}
+pub trait UserTenantStore: TenantOperations {
+    /// Starts a transaction. everything done through the returned object is applied
+    /// together on commit, or not at all. dropping it without calling commit rolls back
+    async fn begin(&self) -> Result<Box<dyn TenantTransaction>, anyhow::Error> {
+        self.begin_as(AuditContext::system()).await
+    }
+    /// begin, with the changes recorded in the audit log as made by context instead of the system
+    async fn begin_as(&self, context: AuditContext) -> Result<Box<dyn TenantTransaction>, anyhow::Error>;
+    /// begin, with nothing recorded in the audit log. only for restoring a backup, whose own log
+    /// is copied in with restore_audit_log
+    async fn begin_restore(&self) -> Result<Box<dyn TenantTransaction>, anyhow::Error>;
+    /// closes every connection. anything still holding the store gets errors afterwards
+    async fn close(&self);
+}
+
+#[async_trait::async_trait]
+pub trait TenantTransaction: TenantOperations {
+    async fn commit(self: Box<Self>) -> Result<(), anyhow::Error>;
+    async fn rollback(self: Box<Self>) -> Result<(), anyhow::Error>;
+}
+
+/// a tenant database pool and the seal its audit log is written under
+pub struct TenantPool<DB: sqlx::Database> {
+    pool: sqlx::Pool<DB>,
+    seal: AuditSeal,
+}
+
+impl<DB: sqlx::Database> TenantPool<DB> {
+    pub fn new(pool: sqlx::Pool<DB>, seal: AuditSeal) -> Self {
+        Self { pool, seal }
+    }
+    pub fn pool(&self) -> &sqlx::Pool<DB> {
+        &self.pool
+    }
+}
+
+/// an open sqlx transaction behind a lock, since TenantOperations only hands out &self,
+/// who its changes are recorded as, none for a restore, and the seal of the store it came from
+pub struct SqlxTenantTransaction<DB: sqlx::Database>(
+    tokio::sync::Mutex<sqlx::Transaction<'static, DB>>,
+    Option<AuditContext>,
+    PendingSeal,
+);
+
+#[async_trait::async_trait]
+impl<DB> TenantTransaction for SqlxTenantTransaction<DB>
+where
+    DB: sqlx::Database,
+    Self: TenantOperations,
+{
+    async fn commit(self: Box<Self>) -> Result<(), anyhow::Error> {
+        let Self(tx, _, seal) = *self;
+        tx.into_inner().commit().await?;
+        seal.commit()
+    }
+    async fn rollback(self: Box<Self>) -> Result<(), anyhow::Error> {
+        self.0.into_inner().rollback().await?;
+        Ok(())
+    }
 }
 
-/// Aquires a connection. Mysql, and postgres are single connections with this method, sqlite connections are POOLED, as SqliteConnection is not Send + Sync
+#[async_trait::async_trait]
+impl<DB> UserTenantStore for TenantPool<DB>
+where
+    DB: sqlx::Database,
+    Self: TenantOperations,
+    SqlxTenantTransaction<DB>: TenantTransaction,
+{
+    async fn begin_as(&self, context: AuditContext) -> Result<Box<dyn TenantTransaction>, anyhow::Error> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(SqlxTenantTransaction(tokio::sync::Mutex::new(tx), Some(context), PendingSeal::new(self.seal.clone()))))
+    }
+    async fn begin_restore(&self) -> Result<Box<dyn TenantTransaction>, anyhow::Error> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(SqlxTenantTransaction(tokio::sync::Mutex::new(tx), None, PendingSeal::new(self.seal.clone()))))
+    }
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
+
+/// Aquires a connection. Mysql, and postgres are single connection pools with this method, sqlite connections are POOLED, as SqliteConnection is not Send + Sync.
+/// the store methods take &self, so even a single connection has to live in a pool to be shared
 pub async fn connect_tenant_any_url<S: AsRef<str>>(
     database_url: S,
     migrate: bool,
 ) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
-    sqlx::any::install_default_drivers();
+    let (url, mut settings) = DatabaseUrl::parse(database_url.as_ref())?;
+    // NOTE: Sqlite connections are NOT Send + Sync, so POOLING IS REQUIRED
+    if url.backend() != Backend::Sqlite {
+        settings.max_connections.get_or_insert(1);
+    }
+    connect_tenant_with(&url, &settings, migrate).await
+}
 
-    let url = database_url.as_ref();
-    #[cfg(feature = "sqlite")]
-    {
-        // NOTE: Sqlite connections are NOT Send + Sync, so POOLING IS REQUIRED
-        if url.starts_with("sqlite:///") {
-            let c = sqlite::connect_pool_url(url).await?;
-            if migrate {
-                // use sqlx::{Connection};
-                use sqlx::Acquire;
-
-                let mut conn = c.acquire().await?;
-                let mut tx = conn.begin().await?;
-                self::migrations::user_multitenant::bring_up::<
-                    barrel::backend::Sqlite,
-                    sqlx::Sqlite,
-                >(&mut tx)
-                .await?;
-                tx.commit().await?;
-                // conn.close().await.ok();
-            }
-            let c: Arc<sqlx::Pool<sqlx::Sqlite>> = Arc::new(c);
-            return Ok(c as Arc<dyn UserTenantStore>);
-        }
+/// Aquires a Pool with default options. Mysql, and postgres are POOLED connections with this method, sqlite connections are POOLED, as SqliteConnection is not Send + Sync
+pub async fn connect_tenant_any_pool_url<S: AsRef<str>>(
+    database_url: S,
+    migrate: bool,
+) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+    let (url, settings) = DatabaseUrl::parse(database_url.as_ref())?;
+    connect_tenant_with(&url, &settings, migrate).await
+}
+
+/// a pooled tenant store for an already parsed url. settings given in the url should be merged in by the caller,
+/// see PoolSettings::or
+#[allow(unreachable_code)] // the error is only reached for a backend that was compiled out
+pub async fn connect_tenant_with(
+    url: &DatabaseUrl,
+    settings: &PoolSettings,
+    migrate: bool,
+) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+    if settings.key.is_some() && url.backend() != Backend::Sqlite {
+        anyhow::bail!("only sqlite tenants can be encrypted, not {}", url.backend().as_str());
     }
-    #[cfg(feature = "postgresql")]
-    {
-        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
-            let mut c = postgres::connect_url(url).await?;
-            if migrate {
-                use sqlx::Connection;
-                let mut tx = c.begin().await?;
-                self::migrations::user_multitenant::bring_up::<barrel::backend::Pg, _>(&mut tx)
-                    .await?;
-                tx.commit().await?;
+    let seal = settings.audit.clone().unwrap_or_else(AuditSeal::unsealed);
+    match url {
+        DatabaseUrl::SqliteFile { .. } | DatabaseUrl::SqliteMemory => {
+            #[cfg(feature = "sqlite")]
+            {
+                let p = sqlite::connect_pool_with(url, settings).await?;
+                if migrate {
+                    run_user_multitenent_migrations_sqlite_pooled(&p).await?;
+                }
+                return Ok(Arc::new(TenantPool::new(p, seal)) as Arc<dyn UserTenantStore>);
+            }
+        }
+        DatabaseUrl::Postgres(_) => {
+            #[cfg(feature = "postgresql")]
+            {
+                let p = postgres::connect_pool_with(url, settings).await?;
+                if migrate {
+                    run_user_multitenent_migrations_postgres_pooled(&p).await?;
+                }
+                return Ok(Arc::new(TenantPool::new(p, seal)) as Arc<dyn UserTenantStore>);
+            }
+        }
+        DatabaseUrl::MySql(_) => {
+            #[cfg(feature = "mysql")]
+            {
+                let p = mysql::connect_pool_with(url, settings).await?;
+                if migrate {
+                    run_user_multitenant_migrations_mysql_pooled(&p).await?;
+                }
+                return Ok(Arc::new(TenantPool::new(p, seal)) as Arc<dyn UserTenantStore>);
             }
-            let c = Arc::new(c);
-            return Ok(c as Arc<dyn UserTenantStore>);
         }
     }
-    #[cfg(feature = "mysql")]
-    {
-        if url.starts_with("mysql://") {
-            let mut c = mysql::connect_url(url).await?;
-            if migrate {
-                use sqlx::Acquire;
-                let mut tx = c.begin().await?;
-                let _ = self::migrations::user_multitenant::bring_up::<barrel::backend::MySql, _>(
-                    &mut tx,
-                )
-                .await?;
-                tx.commit().await?;
-            }
-            return mysql::connect_url(url)
-                .await
-                .map(|c| Arc::new(c) as Arc<dyn UserTenantStore>);
+    Err(url.backend().not_compiled())
+}
+
+/// creates the tenant database the url points at, a sqlite file or a postgres/mysql database.
+/// does nothing if it already exists
+#[allow(unreachable_code)] // the error is only reached for a backend that was compiled out
+pub async fn provision_tenant_url<S: AsRef<str>>(database_url: S) -> Result<(), anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url.as_ref())?;
+    match &url {
+        DatabaseUrl::SqliteMemory => return Ok(()),
+        DatabaseUrl::SqliteFile { .. } => {
+            #[cfg(feature = "sqlite")]
+            return sqlite::provision_url(url.as_str()).await;
+        }
+        DatabaseUrl::Postgres(_) => {
+            #[cfg(feature = "postgresql")]
+            return postgres::provision_url(url.as_str()).await;
+        }
+        DatabaseUrl::MySql(_) => {
+            #[cfg(feature = "mysql")]
+            return mysql::provision_url(url.as_str()).await;
         }
     }
+    Err(url.backend().not_compiled())
+}
 
-    return Err(anyhow::Error::msg(
-        "Database crate compiled without any drivers available or invalid connection url. unable to make a connection",
-    ));
+/// destroys the tenant database the url points at. close every store on it first
+#[allow(unreachable_code)] // the error is only reached for a backend that was compiled out
+pub async fn drop_tenant_url<S: AsRef<str>>(database_url: S) -> Result<(), anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url.as_ref())?;
+    match &url {
+        DatabaseUrl::SqliteMemory => return Ok(()),
+        DatabaseUrl::SqliteFile { .. } => {
+            #[cfg(feature = "sqlite")]
+            return sqlite::drop_url(url.as_str()).await;
+        }
+        DatabaseUrl::Postgres(_) => {
+            #[cfg(feature = "postgresql")]
+            return postgres::drop_url(url.as_str()).await;
+        }
+        DatabaseUrl::MySql(_) => {
+            #[cfg(feature = "mysql")]
+            return mysql::drop_url(url.as_str()).await;
+        }
+    }
+    Err(url.backend().not_compiled())
 }
 
-/// Aquires a Pool with default options. Mysql, and postgres are POOLED connections with this method, sqlite connections are POOLED, as SqliteConnection is not Send + Sync
-pub async fn connect_tenant_any_pool_url<S: AsRef<str>>(
+/// moves a sqlite tenant into `directory` and returns where it ended up. close every store on it first
+pub async fn archive_tenant_url<S: AsRef<str>>(
     database_url: S,
-    migrate: bool,
-) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
-    sqlx::any::install_default_drivers();
-    use sqlx::Acquire;
-
-    let url = database_url.as_ref();
+    directory: &std::path::Path,
+) -> Result<std::path::PathBuf, anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url.as_ref())?;
     #[cfg(feature = "sqlite")]
     {
-        if url.starts_with("sqlite:///") {
-            let p = sqlite::connect_pool_url(url).await?;
-            if migrate {
-                let mut c = p.acquire().await?;
-                let mut tx = c.begin().await?;
-                self::migrations::user_multitenant::bring_up::<barrel::backend::Sqlite, _>(&mut tx)
-                    .await?;
-            }
-            return Ok(Arc::new(p) as Arc<dyn UserTenantStore>);
+        if let DatabaseUrl::SqliteFile { .. } = url {
+            return sqlite::archive_url(url.as_str(), directory).await;
         }
     }
-    #[cfg(feature = "postgresql")]
+    let _ = directory;
+    Err(anyhow::Error::msg(format!(
+        "archiving is only supported for sqlite file tenants, not {}",
+        url.backend().as_str()
+    )))
+}
+
+/// re-encrypts a sqlite tenant from `key` to `new_key`. close every store on it first
+pub async fn rekey_tenant_url<S: AsRef<str>>(
+    database_url: S,
+    key: &crate::encryption::DatabaseKey,
+    new_key: &crate::encryption::DatabaseKey,
+) -> Result<(), anyhow::Error> {
+    let (url, _) = DatabaseUrl::parse(database_url.as_ref())?;
+    #[cfg(feature = "sqlite")]
     {
-        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
-            let p = postgres::connect_pool_url(url).await?;
-            let mut c = p.acquire().await?;
-            let mut tx = c.begin().await?;
-            self::migrations::user_multitenant::bring_up::<barrel::backend::Sqlite, _>(&mut tx)
-                .await?;
-            return Ok(Arc::new(p) as Arc<dyn UserTenantStore>);
+        if let DatabaseUrl::SqliteFile { .. } = url {
+            return sqlite::rekey_url(url.as_str(), key, new_key).await;
         }
     }
-    #[cfg(feature = "mysql")]
+    let _ = (key, new_key);
+    Err(anyhow::Error::msg(format!(
+        "only sqlite file tenants can be encrypted, not {}",
+        url.backend().as_str()
+    )))
+}
+
+/// whether `key` opens the sqlite tenant, false for every other backend
+pub async fn tenant_key_opens_url<S: AsRef<str>>(
+    database_url: S,
+    key: &crate::encryption::DatabaseKey,
+) -> bool {
+    #[cfg(feature = "sqlite")]
     {
-        if url.starts_with("mysql://") {
-            // use sqlx::postgres::any::AnyConnectionBackend;
-
-            let p = mysql::connect_pool_url(url).await?;
-            let mut c = p.acquire().await?;
-            let mut tx = c.begin().await?;
-            self::migrations::user_multitenant::bring_up::<barrel::backend::MySql, _>(&mut tx)
-                .await?;
-            return Ok(Arc::new(p) as Arc<dyn UserTenantStore>);
+        if let Ok((url @ DatabaseUrl::SqliteFile { .. }, _)) = DatabaseUrl::parse(database_url.as_ref()) {
+            return sqlite::key_opens_url(url.as_str(), key).await;
         }
     }
+    let _ = (database_url, key);
+    false
+}
 
-    return Err(anyhow::Error::msg(
-        "Database crate compiled without any drivers available or invalid connection url. unable to make a connection",
-    ));
+/// copies a tenant into another database, creating it first, and returns what was copied
+/// for each table. see migrations::copy for how the copy is checked
+pub async fn copy_tenant_url(
+    source: &DatabaseUrl,
+    target: &DatabaseUrl,
+) -> Result<Vec<migrations::copy::TableCopy>, anyhow::Error> {
+    if matches!(source, DatabaseUrl::SqliteMemory) || matches!(target, DatabaseUrl::SqliteMemory) {
+        anyhow::bail!("an in memory database can not be copied, it lives in one pool");
+    }
+    provision_tenant_url(target.as_str()).await?;
+    migrations::copy::copy_tenant_url(source.as_str(), target.as_str()).await
 }
 
+#[cfg(feature = "sqlite")]
 pub async fn run_user_multitenent_migrations_sqlite_pooled(
     p: &sqlx::Pool<sqlx::Sqlite>,
 ) -> Result<(), anyhow::Error> {
@@ -173,9 +930,11 @@ pub async fn run_user_multitenent_migrations_sqlite_pooled(
     let mut c = p.acquire().await?;
     let mut tx = c.begin().await?;
     self::migrations::user_multitenant::bring_up::<barrel::backend::Sqlite, _>(&mut tx).await?;
-    return Ok(());
+    tx.commit().await?;
+    Ok(())
 }
 
+#[cfg(feature = "postgresql")]
 pub async fn run_user_multitenent_migrations_postgres_pooled(
     p: &sqlx::Pool<sqlx::Postgres>,
 ) -> Result<(), anyhow::Error> {
@@ -183,10 +942,12 @@ pub async fn run_user_multitenent_migrations_postgres_pooled(
     // let p = sqlite::connect_pool_url(url).await?;
     let mut c = p.acquire().await?;
     let mut tx = c.begin().await?;
-    self::migrations::user_multitenant::bring_up::<barrel::backend::MySql, _>(&mut tx).await?;
-    return Ok(());
+    self::migrations::user_multitenant::bring_up::<barrel::backend::Pg, _>(&mut tx).await?;
+    tx.commit().await?;
+    Ok(())
 }
 
+#[cfg(feature = "mysql")]
 pub async fn run_user_multitenant_migrations_mysql_pooled(
     p: &sqlx::Pool<sqlx::MySql>,
 ) -> Result<(), anyhow::Error> {
@@ -195,5 +956,6 @@ pub async fn run_user_multitenant_migrations_mysql_pooled(
     let mut c = p.acquire().await?;
     let mut tx = c.begin().await?;
     self::migrations::user_multitenant::bring_up::<barrel::backend::MySql, _>(&mut tx).await?;
-    return Ok(());
+    tx.commit().await?;
+    Ok(())
 }
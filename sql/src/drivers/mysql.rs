@@ -3,21 +3,73 @@ use std::str::FromStr;
 use anyhow::Context;
 use sqlx::ConnectOptions;
 
-pub async fn connect_url<S: AsRef<str>>(database_url:S) -> Result<sqlx::mysql::MySqlConnection,anyhow::Error> {
-    let o = sqlx::mysql::MySqlConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing sqlite connection options from database url")?;
-    let c = o.connect().await.context("While attempting to connect to a sqlite_database via database_url")?;
-    Ok(c)
-}
-pub async fn connect_pool_url<S: AsRef<str>>(database_url:S) -> Result<sqlx::mysql::MySqlPool,anyhow::Error> {
-    let o = sqlx::mysql::MySqlConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing sqlite connection options from database url")?;
-    let p = sqlx::mysql::MySqlPoolOptions::default().connect_with(o).await.with_context(|| "while attempting a pooled connection to a sqlite database via a database url")?;
+/// a pool with the settings applied, see super::DatabaseUrl
+pub async fn connect_pool_with(url: &super::DatabaseUrl, settings: &super::PoolSettings) -> Result<sqlx::mysql::MySqlPool,anyhow::Error> {
+    let o = sqlx::mysql::MySqlConnectOptions::from_str(url.as_str()).with_context(|| "while parsing mysql connection options from database url")?;
+    let mut p = sqlx::mysql::MySqlPoolOptions::default();
+    if let Some(max) = settings.max_connections {
+        p = p.max_connections(max);
+    }
+    let p = p.connect_with(o).await.with_context(|| "while attempting a pooled connection to a mysql database via a database url")?;
     Ok(p)
 }
 
-impl super::UserTenantStore for sqlx::mysql::MySqlConnection {
-    
+/// creates the database named in the url if it does not exist
+pub async fn provision_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    let o = sqlx::mysql::MySqlConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing mysql connection options from database url")?;
+    let name = o.get_database().ok_or_else(|| anyhow::Error::msg("the mysql tenant url has to name a database"))?.to_string();
+    let mut c = o.database("information_schema").connect().await.context("while connecting to the mysql server")?;
+    sqlx::query(&format!("CREATE DATABASE IF NOT EXISTS {}",quote_identifier(&name))).execute(&mut c).await?;
+    Ok(())
+}
+/// drops the database named in the url
+pub async fn drop_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    let o = sqlx::mysql::MySqlConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing mysql connection options from database url")?;
+    let name = o.get_database().ok_or_else(|| anyhow::Error::msg("the mysql tenant url has to name a database"))?.to_string();
+    let mut c = o.database("information_schema").connect().await.context("while connecting to the mysql server")?;
+    sqlx::query(&format!("DROP DATABASE IF EXISTS {}",quote_identifier(&name))).execute(&mut c).await?;
+    Ok(())
+}
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`",name.replace('`',"``"))
+}
+
+// sql for TenantOperations, see impl_tenant_operations in the parent module
+pub(crate) mod queries {
+    pub const INSERT_USER: &str = "INSERT INTO users (id) VALUES (?)";
+    pub const SELECT_USER: &str = "SELECT id FROM users WHERE id = ?";
+    pub const DELETE_USER: &str = "DELETE FROM users WHERE id = ?";
+
+    pub const INSERT_PASSKEY: &str = "INSERT INTO passkeys (id, user_id, credential_id, public_key, sign_count, nickname) VALUES (?, ?, ?, ?, ?, ?)";
+    pub const SELECT_PASSKEYS_FOR_USER: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE user_id = ? AND revoked_at IS NULL ORDER BY id";
+    pub const SELECT_PASSKEY: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE id = ?";
+    pub const SELECT_PASSKEY_BY_CREDENTIAL: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE credential_id = ? AND revoked_at IS NULL";
+    pub const SELECT_PASSKEY_STATE: &str = "SELECT sign_count, revoked_at FROM passkeys WHERE credential_id = ?";
+    // a counter of 0 on both sides means the authenticator does not count
+    pub const USE_PASSKEY: &str = "UPDATE passkeys SET sign_count = ?, last_used_at = ? WHERE credential_id = ? AND revoked_at IS NULL AND (sign_count < ? OR (sign_count = 0 AND ? = 0))";
+    pub const RENAME_PASSKEY: &str = "UPDATE passkeys SET nickname = ? WHERE id = ?";
+    pub const REVOKE_PASSKEY: &str = "UPDATE passkeys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL";
+
+    pub const INSERT_ACCOUNT: &str = "INSERT INTO accounts (id, parent_id, name, kind) VALUES (?, ?, ?, ?)";
+    pub const SELECT_ACCOUNT: &str = "SELECT id, parent_id, name, kind FROM accounts WHERE id = ?";
+    pub const SELECT_ACCOUNTS: &str = "SELECT id, parent_id, name, kind FROM accounts ORDER BY name";
+    pub const UPDATE_ACCOUNT: &str = "UPDATE accounts SET parent_id = ?, name = ?, kind = ? WHERE id = ?";
+    pub const SELECT_SUB_ACCOUNT: &str = "SELECT id FROM accounts WHERE parent_id = ? LIMIT 1";
+    pub const DELETE_ACCOUNT_WITHOUT_ENTRIES: &str = "DELETE FROM accounts WHERE id = ? AND NOT EXISTS (SELECT 1 FROM entries WHERE account_id = ?)";
+
+    pub const INSERT_ENTRY: &str = "INSERT INTO entries (id, account_id, posted_on, amount, payee, memo) VALUES (?, ?, ?, ?, ?, ?)";
+    pub const SELECT_ENTRY: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE id = ?";
+    pub const SELECT_ENTRIES_FOR_ACCOUNT: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE account_id = ? ORDER BY posted_on, id";
+    pub const UPDATE_ENTRY: &str = "UPDATE entries SET account_id = ?, posted_on = ?, amount = ?, payee = ?, memo = ? WHERE id = ?";
+    pub const DELETE_ENTRY: &str = "DELETE FROM entries WHERE id = ?";
+
+    pub const INSERT_TENANT: &str = "INSERT INTO tenant_directory (user_id, backend, url, schema_version, created_at, last_access, status) VALUES (?, ?, ?, ?, ?, ?, ?)";
+    pub const SELECT_TENANT: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory WHERE user_id = ?";
+    pub const SELECT_TENANTS: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory ORDER BY created_at";
+    pub const UPDATE_TENANT: &str = "UPDATE tenant_directory SET backend = ?, url = ?, schema_version = ?, last_access = ?, status = ? WHERE user_id = ?";
+    pub const TOUCH_TENANT: &str = "UPDATE tenant_directory SET last_access = ? WHERE user_id = ?";
 }
 
-impl super::UserTenantStore for sqlx::mysql::MySqlPool {
-    
-}
\ No newline at end of file
+impl_tenant_operations!(super::TenantPool<sqlx::MySql>, queries, |pool| &pool.pool, |held| &pool.pool => sqlx::Acquire::begin(held), Some(&crate::audit::AuditContext::system()), pool.seal);
+impl_tenant_operations!(super::SqlxTenantTransaction<sqlx::MySql>, queries, |tx| &mut **tx.0.lock().await, |guard| tx.0.lock().await => sqlx::Acquire::begin(&mut *guard), tx.1.as_ref(), tx.2);
+impl_tenant_directory!(sqlx::mysql::MySqlPool, queries);
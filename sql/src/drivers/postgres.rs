@@ -3,21 +3,77 @@ use std::str::FromStr;
 use anyhow::Context;
 use sqlx::ConnectOptions;
 
-pub async fn connect_url<S: AsRef<str>>(database_url:S) -> Result<sqlx::postgres::PgConnection,anyhow::Error> {
-    let o = sqlx::postgres::PgConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing sqlite connection options from database url")?;
-    let c = o.connect().await.context("While attempting to connect to a sqlite_database via database_url")?;
-    Ok(c)
-}
-pub async fn connect_pool_url<S: AsRef<str>>(database_url:S) -> Result<sqlx::postgres::PgPool,anyhow::Error> {
-    let o = sqlx::postgres::PgConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing sqlite connection options from database url")?;
-    let p = sqlx::postgres::PgPoolOptions::default().connect_with(o).await.with_context(|| "while attempting a pooled connection to a sqlite database via a database url")?;
+/// a pool with the settings applied, see super::DatabaseUrl
+pub async fn connect_pool_with(url: &super::DatabaseUrl, settings: &super::PoolSettings) -> Result<sqlx::postgres::PgPool,anyhow::Error> {
+    let o = sqlx::postgres::PgConnectOptions::from_str(url.as_str()).with_context(|| "while parsing postgres connection options from database url")?;
+    let mut p = sqlx::postgres::PgPoolOptions::default();
+    if let Some(max) = settings.max_connections {
+        p = p.max_connections(max);
+    }
+    let p = p.connect_with(o).await.with_context(|| "while attempting a pooled connection to a postgres database via a database url")?;
     Ok(p)
 }
 
-impl super::UserTenantStore for sqlx::postgres::PgConnection {
-    
+/// creates the database named in the url if it does not exist, through the postgres maintenance database
+pub async fn provision_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    let o = sqlx::postgres::PgConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing postgres connection options from database url")?;
+    let name = o.get_database().ok_or_else(|| anyhow::Error::msg("the postgres tenant url has to name a database"))?.to_string();
+    let mut c = o.database("postgres").connect().await.context("while connecting to the postgres maintenance database")?;
+    let exists: Option<(i32,)> = sqlx::query_as("SELECT 1 FROM pg_database WHERE datname = $1").bind(&name).fetch_optional(&mut c).await?;
+    if exists.is_none() {
+        // CREATE DATABASE can not take a bind parameter
+        sqlx::query(&format!("CREATE DATABASE {}",quote_identifier(&name))).execute(&mut c).await?;
+    }
+    Ok(())
+}
+/// drops the database named in the url. close every pool on it first
+pub async fn drop_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    let o = sqlx::postgres::PgConnectOptions::from_str(database_url.as_ref()).with_context(|| "while parsing postgres connection options from database url")?;
+    let name = o.get_database().ok_or_else(|| anyhow::Error::msg("the postgres tenant url has to name a database"))?.to_string();
+    let mut c = o.database("postgres").connect().await.context("while connecting to the postgres maintenance database")?;
+    sqlx::query(&format!("DROP DATABASE IF EXISTS {}",quote_identifier(&name))).execute(&mut c).await?;
+    Ok(())
+}
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"",name.replace('"',"\"\""))
+}
+
+// sql for TenantOperations, see impl_tenant_operations in the parent module
+pub(crate) mod queries {
+    pub const INSERT_USER: &str = "INSERT INTO users (id) VALUES ($1)";
+    pub const SELECT_USER: &str = "SELECT id FROM users WHERE id = $1";
+    pub const DELETE_USER: &str = "DELETE FROM users WHERE id = $1";
+
+    pub const INSERT_PASSKEY: &str = "INSERT INTO passkeys (id, user_id, credential_id, public_key, sign_count, nickname) VALUES ($1, $2, $3, $4, $5, $6)";
+    pub const SELECT_PASSKEYS_FOR_USER: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE user_id = $1 AND revoked_at IS NULL ORDER BY id";
+    pub const SELECT_PASSKEY: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE id = $1";
+    pub const SELECT_PASSKEY_BY_CREDENTIAL: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE credential_id = $1 AND revoked_at IS NULL";
+    pub const SELECT_PASSKEY_STATE: &str = "SELECT sign_count, revoked_at FROM passkeys WHERE credential_id = $1";
+    // a counter of 0 on both sides means the authenticator does not count
+    pub const USE_PASSKEY: &str = "UPDATE passkeys SET sign_count = $1, last_used_at = $2 WHERE credential_id = $3 AND revoked_at IS NULL AND (sign_count < $4 OR (sign_count = 0 AND $5 = 0))";
+    pub const RENAME_PASSKEY: &str = "UPDATE passkeys SET nickname = $1 WHERE id = $2";
+    pub const REVOKE_PASSKEY: &str = "UPDATE passkeys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL";
+
+    pub const INSERT_ACCOUNT: &str = "INSERT INTO accounts (id, parent_id, name, kind) VALUES ($1, $2, $3, $4)";
+    pub const SELECT_ACCOUNT: &str = "SELECT id, parent_id, name, kind FROM accounts WHERE id = $1";
+    pub const SELECT_ACCOUNTS: &str = "SELECT id, parent_id, name, kind FROM accounts ORDER BY name";
+    pub const UPDATE_ACCOUNT: &str = "UPDATE accounts SET parent_id = $1, name = $2, kind = $3 WHERE id = $4";
+    pub const SELECT_SUB_ACCOUNT: &str = "SELECT id FROM accounts WHERE parent_id = $1 LIMIT 1";
+    pub const DELETE_ACCOUNT_WITHOUT_ENTRIES: &str = "DELETE FROM accounts WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM entries WHERE account_id = $2)";
+
+    pub const INSERT_ENTRY: &str = "INSERT INTO entries (id, account_id, posted_on, amount, payee, memo) VALUES ($1, $2, $3, $4, $5, $6)";
+    pub const SELECT_ENTRY: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE id = $1";
+    pub const SELECT_ENTRIES_FOR_ACCOUNT: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE account_id = $1 ORDER BY posted_on, id";
+    pub const UPDATE_ENTRY: &str = "UPDATE entries SET account_id = $1, posted_on = $2, amount = $3, payee = $4, memo = $5 WHERE id = $6";
+    pub const DELETE_ENTRY: &str = "DELETE FROM entries WHERE id = $1";
+
+    pub const INSERT_TENANT: &str = "INSERT INTO tenant_directory (user_id, backend, url, schema_version, created_at, last_access, status) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+    pub const SELECT_TENANT: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory WHERE user_id = $1";
+    pub const SELECT_TENANTS: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory ORDER BY created_at";
+    pub const UPDATE_TENANT: &str = "UPDATE tenant_directory SET backend = $1, url = $2, schema_version = $3, last_access = $4, status = $5 WHERE user_id = $6";
+    pub const TOUCH_TENANT: &str = "UPDATE tenant_directory SET last_access = $1 WHERE user_id = $2";
 }
 
-impl super::UserTenantStore for sqlx::postgres::PgPool {
-    
-}
\ No newline at end of file
+impl_tenant_operations!(super::TenantPool<sqlx::Postgres>, queries, |pool| &pool.pool, |held| &pool.pool => sqlx::Acquire::begin(held), Some(&crate::audit::AuditContext::system()), pool.seal);
+impl_tenant_operations!(super::SqlxTenantTransaction<sqlx::Postgres>, queries, |tx| &mut **tx.0.lock().await, |guard| tx.0.lock().await => sqlx::Acquire::begin(&mut *guard), tx.1.as_ref(), tx.2);
+impl_tenant_directory!(sqlx::postgres::PgPool, queries);
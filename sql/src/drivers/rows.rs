@@ -0,0 +1,121 @@
+// rows exactly as the tenant tables store them.
+// ids are 16 byte binary and dates are ISO text on every backend, so one set of
+// row types and conversions serves sqlite, postgres and mysql
+
+use crate::core::{
+    Id,
+    models::{Account, Entry, Passkey},
+};
+use crate::directory::TenantRecord;
+
+pub(crate) fn id_to_bytes<T>(id: &Id<T>) -> Vec<u8> {
+    id.as_bytes().to_vec()
+}
+
+pub(crate) fn id_from_bytes<T>(bytes: Vec<u8>) -> Result<Id<T>, anyhow::Error> {
+    Ok(Id::try_from(bytes)?)
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct IdRow {
+    pub id: Vec<u8>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct PasskeyRow {
+    pub id: Vec<u8>,
+    pub credential_id: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub sign_count: i64,
+    pub nickname: Option<String>,
+    pub last_used_at: Option<i64>,
+}
+impl TryFrom<PasskeyRow> for Passkey {
+    type Error = anyhow::Error;
+    fn try_from(r: PasskeyRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: id_from_bytes(r.id)?,
+            credential_id: r.credential_id,
+            public_key: r.public_key,
+            sign_count: r.sign_count.try_into()?,
+            nickname: r.nickname,
+            last_used_at: r.last_used_at,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct PasskeyStateRow {
+    pub sign_count: i64,
+    pub revoked_at: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccountRow {
+    pub id: Vec<u8>,
+    pub parent_id: Option<Vec<u8>>,
+    pub name: String,
+    pub kind: String,
+}
+impl TryFrom<AccountRow> for Account {
+    type Error = anyhow::Error;
+    fn try_from(r: AccountRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: id_from_bytes(r.id)?,
+            parent_id: r
+                .parent_id
+                .map(id_from_bytes)
+                .transpose()?,
+            name: r.name,
+            kind: r.kind.parse()?,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct EntryRow {
+    pub id: Vec<u8>,
+    pub account_id: Vec<u8>,
+    pub posted_on: String,
+    pub amount: i64,
+    pub payee: Option<String>,
+    pub memo: Option<String>,
+}
+impl TryFrom<EntryRow> for Entry {
+    type Error = anyhow::Error;
+    fn try_from(r: EntryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: id_from_bytes(r.id)?,
+            account_id: id_from_bytes(r.account_id)?,
+            posted_on: r.posted_on.parse()?,
+            amount: r.amount,
+            payee: r.payee,
+            memo: r.memo,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct TenantRow {
+    pub user_id: Vec<u8>,
+    pub backend: String,
+    pub url: String,
+    pub schema_version: i64,
+    pub created_at: i64,
+    pub last_access: i64,
+    pub status: String,
+}
+impl TryFrom<TenantRow> for TenantRecord {
+    type Error = anyhow::Error;
+    fn try_from(r: TenantRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: id_from_bytes(r.user_id)?,
+            backend: r.backend,
+            url: r.url,
+            schema_version: r.schema_version,
+            created_at: r.created_at,
+            last_access: r.last_access,
+            status: r.status.parse()?,
+        })
+    }
+}
@@ -2,20 +2,197 @@ use std::str::FromStr;
 
 use anyhow::Context;
 use sqlx::{ConnectOptions, SqliteConnection};
-/// Creates a connection to a sqlite database specified by database_url.
-/// defaults to create_if_missing = true
-pub async fn connect_url<S: AsRef<str>>(database_url:S) -> Result<SqliteConnection,anyhow::Error> {
-    let mut o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_ref()).with_context(||"while parsing sqlite connection options from database url")?;
-    o = o.create_if_missing(true);
-    let c = o.connect().await.with_context(||"While attempting to connect to a sqlite_database via database_url")?;
+/// a pool with the settings applied. an in memory database keeps one connection open
+/// for the life of the pool, it is dropped with its last connection
+pub async fn connect_pool_with(url: &super::DatabaseUrl, settings: &super::PoolSettings) -> Result<sqlx::sqlite::SqlitePool,anyhow::Error> {
+    let mut o = sqlx::sqlite::SqliteConnectOptions::from_str(url.as_str()).with_context(||"while parsing sqlite connection options from database url")?;
+    if let Some(wal) = settings.wal {
+        o = o.journal_mode(if wal { sqlx::sqlite::SqliteJournalMode::Wal } else { sqlx::sqlite::SqliteJournalMode::Delete });
+    }
+    if let Some(timeout) = settings.busy_timeout {
+        o = o.busy_timeout(timeout);
+    }
+    if let Some(foreign_keys) = settings.foreign_keys {
+        o = o.foreign_keys(foreign_keys);
+    }
+    if let Some(key) = &settings.key {
+        o = keyed(o, key);
+    }
+    let mut p = sqlx::sqlite::SqlitePoolOptions::default();
+    if let Some(max) = settings.max_connections {
+        p = p.max_connections(max);
+    }
+    if matches!(url, super::DatabaseUrl::SqliteMemory) {
+        p = p.min_connections(1).idle_timeout(None).max_lifetime(None);
+    }
+    let p = p.connect_with(o).await.with_context(||"while attempting a pooled connection to a sqlite database via a database url")?;
+    if settings.key.is_some() {
+        let checked = match p.acquire().await {
+            Ok(mut c) => check_key(&mut c).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = checked {
+            p.close().await;
+            return Err(e);
+        }
+    }
+    Ok(p)
+}
+
+// sqlx sends PRAGMA key ahead of every other pragma, sqlcipher needs it before the file is read
+fn keyed(o: sqlx::sqlite::SqliteConnectOptions, key: &crate::encryption::DatabaseKey) -> sqlx::sqlite::SqliteConnectOptions {
+    o.pragma("key", key.pragma_value())
+}
+
+/// plain sqlite ignores PRAGMA key and would go on writing plaintext, and sqlcipher only
+/// notices a wrong key once a page is read
+async fn check_key(c: &mut SqliteConnection) -> Result<(),anyhow::Error> {
+    let cipher: Option<String> = sqlx::query_scalar("PRAGMA cipher_version").fetch_optional(&mut *c).await?;
+    if cipher.is_none() {
+        anyhow::bail!("sqlite was built without sqlcipher, enable the sqlcipher feature to open encrypted databases");
+    }
+    sqlx::query("SELECT count(*) FROM sqlite_master").execute(&mut *c).await.with_context(||"the key does not open this database, or it is not encrypted")?;
+    Ok(())
+}
+
+/// re-encrypts the database under `new_key`. close every pool on it first.
+/// sqlcipher can not rekey a plaintext database in place
+pub async fn rekey_url<S: AsRef<str>>(database_url:S, key: &crate::encryption::DatabaseKey, new_key: &crate::encryption::DatabaseKey) -> Result<(),anyhow::Error> {
+    let mut c = connect_keyed(database_url.as_ref(), Some(key)).await?;
+    sqlx::query(&format!("PRAGMA rekey = {}", new_key.pragma_value())).execute(&mut c).await.with_context(||"while rekeying a sqlite database")?;
+    sqlx::Connection::close(c).await?;
+    Ok(())
+}
+
+/// whether `key` opens the database
+pub async fn key_opens_url<S: AsRef<str>>(database_url:S, key: &crate::encryption::DatabaseKey) -> bool {
+    match connect_keyed(database_url.as_ref(), Some(key)).await {
+        Ok(c) => sqlx::Connection::close(c).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// a consistent copy of the database at `destination`, taken with VACUUM INTO while other
+/// connections keep writing. `destination` must not exist yet
+pub async fn backup_url<S: AsRef<str>>(database_url:S, key: Option<&crate::encryption::DatabaseKey>, destination: &std::path::Path) -> Result<(),anyhow::Error> {
+    let mut c = connect_keyed(database_url.as_ref(), key).await?;
+    sqlx::query("VACUUM INTO ?").bind(destination.to_string_lossy().into_owned()).execute(&mut c).await.with_context(|| format!("while backing up a sqlite database to {}",destination.display()))?;
+    sqlx::Connection::close(c).await?;
+    Ok(())
+}
+
+/// checks a database file is intact and returns the newest tenant migration applied to it,
+/// along with the users in it
+pub async fn check_backup_url<S: AsRef<str>>(database_url:S, key: Option<&crate::encryption::DatabaseKey>) -> Result<(i64,Vec<crate::core::models::UserId>),anyhow::Error> {
+    let mut c = connect_keyed(database_url.as_ref(), key).await?;
+    let check: String = sqlx::query_scalar("PRAGMA quick_check").fetch_one(&mut c).await?;
+    if check != "ok" {
+        anyhow::bail!("the backup is damaged: {check}");
+    }
+    let version: Option<i64> = sqlx::query_scalar(super::SELECT_SCHEMA_VERSION).fetch_one(&mut c).await.with_context(||"the backup is not a tenant database")?;
+    let users = sqlx::query_as::<_, super::rows::IdRow>("SELECT id FROM users").fetch_all(&mut c).await?
+        .into_iter()
+        .map(|r| super::rows::id_from_bytes(r.id))
+        .collect::<Result<Vec<_>,_>>()?;
+    sqlx::Connection::close(c).await?;
+    Ok((version.unwrap_or(0), users))
+}
+
+async fn connect_keyed(database_url: &str, key: Option<&crate::encryption::DatabaseKey>) -> Result<SqliteConnection,anyhow::Error> {
+    let o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url).with_context(||"while parsing sqlite connection options from database url")?;
+    let Some(key) = key else {
+        return o.connect().await.with_context(||"While attempting to connect to a sqlite_database via database_url");
+    };
+    let mut c = keyed(o, key).connect().await.with_context(||"while opening an encrypted sqlite database")?;
+    check_key(&mut c).await?;
     Ok(c)
 }
-pub async fn connect_pool_url<S: AsRef<str>>(database_url:S) -> Result<sqlx::sqlite::SqlitePool,anyhow::Error> {
+
+/// creates the database file, and its directory, if they do not exist yet
+pub async fn provision_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    let o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_ref()).with_context(||"while parsing sqlite connection options from database url")?;
+    if let Some(parent) = o.get_filename().parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("while creating the tenant directory {}",parent.display()))?;
+    }
+    let c = o.create_if_missing(true).connect().await.with_context(||"while creating a sqlite database via database_url")?;
+    sqlx::Connection::close(c).await?;
+    Ok(())
+}
+/// removes the database file along with its wal, shm and key version files. close every pool on it first
+pub async fn drop_url<S: AsRef<str>>(database_url:S) -> Result<(),anyhow::Error> {
+    use crate::encryption::{KEY_VERSION_SUFFIX, PENDING_KEY_VERSION_SUFFIX};
     let o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_ref()).with_context(||"while parsing sqlite connection options from database url")?;
-    let p = sqlx::sqlite::SqlitePoolOptions::default().connect_with(o).await.with_context(||"while attempting a pooled connection to a sqlite database via a database url")?;
-    Ok(p)
+    let path = o.get_filename().to_path_buf();
+    for suffix in ["-wal","-shm",KEY_VERSION_SUFFIX,PENDING_KEY_VERSION_SUFFIX] {
+        let mut side = path.clone().into_os_string();
+        side.push(suffix);
+        let _ = std::fs::remove_file(side);
+    }
+    // like DROP DATABASE IF EXISTS on the other backends
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("while removing the tenant database {}",path.display())),
+        _ => Ok(()),
+    }
 }
+/// moves the database file into `directory`, named after the file and the current time.
+/// close every pool on it first so the wal is checkpointed into the file
+pub async fn archive_url<S: AsRef<str>>(database_url:S, directory: &std::path::Path) -> Result<std::path::PathBuf,anyhow::Error> {
+    let o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_ref()).with_context(||"while parsing sqlite connection options from database url")?;
+    let path = o.get_filename().to_path_buf();
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "tenant".to_string());
+    std::fs::create_dir_all(directory).with_context(|| format!("while creating the archive directory {}",directory.display()))?;
+    let target = directory.join(format!("{}-{}.db",stem,chrono::Utc::now().format("%Y%m%dT%H%M%S")));
+    // rename fails across filesystems, fall back to a copy
+    if std::fs::rename(&path,&target).is_err() {
+        std::fs::copy(&path,&target).with_context(|| format!("while archiving {} to {}",path.display(),target.display()))?;
+        std::fs::remove_file(&path)?;
+    }
+    // the key version goes with it, an encrypted archive can not be opened without it
+    let version = crate::encryption::side_file(&path, crate::encryption::KEY_VERSION_SUFFIX);
+    if version.exists() {
+        std::fs::copy(&version, crate::encryption::side_file(&target, crate::encryption::KEY_VERSION_SUFFIX))
+            .with_context(|| format!("while archiving {}", version.display()))?;
+        std::fs::remove_file(&version)?;
+    }
+    Ok(target)
+}
+
+// sql for TenantOperations, see impl_tenant_operations in the parent module
+pub(crate) mod queries {
+    pub const INSERT_USER: &str = "INSERT INTO users (id) VALUES (?)";
+    pub const SELECT_USER: &str = "SELECT id FROM users WHERE id = ?";
+    pub const DELETE_USER: &str = "DELETE FROM users WHERE id = ?";
 
-impl super::UserTenantStore for sqlx::sqlite::SqlitePool {
+    pub const INSERT_PASSKEY: &str = "INSERT INTO passkeys (id, user_id, credential_id, public_key, sign_count, nickname) VALUES (?, ?, ?, ?, ?, ?)";
+    pub const SELECT_PASSKEYS_FOR_USER: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE user_id = ? AND revoked_at IS NULL ORDER BY id";
+    pub const SELECT_PASSKEY: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE id = ?";
+    pub const SELECT_PASSKEY_BY_CREDENTIAL: &str = "SELECT id, credential_id, public_key, sign_count, nickname, last_used_at FROM passkeys WHERE credential_id = ? AND revoked_at IS NULL";
+    pub const SELECT_PASSKEY_STATE: &str = "SELECT sign_count, revoked_at FROM passkeys WHERE credential_id = ?";
+    // a counter of 0 on both sides means the authenticator does not count
+    pub const USE_PASSKEY: &str = "UPDATE passkeys SET sign_count = ?, last_used_at = ? WHERE credential_id = ? AND revoked_at IS NULL AND (sign_count < ? OR (sign_count = 0 AND ? = 0))";
+    pub const RENAME_PASSKEY: &str = "UPDATE passkeys SET nickname = ? WHERE id = ?";
+    pub const REVOKE_PASSKEY: &str = "UPDATE passkeys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL";
 
+    pub const INSERT_ACCOUNT: &str = "INSERT INTO accounts (id, parent_id, name, kind) VALUES (?, ?, ?, ?)";
+    pub const SELECT_ACCOUNT: &str = "SELECT id, parent_id, name, kind FROM accounts WHERE id = ?";
+    pub const SELECT_ACCOUNTS: &str = "SELECT id, parent_id, name, kind FROM accounts ORDER BY name";
+    pub const UPDATE_ACCOUNT: &str = "UPDATE accounts SET parent_id = ?, name = ?, kind = ? WHERE id = ?";
+    pub const SELECT_SUB_ACCOUNT: &str = "SELECT id FROM accounts WHERE parent_id = ? LIMIT 1";
+    pub const DELETE_ACCOUNT_WITHOUT_ENTRIES: &str = "DELETE FROM accounts WHERE id = ? AND NOT EXISTS (SELECT 1 FROM entries WHERE account_id = ?)";
+
+    pub const INSERT_ENTRY: &str = "INSERT INTO entries (id, account_id, posted_on, amount, payee, memo) VALUES (?, ?, ?, ?, ?, ?)";
+    pub const SELECT_ENTRY: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE id = ?";
+    pub const SELECT_ENTRIES_FOR_ACCOUNT: &str = "SELECT id, account_id, posted_on, amount, payee, memo FROM entries WHERE account_id = ? ORDER BY posted_on, id";
+    pub const UPDATE_ENTRY: &str = "UPDATE entries SET account_id = ?, posted_on = ?, amount = ?, payee = ?, memo = ? WHERE id = ?";
+    pub const DELETE_ENTRY: &str = "DELETE FROM entries WHERE id = ?";
+
+    pub const INSERT_TENANT: &str = "INSERT INTO tenant_directory (user_id, backend, url, schema_version, created_at, last_access, status) VALUES (?, ?, ?, ?, ?, ?, ?)";
+    pub const SELECT_TENANT: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory WHERE user_id = ?";
+    pub const SELECT_TENANTS: &str = "SELECT user_id, backend, url, schema_version, created_at, last_access, status FROM tenant_directory ORDER BY created_at";
+    pub const UPDATE_TENANT: &str = "UPDATE tenant_directory SET backend = ?, url = ?, schema_version = ?, last_access = ?, status = ? WHERE user_id = ?";
+    pub const TOUCH_TENANT: &str = "UPDATE tenant_directory SET last_access = ? WHERE user_id = ?";
 }
+
+impl_tenant_operations!(super::TenantPool<sqlx::Sqlite>, queries, |pool| &pool.pool, |held| &pool.pool => sqlx::Acquire::begin(held), Some(&crate::audit::AuditContext::system()), pool.seal);
+impl_tenant_operations!(super::SqlxTenantTransaction<sqlx::Sqlite>, queries, |tx| &mut **tx.0.lock().await, |guard| tx.0.lock().await => sqlx::Acquire::begin(&mut *guard), tx.1.as_ref(), tx.2);
+impl_tenant_directory!(sqlx::sqlite::SqlitePool, queries);
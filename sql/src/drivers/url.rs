@@ -0,0 +1,286 @@
+// the one place a connection url is turned into a backend.
+// our pool parameters are taken out of the query string before sqlx sees the url,
+// sqlx refuses sqlite parameters it does not know
+
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+impl Backend {
+    /// the backend a url is for, from its scheme alone
+    pub fn from_url(url: &str) -> Option<Self> {
+        let scheme = url.split_once(':')?.0;
+        match scheme {
+            "sqlite" => Some(Self::Sqlite),
+            "postgres" | "postgresql" => Some(Self::Postgres),
+            "mysql" => Some(Self::MySql),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sqlite => "sqlite",
+            Self::Postgres => "postgres",
+            Self::MySql => "mysql",
+        }
+    }
+    /// false if the crate was built without the feature for this backend
+    pub fn is_compiled(&self) -> bool {
+        match self {
+            Self::Sqlite => cfg!(feature = "sqlite"),
+            Self::Postgres => cfg!(feature = "postgresql"),
+            Self::MySql => cfg!(feature = "mysql"),
+        }
+    }
+    pub(crate) fn not_compiled(&self) -> anyhow::Error {
+        anyhow::Error::msg(format!(
+            "the database crate was compiled without {} support",
+            self.as_str()
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatabaseUrl {
+    /// sqlite://relative/path, sqlite:///absolute/path or sqlite:path
+    SqliteFile {
+        path: PathBuf,
+        url: String,
+    },
+    /// sqlite::memory:, gone once the pool closes
+    SqliteMemory,
+    Postgres(String),
+    MySql(String),
+}
+
+/// pool and connection settings, from the url or filled in by the caller
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolSettings {
+    /// ?max_connections=
+    pub max_connections: Option<u32>,
+    /// sqlite only, ?wal=true puts the database in WAL journal mode
+    pub wal: Option<bool>,
+    /// sqlite only, ?busy_timeout= in milliseconds. how long to wait on a locked database
+    pub busy_timeout: Option<Duration>,
+    /// sqlite only, ?foreign_keys=. sqlx turns them on unless told otherwise
+    pub foreign_keys: Option<bool>,
+    /// sqlite only, opens the database with sqlcipher. never taken from the url
+    pub key: Option<crate::encryption::DatabaseKey>,
+    /// what the audit log is MACed with and where its head is kept. never taken from the url,
+    /// stores opened without one use AuditSeal::unsealed
+    pub audit: Option<crate::audit::AuditSeal>,
+}
+
+impl PoolSettings {
+    /// the settings from `self`, falling back to `other` where self has none
+    pub fn or(self, other: &PoolSettings) -> Self {
+        Self {
+            max_connections: self.max_connections.or(other.max_connections),
+            wal: self.wal.or(other.wal),
+            busy_timeout: self.busy_timeout.or(other.busy_timeout),
+            foreign_keys: self.foreign_keys.or(other.foreign_keys),
+            key: self.key.or_else(|| other.key.clone()),
+            audit: self.audit.or_else(|| other.audit.clone()),
+        }
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, anyhow::Error> {
+    match value {
+        "true" | "1" | "on" => Ok(true),
+        "false" | "0" | "off" => Ok(false),
+        _ => anyhow::bail!("{key} must be true or false, found {value}"),
+    }
+}
+
+impl DatabaseUrl {
+    /// checks the url, splits off the pool settings and fails if the backend was not compiled in
+    pub fn parse(url: &str) -> Result<(Self, PoolSettings), anyhow::Error> {
+        let backend = Backend::from_url(url).ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "unsupported database url {}, expected sqlite:, postgres:// or mysql://",
+                url.split(':').next().unwrap_or_default()
+            ))
+        })?;
+        if !backend.is_compiled() {
+            return Err(backend.not_compiled());
+        }
+
+        let (base, query) = url.split_once('?').unwrap_or((url, ""));
+        let mut settings = PoolSettings::default();
+        let mut passed_on = Vec::new();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "max_connections" => {
+                    let n: u32 = value.parse().map_err(|_| {
+                        anyhow::anyhow!("max_connections must be a number, found {value}")
+                    })?;
+                    if n == 0 {
+                        anyhow::bail!("max_connections must be at least 1");
+                    }
+                    settings.max_connections = Some(n);
+                }
+                "wal" => settings.wal = Some(parse_bool(key, value)?),
+                "foreign_keys" => settings.foreign_keys = Some(parse_bool(key, value)?),
+                "busy_timeout" => {
+                    let ms: u64 = value.parse().map_err(|_| {
+                        anyhow::anyhow!("busy_timeout must be milliseconds, found {value}")
+                    })?;
+                    settings.busy_timeout = Some(Duration::from_millis(ms));
+                }
+                "key" => anyhow::bail!("database keys can not be given in the url"),
+                _ => passed_on.push(pair),
+            }
+        }
+        let stripped = if passed_on.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base}?{}", passed_on.join("&"))
+        };
+
+        let parsed = match backend {
+            Backend::Sqlite => {
+                let rest = base.trim_start_matches("sqlite:");
+                let path = rest.strip_prefix("//").unwrap_or(rest);
+                if path == ":memory:" {
+                    Self::SqliteMemory
+                } else if path.is_empty() {
+                    anyhow::bail!("the sqlite url {url} does not name a file");
+                } else {
+                    Self::SqliteFile {
+                        path: PathBuf::from(path),
+                        url: stripped,
+                    }
+                }
+            }
+            Backend::Postgres | Backend::MySql => {
+                if settings.wal.is_some()
+                    || settings.busy_timeout.is_some()
+                    || settings.foreign_keys.is_some()
+                {
+                    anyhow::bail!("wal, busy_timeout and foreign_keys only apply to sqlite urls");
+                }
+                if !base.contains("://") {
+                    anyhow::bail!("the {} url {url} is missing a host", backend.as_str());
+                }
+                match backend {
+                    Backend::Postgres => Self::Postgres(stripped),
+                    _ => Self::MySql(stripped),
+                }
+            }
+        };
+        Ok((parsed, settings))
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self {
+            Self::SqliteFile { .. } | Self::SqliteMemory => Backend::Sqlite,
+            Self::Postgres(_) => Backend::Postgres,
+            Self::MySql(_) => Backend::MySql,
+        }
+    }
+
+    /// the url handed to sqlx, without the pool settings
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SqliteFile { url, .. } => url,
+            Self::SqliteMemory => "sqlite::memory:",
+            Self::Postgres(url) | Self::MySql(url) => url,
+        }
+    }
+}
+
+/// the url of a new database next to the one in `url`, named after it with `_<tag><unix millis>`:
+/// another file in the same directory for sqlite, another database on the same server otherwise.
+/// the query string is kept, and a suffix with the same tag from an earlier call is replaced
+pub fn sibling_url(url: &str, tag: &str) -> Result<String, anyhow::Error> {
+    let (parsed, _) = DatabaseUrl::parse(url)?;
+    if parsed == DatabaseUrl::SqliteMemory {
+        anyhow::bail!("an in-memory sqlite database has nothing next to it");
+    }
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, format!("?{query}")),
+        None => (url, String::new()),
+    };
+    let (front, name) = match parsed.backend() {
+        Backend::Sqlite => base.rsplit_once(['/', ':']),
+        _ => base.split_once("://").filter(|(_, rest)| rest.contains('/')).and(base.rsplit_once('/')),
+    }
+    .filter(|(_, name)| !name.is_empty())
+    .ok_or_else(|| anyhow::Error::msg(format!("the url {base} does not name a database")))?;
+    let separator = &base[front.len()..front.len() + 1];
+    let (stem, extension) = match parsed.backend() {
+        Backend::Sqlite => name.rsplit_once('.').map_or((name, String::new()), |(stem, ext)| (stem, format!(".{ext}"))),
+        _ => (name, String::new()),
+    };
+    let stem = match stem.rsplit_once(&format!("_{tag}")) {
+        Some((earlier, stamp)) if !stamp.is_empty() && stamp.bytes().all(|b| b.is_ascii_digit()) => earlier,
+        _ => stem,
+    };
+    let stamp = chrono::Utc::now().timestamp_millis();
+    Ok(format!("{front}{separator}{stem}_{tag}{stamp}{extension}{query}"))
+}
+
+impl std::str::FromStr for DatabaseUrl {
+    type Err = anyhow::Error;
+    /// the url alone, see parse for the pool settings
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).map(|(url, _)| url)
+    }
+}
+
+#[test]
+fn database_urls_parse() {
+    let (url, settings) =
+        DatabaseUrl::parse("sqlite:///data/a.db?mode=rwc&wal=true&busy_timeout=250").unwrap();
+    assert_eq!(
+        url,
+        DatabaseUrl::SqliteFile {
+            path: PathBuf::from("/data/a.db"),
+            url: "sqlite:///data/a.db?mode=rwc".to_string()
+        }
+    );
+    assert_eq!(settings.wal, Some(true));
+    assert_eq!(settings.busy_timeout, Some(Duration::from_millis(250)));
+    assert!(matches!(
+        "sqlite://./a.db".parse::<DatabaseUrl>().unwrap(),
+        DatabaseUrl::SqliteFile { path, .. } if path.as_os_str() == "./a.db"
+    ));
+    assert_eq!(
+        "sqlite::memory:".parse::<DatabaseUrl>().unwrap(),
+        DatabaseUrl::SqliteMemory
+    );
+
+    if cfg!(feature = "postgresql") {
+        let (url, settings) =
+            DatabaseUrl::parse("postgresql://u@host/db?sslmode=disable&max_connections=4").unwrap();
+        assert_eq!(
+            url,
+            DatabaseUrl::Postgres("postgresql://u@host/db?sslmode=disable".to_string())
+        );
+        assert_eq!(settings.max_connections, Some(4));
+        assert!(DatabaseUrl::parse("postgres://host/db?wal=true").is_err());
+    }
+    assert!(DatabaseUrl::parse("sqlite://a.db?max_connections=0").is_err());
+    assert!(DatabaseUrl::parse("sqlite://a.db?foreign_keys=maybe").is_err());
+    assert!(DatabaseUrl::parse("oracle://host/db").is_err());
+    assert!(DatabaseUrl::parse("sqlite://").is_err());
+    assert!(DatabaseUrl::parse("sqlite://a.db?key=00").is_err());
+
+    let sibling = sibling_url("sqlite:///data/a.db?mode=rwc", "restored").unwrap();
+    assert!(sibling.starts_with("sqlite:///data/a_restored") && sibling.ends_with(".db?mode=rwc"));
+    let again = sibling_url(&sibling, "restored").unwrap();
+    assert!(again.starts_with("sqlite:///data/a_restored") && !again.contains("restored_restored"));
+    assert!(sibling_url("sqlite::memory:", "restored").is_err());
+    if cfg!(feature = "postgresql") {
+        let sibling = sibling_url("postgres://u@host/db?sslmode=disable", "restored").unwrap();
+        assert!(sibling.starts_with("postgres://u@host/db_restored") && sibling.ends_with("?sslmode=disable"));
+        assert!(sibling_url("postgres://u@host", "restored").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+// encryption at rest for sqlite tenants. each tenant file is encrypted page by page with sqlcipher,
+// under its own key. the keys are not stored anywhere, they are derived again on every open from
+// the user and the key version, usually with the vault master key, see TenantEncryption::from_master_key.
+// the version is the one thing kept on disk, next to the database, and rekey moves it on
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+
+use crate::core::models::UserId;
+
+/// a 256 bit sqlcipher key. zeroed on drop and never printed
+#[derive(Clone, PartialEq, Eq)]
+pub struct DatabaseKey(zeroize::Zeroizing<[u8; 32]>);
+
+impl DatabaseKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(zeroize::Zeroizing::new(bytes))
+    }
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+    /// the value for PRAGMA key and PRAGMA rekey, a raw key so sqlcipher skips its own key derivation
+    pub(crate) fn pragma_value(&self) -> String {
+        let mut hex = String::with_capacity(64 + 5);
+        hex.push_str("\"x'");
+        for b in self.0.iter() {
+            hex.push_str(&format!("{b:02X}"));
+        }
+        hex.push_str("'\"");
+        hex
+    }
+}
+impl From<[u8; 32]> for DatabaseKey {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+impl From<zeroize::Zeroizing<[u8; 32]>> for DatabaseKey {
+    fn from(bytes: zeroize::Zeroizing<[u8; 32]>) -> Self {
+        Self(bytes)
+    }
+}
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatabaseKey(..)")
+    }
+}
+
+type KeyFn = dyn Fn(&UserId, u32) -> Result<DatabaseKey, anyhow::Error> + Send + Sync;
+
+/// finds the key for a tenant from its user and key version. the same pair has to give the
+/// same key every time, and a new version a new key
+#[derive(Clone)]
+pub struct TenantEncryption(Arc<KeyFn>);
+
+impl TenantEncryption {
+    pub fn new(
+        key_for: impl Fn(&UserId, u32) -> Result<DatabaseKey, anyhow::Error> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(key_for))
+    }
+    /// keys derived from the vault master key, see vault::crypto::derive_tenant_database_key
+    #[cfg(feature = "sqlcipher")]
+    pub fn from_master_key(master_key: zeroize::Zeroizing<[u8; 32]>) -> Self {
+        Self::new(move |id, version| {
+            Ok(vault::crypto::derive_tenant_database_key(&master_key, id.as_bytes(), version)?.into())
+        })
+    }
+    pub fn key_for(&self, id: &UserId, version: u32) -> Result<DatabaseKey, anyhow::Error> {
+        (self.0)(id, version)
+    }
+}
+impl std::fmt::Debug for TenantEncryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TenantEncryption(..)")
+    }
+}
+
+/// the key version of a sqlite tenant, in `<database>-key`. a database without one is at version 0
+pub(crate) const KEY_VERSION_SUFFIX: &str = "-key";
+/// written before a rekey starts and removed once the new version is recorded. while it is there
+/// the database may be under either key
+pub(crate) const PENDING_KEY_VERSION_SUFFIX: &str = "-key-next";
+
+pub(crate) fn side_file(database: &Path, suffix: &str) -> PathBuf {
+    let mut side = database.as_os_str().to_owned();
+    side.push(suffix);
+    PathBuf::from(side)
+}
+
+fn read_version(file: &Path) -> Result<Option<u32>, anyhow::Error> {
+    match std::fs::read_to_string(file) {
+        Ok(s) => Ok(Some(s.trim().parse().with_context(|| format!("{} does not hold a key version", file.display()))?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("while reading {}", file.display())),
+    }
+}
+
+// through a temporary file so a crash leaves the old version or the new one, never half of it
+fn write_version(file: &Path, version: u32) -> Result<(), anyhow::Error> {
+    let partial = side_file(file, ".partial");
+    std::fs::write(&partial, version.to_string()).with_context(|| format!("while writing {}", partial.display()))?;
+    std::fs::rename(&partial, file).with_context(|| format!("while writing {}", file.display()))
+}
+
+/// the key version of the sqlite file the url points at. every other database is at version 0,
+/// only sqlite files are encrypted. fails while a rekey of the file is unfinished
+pub fn key_version_url(database_url: &str) -> Result<u32, anyhow::Error> {
+    let Some(database) = database_file(database_url)? else {
+        return Ok(0)
+    };
+    if read_version(&side_file(&database, PENDING_KEY_VERSION_SUFFIX))?.is_some() {
+        anyhow::bail!("a rekey of {} did not finish, rekey it again", database.display());
+    }
+    Ok(read_version(&side_file(&database, KEY_VERSION_SUFFIX))?.unwrap_or(0))
+}
+
+fn database_file(database_url: &str) -> Result<Option<PathBuf>, anyhow::Error> {
+    match crate::drivers::DatabaseUrl::parse(database_url)?.0 {
+        crate::drivers::DatabaseUrl::SqliteFile { path, .. } => Ok(Some(path)),
+        _ => Ok(None),
+    }
+}
+
+/// the versions a rekey of a sqlite file goes between, recorded on disk by its methods
+pub(crate) struct KeyRotation {
+    database: PathBuf,
+    /// the version the file is under, or was before an unfinished rekey
+    pub from: u32,
+    pub to: u32,
+    /// a rekey to `to` was started before and may have gone through
+    pub resumed: bool,
+}
+
+impl KeyRotation {
+    pub fn next(database_url: &str) -> Result<Self, anyhow::Error> {
+        let database = database_file(database_url)?
+            .ok_or_else(|| anyhow::Error::msg("only sqlite file tenants can be encrypted"))?;
+        let from = read_version(&side_file(&database, KEY_VERSION_SUFFIX))?.unwrap_or(0);
+        let pending = read_version(&side_file(&database, PENDING_KEY_VERSION_SUFFIX))?;
+        let to = from.checked_add(1).ok_or_else(|| anyhow::Error::msg("the key version is exhausted"))?;
+        Ok(Self { database, from, to, resumed: pending == Some(to) })
+    }
+    /// call before the file is rekeyed
+    pub fn start(&self) -> Result<(), anyhow::Error> {
+        write_version(&side_file(&self.database, PENDING_KEY_VERSION_SUFFIX), self.to)
+    }
+    /// call once the file is under the new key
+    pub fn finish(&self) -> Result<(), anyhow::Error> {
+        write_version(&side_file(&self.database, KEY_VERSION_SUFFIX), self.to)?;
+        let pending = side_file(&self.database, PENDING_KEY_VERSION_SUFFIX);
+        std::fs::remove_file(&pending).with_context(|| format!("while removing {}", pending.display()))
+    }
+}
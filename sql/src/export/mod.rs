@@ -0,0 +1,3 @@
+// file exports that leave the app, usually for the bank or another program
+
+pub mod positive_pay;
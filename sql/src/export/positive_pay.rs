@@ -0,0 +1,414 @@
+// positive pay issue files.
+// the bank compares presented checks against this list and flags anything
+// that was not issued (or was voided) so we can reject it.
+//
+// the file is driven from a CheckRegister, every check written on the accounts with the day
+// it was voided, if it was. there is no check register table in the tenant database yet, so
+// the caller fills the register. once the table exists only the loading changes.
+
+use anyhow::Context;
+use chrono::NaiveDate;
+
+/// One line in the issue file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct IssuedCheck {
+    pub account_number: String,
+    pub check_number: u64,
+    /// amount in cents, always positive. a voided check keeps the amount it was written for
+    pub amount: i64,
+    pub issue_date: NaiveDate,
+    pub payee: String,
+    /// the day the check was voided, none while it stands
+    pub void_date: Option<NaiveDate>,
+}
+
+/// every check written, one per account and check number, in the order they were written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct CheckRegister {
+    checks: Vec<IssuedCheck>,
+}
+
+impl CheckRegister {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn checks(&self) -> &[IssuedCheck] {
+        &self.checks
+    }
+    fn find(&self, account_number: &str, check_number: u64) -> Option<usize> {
+        self.checks
+            .iter()
+            .position(|c| c.account_number == account_number && c.check_number == check_number)
+    }
+    /// records a written check. a check number is only used once per account
+    pub fn issue(&mut self, check: IssuedCheck) -> Result<(), anyhow::Error> {
+        if self.find(&check.account_number, check.check_number).is_some() {
+            anyhow::bail!(
+                "check number {} is already in the register for account {}",
+                check.check_number,
+                check.account_number
+            );
+        }
+        if check.void_date.is_some_and(|d| d < check.issue_date) {
+            anyhow::bail!("check number {} is voided before it was issued", check.check_number);
+        }
+        self.checks.push(check);
+        Ok(())
+    }
+    /// marks a check in the register voided on `on`
+    pub fn void(
+        &mut self,
+        account_number: &str,
+        check_number: u64,
+        on: NaiveDate,
+    ) -> Result<(), anyhow::Error> {
+        let i = self.find(account_number, check_number).ok_or_else(|| {
+            anyhow::Error::msg(format!(
+                "check number {check_number} is not in the register for account {account_number}"
+            ))
+        })?;
+        let check = &mut self.checks[i];
+        if let Some(voided) = check.void_date {
+            anyhow::bail!("check number {check_number} was already voided on {voided}");
+        }
+        if on < check.issue_date {
+            anyhow::bail!("check number {check_number} can not be voided before it was issued");
+        }
+        check.void_date = Some(on);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum PositivePayField {
+    AccountNumber,
+    CheckNumber,
+    Amount,
+    IssueDate,
+    Payee,
+    VoidFlag,
+    /// a fixed value the bank wants in every record, like a record type code
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// a column in the layout. width, align and pad are only used by fixed width files
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct PositivePayColumn {
+    pub field: PositivePayField,
+    pub header: String,
+    pub width: usize,
+    pub align: Align,
+    pub pad: char,
+}
+
+impl PositivePayColumn {
+    pub fn new(field: PositivePayField, header: &str, width: usize) -> Self {
+        // numbers are right aligned and zero filled by almost every bank spec
+        let (align, pad) = match field {
+            PositivePayField::CheckNumber | PositivePayField::Amount => (Align::Right, '0'),
+            _ => (Align::Left, ' '),
+        };
+        Self {
+            field,
+            header: header.to_string(),
+            width,
+            align,
+            pad,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum PositivePayFormat {
+    Csv { delimiter: char, header: bool },
+    FixedWidth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct PositivePayLayout {
+    pub format: PositivePayFormat,
+    pub columns: Vec<PositivePayColumn>,
+    /// strftime style format, ex: "%m%d%Y"
+    pub date_format: String,
+    /// when true amounts are written in cents without a decimal point (12345 instead of 123.45)
+    pub implied_decimal: bool,
+    pub issued_flag: String,
+    pub void_flag: String,
+    pub line_ending: String,
+}
+
+impl PositivePayLayout {
+    /// a plain csv layout that most banks accept for manual uploads
+    pub fn csv() -> Self {
+        use PositivePayField::*;
+        Self {
+            format: PositivePayFormat::Csv {
+                delimiter: ',',
+                header: true,
+            },
+            columns: vec![
+                PositivePayColumn::new(AccountNumber, "Account", 0),
+                PositivePayColumn::new(CheckNumber, "Check Number", 0),
+                PositivePayColumn::new(Amount, "Amount", 0),
+                PositivePayColumn::new(IssueDate, "Issue Date", 0),
+                PositivePayColumn::new(Payee, "Payee", 0),
+                PositivePayColumn::new(VoidFlag, "Void", 0),
+            ],
+            date_format: "%m/%d/%Y".to_string(),
+            implied_decimal: false,
+            issued_flag: "I".to_string(),
+            void_flag: "V".to_string(),
+            line_ending: "\r\n".to_string(),
+        }
+    }
+    /// the common 80 byte fixed width record. adjust widths for your bank's spec
+    pub fn fixed_width() -> Self {
+        use PositivePayField::*;
+        Self {
+            format: PositivePayFormat::FixedWidth,
+            columns: vec![
+                PositivePayColumn::new(AccountNumber, "Account", 15),
+                PositivePayColumn::new(CheckNumber, "Check Number", 10),
+                PositivePayColumn::new(Amount, "Amount", 12),
+                PositivePayColumn::new(IssueDate, "Issue Date", 8),
+                PositivePayColumn::new(Payee, "Payee", 34),
+                PositivePayColumn::new(VoidFlag, "Void", 1),
+            ],
+            date_format: "%m%d%Y".to_string(),
+            implied_decimal: true,
+            issued_flag: "I".to_string(),
+            void_flag: "V".to_string(),
+            line_ending: "\r\n".to_string(),
+        }
+    }
+
+    fn format_value(&self, check: &IssuedCheck, field: &PositivePayField) -> String {
+        match field {
+            PositivePayField::AccountNumber => check.account_number.clone(),
+            PositivePayField::CheckNumber => check.check_number.to_string(),
+            PositivePayField::Amount => {
+                let cents = check.amount;
+                if self.implied_decimal {
+                    cents.to_string()
+                } else {
+                    format!("{}.{:02}", cents / 100, cents % 100)
+                }
+            }
+            PositivePayField::IssueDate => check.issue_date.format(&self.date_format).to_string(),
+            PositivePayField::Payee => check.payee.clone(),
+            PositivePayField::VoidFlag => {
+                if check.void_date.is_some() {
+                    self.void_flag.clone()
+                } else {
+                    self.issued_flag.clone()
+                }
+            }
+            PositivePayField::Literal(s) => s.clone(),
+        }
+    }
+
+    fn format_csv_value(value: String, delimiter: char) -> String {
+        if value.contains(delimiter) || value.contains('"') || value.contains('\n') {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    }
+
+    fn format_fixed_value(column: &PositivePayColumn, value: String) -> Result<String, anyhow::Error> {
+        let len = value.chars().count();
+        if len > column.width {
+            // payee names can be cut, numbers cannot
+            if column.field == PositivePayField::Payee {
+                return Ok(value.chars().take(column.width).collect());
+            }
+            return Err(anyhow::Error::msg(format!(
+                "value '{value}' for column {} does not fit in {} characters",
+                column.header, column.width
+            )));
+        }
+        let padding: String = std::iter::repeat_n(column.pad, column.width - len).collect();
+        Ok(match column.align {
+            Align::Left => value + &padding,
+            Align::Right => padding + &value,
+        })
+    }
+
+    pub fn format_record(&self, check: &IssuedCheck) -> Result<String, anyhow::Error> {
+        // the bank would read a reversal as a new issue, voids go out with a void date instead
+        if check.amount <= 0 {
+            return Err(anyhow::Error::msg(format!(
+                "check number {} has amount {}, positive pay only takes positive amounts. void the check in the register instead",
+                check.check_number, check.amount
+            )));
+        }
+        let mut line = String::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            let value = self.format_value(check, &column.field);
+            match self.format {
+                PositivePayFormat::Csv { delimiter, .. } => {
+                    if i > 0 {
+                        line.push(delimiter);
+                    }
+                    line.push_str(&Self::format_csv_value(value, delimiter));
+                }
+                PositivePayFormat::FixedWidth => {
+                    let value = Self::format_fixed_value(column, value).with_context(|| {
+                        format!("while formatting check number {}", check.check_number)
+                    })?;
+                    line.push_str(&value);
+                }
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Writes every check in the register issued or voided between from and to (inclusive) in the given layout.
+/// a check voided in the range goes out as a void whenever it was issued. one issued in the range
+/// and voided after it goes out as issued, the void follows in a later file.
+/// checks are written in check number order
+pub fn export_positive_pay(
+    register: &CheckRegister,
+    from: NaiveDate,
+    to: NaiveDate,
+    layout: &PositivePayLayout,
+) -> Result<String, anyhow::Error> {
+    if from > to {
+        return Err(anyhow::Error::msg(
+            "positive pay export start date is after the end date",
+        ));
+    }
+    let in_range = |d: NaiveDate| d >= from && d <= to;
+    let mut checks: Vec<IssuedCheck> = register
+        .checks()
+        .iter()
+        .filter_map(|c| match c.void_date {
+            Some(voided) if in_range(voided) => Some(c.clone()),
+            _ if in_range(c.issue_date) => Some(IssuedCheck {
+                void_date: None,
+                ..c.clone()
+            }),
+            _ => None,
+        })
+        .collect();
+    checks.sort_by(|a, b| {
+        (&a.account_number, a.check_number).cmp(&(&b.account_number, b.check_number))
+    });
+
+    let mut out = String::new();
+    if let PositivePayFormat::Csv {
+        delimiter,
+        header: true,
+    } = layout.format
+    {
+        let header: Vec<String> = layout
+            .columns
+            .iter()
+            .map(|c| PositivePayLayout::format_csv_value(c.header.clone(), delimiter))
+            .collect();
+        out.push_str(&header.join(&delimiter.to_string()));
+        out.push_str(&layout.line_ending);
+    }
+    for check in &checks {
+        out.push_str(&layout.format_record(check)?);
+        out.push_str(&layout.line_ending);
+    }
+    Ok(out)
+}
+
+#[test]
+pub fn test_positive_pay_export() -> Result<(), anyhow::Error> {
+    let day = |d| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+    let check = |check_number, amount, issue_date, payee: &str| IssuedCheck {
+        account_number: "123456789".to_string(),
+        check_number,
+        amount,
+        issue_date,
+        payee: payee.to_string(),
+        void_date: None,
+    };
+    let mut register = CheckRegister::new();
+    register.issue(check(1002, 4_250, day(14), "Home Depot, Inc"))?;
+    register.issue(check(1001, 123_456, day(2), "City Water"))?;
+    register.issue(check(1003, 100, day(20), "Out of range"))?;
+    register.void("123456789", 1002, day(15))?;
+    assert!(register.issue(check(1001, 1, day(3), "Again")).is_err());
+    assert!(register.void("123456789", 1002, day(16)).is_err());
+
+    let csv = export_positive_pay(&register, day(1), day(15), &PositivePayLayout::csv())?;
+    assert_eq!(
+        csv,
+        "Account,Check Number,Amount,Issue Date,Payee,Void\r\n\
+         123456789,1001,1234.56,03/02/2026,City Water,I\r\n\
+         123456789,1002,42.50,03/14/2026,\"Home Depot, Inc\",V\r\n"
+    );
+
+    let fixed = export_positive_pay(&register, day(1), day(15), &PositivePayLayout::fixed_width())?;
+    let first = fixed.lines().next().unwrap();
+    assert_eq!(first.len(), 80);
+    assert_eq!(&first[..25], "123456789      0000001001");
+    assert_eq!(&first[25..45], "00000012345603022026");
+    assert!(first.ends_with('I'));
+
+    // voids are picked by the day they were voided, not the day the check was written
+    register.void("123456789", 1001, day(21))?;
+    let csv = export_positive_pay(&register, day(16), day(31), &PositivePayLayout::csv())?;
+    assert_eq!(
+        csv.lines().skip(1).collect::<Vec<_>>(),
+        [
+            "123456789,1001,1234.56,03/02/2026,City Water,V",
+            "123456789,1003,1.00,03/20/2026,Out of range,I",
+        ]
+    );
+    // a check voided after the range still went out as issued
+    let csv = export_positive_pay(&register, day(1), day(14), &PositivePayLayout::csv())?;
+    assert!(csv.contains("1002,42.50,03/14/2026,\"Home Depot, Inc\",I"));
+    Ok(())
+}
+
+#[test]
+pub fn test_positive_pay_rejects_non_positive_amounts() -> Result<(), anyhow::Error> {
+    let day = |d| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+    let check = IssuedCheck {
+        account_number: "123456789".to_string(),
+        check_number: 1004,
+        amount: -4_250,
+        issue_date: day(3),
+        payee: "Reversed".to_string(),
+        void_date: Some(day(3)),
+    };
+    for layout in [PositivePayLayout::csv(), PositivePayLayout::fixed_width()] {
+        let mut register = CheckRegister::new();
+        register.issue(check.clone())?;
+        let err = export_positive_pay(&register, day(1), day(31), &layout).unwrap_err();
+        assert!(err.to_string().contains("check number 1004"));
+        let mut register = CheckRegister::new();
+        register.issue(IssuedCheck {
+            amount: 0,
+            ..check.clone()
+        })?;
+        assert!(export_positive_pay(&register, day(1), day(31), &layout).is_err());
+    }
+    Ok(())
+}
@@ -1,12 +1,22 @@
-use std::{marker::PhantomData, ops::Deref};
-
-use sqlx::{SqlitePool, prelude::FromRow, sqlite::SqliteConnectOptions};
-use uuid::Uuid;
+use sqlx::SqlitePool;
 
 pub mod user;
 pub mod core;
 pub mod drivers;
 pub mod registry;
+pub mod directory;
+pub mod export;
+pub mod audit;
+pub mod encryption;
+#[cfg(feature = "backup")]
+pub mod backup;
+
+// lets code generated by sql_proc_macro name this crate as ::sql, here and downstream
+extern crate self as sql;
+#[doc(hidden)]
+pub mod __private {
+    pub use sqlx;
+}
 
 
 // macro_rules! entity {
@@ -17,22 +27,6 @@ pub mod registry;
 //     };
 // }
 
-macro_rules! sqlite_impl {
-    ($T:ty,$Table_literal:literal) => {
-        impl $T
-        where
-            $T: $crate::Table + $crate::GetId + $crate::PrimaryKey + Self::Sized,
-        {
-            async fn async_get_one<'a, E>(executor: E, id: &Id<$T>) -> $T {
-                sqlx::query_as!($T, concat!("src/scripts/sqlite/", $table_literal,))
-                    .fetch_one(executor)
-                    .await
-            }
-        }
-    };
-}
-
-// sql_proc_macro::sqlite_impl!(User);
 
 pub async fn connect_sqlite<S: AsRef<str>>(
     database_url: Option<S>,
@@ -1,32 +1,419 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::{Duration, Instant}};
 
+use anyhow::Context;
 use dashmap::DashMap;
 
+use crate::core::models::UserId;
+use crate::directory::{TenantDirectory, TenantRecord, TenantStatus};
+use crate::audit::{AuditSeal, TenantAudit};
+use crate::drivers::{Backend, DatabaseUrl, UserTenantStore};
+use crate::encryption::{DatabaseKey, TenantEncryption};
+
+/// how the factory finds, opens and retires tenant databases
+#[derive(Debug, Clone)]
+pub struct TenantOptions {
+    /// where a tenant lives, {user_id} is replaced with the user id. ex: sqlite:///data/{user_id}.db
+    pub url_template: Option<String>,
+    /// stores unused for longer than this are dropped by evict_idle
+    pub idle_timeout: Option<Duration>,
+    /// past this many open stores the least recently used one is closed, see evict
+    pub max_open_tenants: Option<usize>,
+    /// where archive() moves sqlite tenants
+    pub archive_directory: Option<PathBuf>,
+    pub run_migrations_on_connect: bool,
+    /// opens sqlite tenants with sqlcipher, under a key per user. needs the sqlcipher feature
+    pub encryption: Option<TenantEncryption>,
+    /// MACs each tenant's audit log under a key per user and keeps its head outside the tenant
+    pub audit: Option<TenantAudit>,
+}
+impl Default for TenantOptions {
+    fn default() -> Self {
+        Self {
+            url_template: None,
+            idle_timeout: None,
+            max_open_tenants: None,
+            archive_directory: None,
+            run_migrations_on_connect: true,
+            encryption: None,
+            audit: None,
+        }
+    }
+}
+
+struct OpenTenant {
+    store: Arc<dyn UserTenantStore>,
+    url: String,
+    last_used: std::sync::Mutex<Instant>,
+}
+impl OpenTenant {
+    fn touch(&self) -> Arc<dyn UserTenantStore> {
+        *self.last_used.lock().unwrap() = Instant::now();
+        self.store.clone()
+    }
+    fn idle_for(&self) -> Duration {
+        self.last_used.lock().unwrap().elapsed()
+    }
+}
+
+/// the open tenant stores, keyed by user. every method takes &self so one factory
+/// can be shared between request handlers behind an Arc.
+/// with a directory, tenants are found through the primary database instead of the url template
 pub struct UserStoreFactory {
-    pools: DashMap<crate::core::models::UserId,Arc<dyn crate::drivers::UserTenantStore>>
+    pools: DashMap<UserId,OpenTenant>,
+    options: TenantOptions,
+    directory: Option<Arc<dyn TenantDirectory>>,
+}
+
+fn tenant_schema_version() -> i64 {
+    crate::drivers::migrations::runner::latest(crate::drivers::migrations::user_multitenant::MIGRATIONS)
+}
+impl Default for UserStoreFactory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl UserStoreFactory {
     pub fn new()-> Self {
+        Self::with_options(TenantOptions::default())
+    }
+    pub fn with_options(options: TenantOptions) -> Self {
         Self {
-            pools: DashMap::new()
+            pools: DashMap::new(),
+            options,
+            directory: None,
+        }
+    }
+    pub fn with_directory(options: TenantOptions, directory: Arc<dyn TenantDirectory>) -> Self {
+        Self {
+            pools: DashMap::new(),
+            options,
+            directory: Some(directory),
+        }
+    }
+    pub fn directory(&self) -> Option<&Arc<dyn TenantDirectory>> {
+        self.directory.as_ref()
+    }
+    pub fn options(&self) -> &TenantOptions {
+        &self.options
+    }
+    /// the number of stores currently held open
+    pub fn open_tenants(&self) -> usize {
+        self.pools.len()
+    }
+    /// the url for a tenant from the url template
+    pub fn tenant_url(&self, id: &UserId) -> Result<String, anyhow::Error> {
+        let template = self.options.url_template.as_ref().ok_or_else(|| anyhow::Error::msg("no tenant url template configured"))?;
+        Ok(template.replace("{user_id}", &id.value.to_string()))
+    }
+    /// the open store for a user, without connecting
+    pub fn get(&self, id: &UserId) -> Option<Arc<dyn UserTenantStore>> {
+        self.pools.get(id).map(|t| t.touch())
+    }
+    /// the store for a user, connecting if it is not open yet. the url comes from the directory
+    /// when there is one, the url template otherwise. the database has to exist, see provision
+    pub async fn store(&self, id: &UserId) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+        if let Some(p) = self.get(id) {
+            return Ok(p)
+        }
+        let Some(directory) = &self.directory else {
+            let url = self.tenant_url(id)?;
+            return self.connect_url(id, url, self.options.run_migrations_on_connect).await
+        };
+        let mut record = directory.lookup(id).await?.ok_or_else(|| anyhow::Error::msg("no tenant registered for this user"))?;
+        if record.status != TenantStatus::Active {
+            anyhow::bail!("the tenant for this user is {}", record.status.as_str());
+        }
+        let migrate = self.options.run_migrations_on_connect;
+        let store = self.connect_url(id, &record.url, migrate).await?;
+        record.last_access = chrono::Utc::now().timestamp_millis();
+        if migrate {
+            record.schema_version = tenant_schema_version();
+        }
+        directory.update(&record).await?;
+        Ok(store)
+    }
+    /// creates the tenant database if needed, brings its schema up to date, opens it and
+    /// records it in the directory. an active tenant already in the directory keeps its url,
+    /// anyone else gets the url template
+    pub async fn provision(&self, id: &UserId) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+        let existing = match &self.directory {
+            Some(directory) => directory.lookup(id).await?,
+            None => None,
+        };
+        let url = match &existing {
+            Some(record) if record.status == TenantStatus::Active => record.url.clone(),
+            _ => self.tenant_url(id)?,
+        };
+        crate::drivers::provision_tenant_url(&url).await?;
+        let store = match self.get(id) {
+            Some(p) => p,
+            None => self.connect_url(id, &url, true).await?,
+        };
+        if let Some(directory) = &self.directory {
+            let mut record = TenantRecord::new(id.clone(), url, tenant_schema_version());
+            match existing {
+                Some(old) => {
+                    record.created_at = old.created_at;
+                    directory.update(&record).await?;
+                }
+                None => directory.register(&record).await?,
+            }
+        }
+        Ok(store)
+    }
+    pub async fn connect_pooled_url<S: AsRef<str>>(&self, id: &UserId, database_url:S,run_migrations_on_connect:bool) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+        if let Some(p) = self.get(id) {
+            return Ok(p)
         }
+        let p = self.connect_tenant(id, database_url.as_ref(), true, run_migrations_on_connect).await?;
+        Ok(self.insert(id, database_url.as_ref(), p).await)
     }
-    pub async fn connect_pooled_url<S: AsRef<str>>(&mut self, id: &crate::core::models::UserId, database_url:S,run_migrations_on_connect:bool) -> Result<Arc<dyn crate::drivers::UserTenantStore>, anyhow::Error> {
-        if let Some(p) = self.pools.get(id) {
-            return Ok(p.clone())
+    pub async fn connect_url<S: AsRef<str>>(&self, id: &UserId, database_url:S,run_migrations_on_connect:bool) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+        if let Some(p) = self.get(id) {
+            return Ok(p)
         }
-        let p = crate::drivers::connect_tenant_any_pool_url(database_url.as_ref(),run_migrations_on_connect).await?;
-        let c = p.clone();
-        self.pools.insert(id.clone(), p);
-        Ok(c)
+        let p = self.connect_tenant(id, database_url.as_ref(), false, run_migrations_on_connect).await?;
+        Ok(self.insert(id, database_url.as_ref(), p).await)
     }
-        pub async fn connect_url<S: AsRef<str>>(&mut self, id: &crate::core::models::UserId, database_url:S,run_migrations_on_connect:bool) -> Result<Arc<dyn crate::drivers::UserTenantStore>, anyhow::Error> {
-        if let Some(p) = self.pools.get(id) {
-            return Ok(p.clone())
+    /// the key the tenant's database at `url` is encrypted with, if encryption is configured.
+    /// derived from the key version recorded next to the database, see rekey
+    pub fn tenant_key(&self, id: &UserId, url: &str) -> Result<Option<DatabaseKey>, anyhow::Error> {
+        let Some(encryption) = &self.options.encryption else {
+            return Ok(None)
+        };
+        encryption.key_for(id, crate::encryption::key_version_url(url)?).map(Some)
+    }
+    /// the seal the tenant's audit log is written under, if audit keys are configured
+    pub fn audit_seal(&self, id: &UserId) -> Result<Option<AuditSeal>, anyhow::Error> {
+        self.options.audit.as_ref().map(|audit| audit.seal_for(id)).transpose()
+    }
+    /// opens the tenant with its database key and audit seal. unpooled postgres and mysql
+    /// tenants get a single connection, like connect_tenant_any_url
+    async fn connect_tenant(&self, id: &UserId, database_url: &str, pooled: bool, migrate: bool) -> Result<Arc<dyn UserTenantStore>, anyhow::Error> {
+        let (url, mut settings) = DatabaseUrl::parse(database_url)?;
+        if !pooled && url.backend() != Backend::Sqlite {
+            settings.max_connections.get_or_insert(1);
+        }
+        settings.key = self.tenant_key(id, database_url)?;
+        settings.audit = self.audit_seal(id)?;
+        crate::drivers::connect_tenant_with(&url, &settings, migrate).await
+    }
+    async fn insert(&self, id: &UserId, url: &str, store: Arc<dyn UserTenantStore>) -> Arc<dyn UserTenantStore> {
+        // two callers can race to open the same tenant, the first one in wins
+        let winner = self.pools.entry(id.clone()).or_insert_with(|| OpenTenant {
+            store: store.clone(),
+            url: url.to_string(),
+            last_used: std::sync::Mutex::new(Instant::now()),
+        }).touch();
+        if !Arc::ptr_eq(&winner, &store) {
+            store.close().await;
+        }
+        if let Some(max) = self.options.max_open_tenants {
+            while self.pools.len() > max {
+                let oldest = self.pools.iter()
+                    .filter(|t| t.key() != id)
+                    .max_by_key(|t| t.idle_for())
+                    .map(|t| t.key().clone());
+                match oldest {
+                    Some(oldest) => {
+                        if let Some((_, evicted)) = self.pools.remove(&oldest) {
+                            Self::release(evicted.store).await;
+                        }
+                    }
+                    None => break,
+                }
+            }
         }
-        let p = crate::drivers::connect_tenant_any_url(database_url.as_ref(),run_migrations_on_connect).await?;
-        let c = p.clone();
-        self.pools.insert(id.clone(), p);
-        Ok(c)
+        winner
     }
-}1
\ No newline at end of file
+    // closes an evicted store now, unless a handler still has it. that one keeps working and
+    // the pool closes once the last of them lets go, as with evict
+    async fn release(store: Arc<dyn UserTenantStore>) {
+        if Arc::strong_count(&store) == 1 {
+            store.close().await;
+        }
+    }
+    /// stops holding a store open. handlers that still have it keep working, the pool
+    /// closes once the last of them lets go
+    pub fn evict(&self, id: &UserId) -> bool {
+        self.pools.remove(id).is_some()
+    }
+    /// evicts every store unused for longer than the idle timeout, returns who was evicted
+    pub fn evict_idle(&self) -> Vec<UserId> {
+        let Some(timeout) = self.options.idle_timeout else {
+            return vec![]
+        };
+        let idle: Vec<UserId> = self.pools.iter()
+            .filter(|t| t.idle_for() >= timeout)
+            .map(|t| t.key().clone())
+            .collect();
+        for id in &idle {
+            self.pools.remove_if(id, |_, t| t.idle_for() >= timeout);
+        }
+        idle
+    }
+    /// runs evict_idle every `every` until the factory is dropped
+    pub fn spawn_evictor(self: &Arc<Self>, every: Duration) -> tokio::task::JoinHandle<()> {
+        let factory = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            loop {
+                interval.tick().await;
+                let Some(factory) = factory.upgrade() else {
+                    return
+                };
+                factory.evict_idle();
+            }
+        })
+    }
+    /// closes the store and returns its url: the one it was opened with, the directory's, or the template's
+    async fn close(&self, id: &UserId) -> Result<(String, Option<TenantRecord>), anyhow::Error> {
+        let record = match &self.directory {
+            Some(directory) => directory.lookup(id).await?,
+            None => None,
+        };
+        if let Some((_, t)) = self.pools.remove(id) {
+            t.store.close().await;
+            return Ok((t.url, record))
+        }
+        match &record {
+            Some(r) => Ok((r.url.clone(), record)),
+            None => Ok((self.tenant_url(id)?, record)),
+        }
+    }
+    async fn set_status(&self, record: Option<TenantRecord>, status: TenantStatus, url: Option<String>) -> Result<(), anyhow::Error> {
+        if let (Some(directory), Some(mut record)) = (&self.directory, record) {
+            record.status = status;
+            if let Some(url) = url {
+                record.url = url;
+            }
+            directory.update(&record).await?;
+        }
+        Ok(())
+    }
+    /// closes the tenant and destroys its database. this can not be undone, see archive
+    pub async fn deprovision(&self, id: &UserId) -> Result<(), anyhow::Error> {
+        let (url, record) = self.close(id).await?;
+        crate::drivers::drop_tenant_url(url).await?;
+        // a tenant provisioned again starts a new log
+        if let Some(seal) = self.audit_seal(id)? {
+            seal.restored(None)?;
+        }
+        self.set_status(record, TenantStatus::Deprovisioned, None).await
+    }
+    /// the url the tenant is open with, or the one it would be opened with
+    async fn url_for(&self, id: &UserId) -> Result<String, anyhow::Error> {
+        if let Some(t) = self.pools.get(id) {
+            return Ok(t.url.clone())
+        }
+        if let Some(directory) = &self.directory
+            && let Some(record) = directory.lookup(id).await?
+        {
+            return Ok(record.url)
+        }
+        self.tenant_url(id)
+    }
+    /// backs the tenant up into `directory` while it stays open, see crate::backup::backup_tenant.
+    /// `archive_key` seals the backup
+    #[cfg(feature = "backup")]
+    pub async fn backup(&self, id: &UserId, directory: &std::path::Path, archive_key: Option<&DatabaseKey>) -> Result<PathBuf, anyhow::Error> {
+        let url = self.url_for(id).await?;
+        let store = self.store(id).await?;
+        let tenant_key = self.tenant_key(id, &url)?;
+        crate::backup::backup_tenant(&url, store.as_ref(), id, tenant_key.as_ref(), archive_key, directory).await
+    }
+    /// replaces the tenant's data with a backup. the backup is opened and checked before the
+    /// tenant is touched. a sqlite file backup goes back in place of the file. a bundle is imported
+    /// into a new database next to the tenant's, which only replaces it once the import succeeded
+    #[cfg(feature = "backup")]
+    #[allow(unreachable_code)] // the error is only reached without sqlite
+    pub async fn restore(&self, id: &UserId, archive: &std::path::Path, archive_key: Option<&DatabaseKey>) -> Result<(), anyhow::Error> {
+        let bytes = std::fs::read(archive).with_context(|| format!("while reading the backup {}", archive.display()))?;
+        let (kind, payload) = crate::backup::open_archive(&bytes, archive_key)?;
+        if kind == crate::backup::ArchiveKind::Bundle {
+            let bundle = crate::backup::bundle_from_json(&payload)?;
+            crate::backup::check_bundle(&bundle, tenant_schema_version())?;
+            if bundle.user_id != *id {
+                anyhow::bail!("the backup belongs to user {}", bundle.user_id);
+            }
+            return self.restore_bundle(id, &bundle).await
+        }
+        #[cfg(feature = "sqlite")]
+        {
+            let (url, _) = self.close(id).await?;
+            let tenant_key = self.tenant_key(id, &url)?;
+            crate::backup::restore_sqlite_file(&payload, &url, id, tenant_key.as_ref()).await?;
+            // the file brought its own log back, the head goes back with it
+            if let Some(seal) = self.audit_seal(id)? {
+                let store = self.store(id).await?;
+                seal.restored(store.audit_log().await?.last())?;
+            }
+            return Ok(())
+        }
+        Err(anyhow::Error::msg("the backup is a sqlite file, which this build can not restore"))
+    }
+    /// imports into a staging database, then points the directory at it, or without a directory
+    /// imports again at the template url. the old data is only dropped after the first import
+    #[cfg(feature = "backup")]
+    async fn restore_bundle(&self, id: &UserId, bundle: &crate::backup::TenantBundle) -> Result<(), anyhow::Error> {
+        let (url, record) = self.close(id).await?;
+        let staging = crate::drivers::sibling_url(&url, "restored")?;
+        crate::drivers::provision_tenant_url(&staging).await?;
+        if let Err(e) = self.import_unpooled(id, &staging, bundle).await {
+            return Err(match crate::drivers::drop_tenant_url(&staging).await {
+                Ok(()) => e,
+                Err(dropping) => e.context(format!("the partial restore in {staging} could not be removed: {dropping}")),
+            })
+        }
+        if let Some(record) = record {
+            let replaced = (record.status == TenantStatus::Active).then(|| record.url.clone());
+            self.set_status(Some(record), TenantStatus::Active, Some(staging.clone())).await?;
+            if let Some(old) = replaced {
+                crate::drivers::drop_tenant_url(&old).await
+                    .with_context(|| format!("the backup was restored into {staging}, but the replaced database {old} could not be removed"))?;
+            }
+            return Ok(())
+        }
+        crate::drivers::drop_tenant_url(&url).await
+            .with_context(|| format!("the backup was restored into {staging}, but the database it replaces could not be removed"))?;
+        let store = self.provision(id).await
+            .with_context(|| format!("the backup was restored into {staging}, but {url} could not be created again"))?;
+        crate::backup::import_tenant(store.as_ref(), bundle).await
+            .with_context(|| format!("the backup was restored into {staging}, but could not be copied to {url}"))?;
+        crate::drivers::drop_tenant_url(&staging).await
+    }
+    /// imports into a database the factory does not hold open
+    #[cfg(feature = "backup")]
+    async fn import_unpooled(&self, id: &UserId, url: &str, bundle: &crate::backup::TenantBundle) -> Result<(), anyhow::Error> {
+        let store = self.connect_tenant(id, url, false, true).await?;
+        let imported = crate::backup::import_tenant(store.as_ref(), bundle).await;
+        store.close().await;
+        imported
+    }
+    /// closes the tenant and re-encrypts it under the key for its next key version, sqlite only.
+    /// the version is recorded next to the database, so every factory with the same encryption
+    /// opens it with the new key from then on. returns the new version.
+    /// a rekey that was cut short is finished by calling rekey again
+    pub async fn rekey(&self, id: &UserId) -> Result<u32, anyhow::Error> {
+        let encryption = self.options.encryption.as_ref().ok_or_else(|| anyhow::Error::msg("tenant encryption is not configured"))?;
+        let (url, _) = self.close(id).await?;
+        let rotation = crate::encryption::KeyRotation::next(&url)?;
+        let key = encryption.key_for(id, rotation.from)?;
+        let new_key = encryption.key_for(id, rotation.to)?;
+        // sqlcipher rekeys in one transaction, so the file is under one of the two keys
+        if !(rotation.resumed && crate::drivers::tenant_key_opens_url(&url, &new_key).await) {
+            rotation.start()?;
+            crate::drivers::rekey_tenant_url(&url, &key, &new_key).await?;
+        }
+        rotation.finish()?;
+        Ok(rotation.to)
+    }
+    /// closes the tenant and moves its database into the archive directory, sqlite only
+    pub async fn archive(&self, id: &UserId) -> Result<PathBuf, anyhow::Error> {
+        let directory = self.options.archive_directory.clone().ok_or_else(|| anyhow::Error::msg("no archive directory configured"))?;
+        let (url, record) = self.close(id).await?;
+        let archived = crate::drivers::archive_tenant_url(url, &directory).await?;
+        self.set_status(record, TenantStatus::Archived, Some(format!("sqlite://{}", archived.display()))).await?;
+        Ok(archived)
+    }
+}
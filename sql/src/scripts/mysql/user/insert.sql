@@ -1 +0,0 @@
--- TODO: insert user
\ No newline at end of file
@@ -1 +0,0 @@
-Select * from users
\ No newline at end of file
@@ -1 +0,0 @@
-SELECT * from "users" where "users"."id" = ? and "users.id" is not null LIMIT 1
\ No newline at end of file
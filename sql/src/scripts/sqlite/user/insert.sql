@@ -1 +0,0 @@
-INSERT INTO "users" (id) VALUES(?) returning "users"."id"
\ No newline at end of file
@@ -1 +0,0 @@
-INSERT INTO "users" (id) VALUES(?)
\ No newline at end of file
@@ -1 +0,0 @@
-insert into "users" (id) values (?) returning "users"."id"
\ No newline at end of file
@@ -1 +0,0 @@
-UPDATE "users" set "users"."id" = $1 where id = $1
\ No newline at end of file
@@ -1,17 +1,14 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr};
-
 use dashmap::DashMap;
-use sqlx::{Acquire, Connection, SqlitePool, sqlite::SqliteConnectOptions};
 
-use crate::core::{GetId, Id, Table};
+use crate::core::Id;
 
 
 const ERROR_NO_DRIVERS_AVAILABLE: &str = "this operation cannot be completed because the database was compiled without any drivers enabled.";
 
-#[derive(PartialEq,Eq,Hash)]
-// #[sql_proc_macro::sqlite_impl]
+#[derive(PartialEq,Eq,Hash,sqlx::FromRow,crate::core::Entity)]
+#[table = "users"]
 pub struct User {
-    pub id: Id,
+    pub id: Id<User>,
     // #[sqlx(skip)]
     // connection: UserDatabase
 }
@@ -26,47 +23,28 @@ pub enum UserDatabasePool {
 }
 impl UserDatabasePool {
     pub async fn connect<S: AsRef<str>>(database_url: S) -> Result<Self,anyhow::Error> {
-        
-        if database_url.as_ref().starts_with("postgresql://") {
-            // assume postgres
-            #[cfg(not(feature = "postgresql"))] {
-                return Err(anyhow::Error::msg("The database library was not compiled with postgres support. postgres is not available"))
-            }
-            #[cfg(feature = "postgresql")] {
-                let o = sqlx::postgres::PgConnectOptions::from_str(database_url.as_ref())?;
-                let pool = sqlx::postgres::PgPool::connect_with(o).await?;
-                Result::Ok(UserDatabasePool::Postgres(pool))
-            }
-            
-        }
-        else if database_url.as_ref().starts_with("sqlite:///") {
-            // assume sqlite
-            #[cfg(feature = "sqlite")] {
-               let o = sqlx::sqlite::SqliteConnectOptions::from_str(database_url.as_ref())?;
-                let pool = sqlx::sqlite::SqlitePool::connect_with(o).await?;
-                Result::Ok(UserDatabasePool::Sqlite(pool))
-            }
-            // default path
-            #[cfg(not(feature = "sqlite"))]
-            {
-                return Err(anyhow::Error::msg("Database was not compiled with sqlite support enabled. cannot make a connection for sqlite"));
+        let (url, settings) = crate::drivers::DatabaseUrl::parse(database_url.as_ref())?;
+        Self::connect_with(&url, &settings).await
+    }
+    /// a pool for whichever backend the url is for
+    #[allow(unreachable_code)] // the error is only reached for a backend that was compiled out
+    pub async fn connect_with(url: &crate::drivers::DatabaseUrl, settings: &crate::drivers::PoolSettings) -> Result<Self,anyhow::Error> {
+        use crate::drivers::DatabaseUrl;
+        match url {
+            DatabaseUrl::SqliteFile { .. } | DatabaseUrl::SqliteMemory => {
+                #[cfg(feature = "sqlite")]
+                return Ok(Self::Sqlite(crate::drivers::sqlite::connect_pool_with(url, settings).await?));
             }
-        }
-        else if database_url.as_ref().starts_with("mysql://") {
-            // assume mysql
-            #[cfg(feature = "mysql")] {
-                let o = sqlx::mysql::MySqlConnectOptions::from_str(database_url.as_ref())?;
-                let pool = sqlx::mysql::MySqlPool::connect_with(o).await?;
-                Result::Ok(UserDatabasePool::MySql(pool))   
+            DatabaseUrl::Postgres(_) => {
+                #[cfg(feature = "postgresql")]
+                return Ok(Self::Postgres(crate::drivers::postgres::connect_pool_with(url, settings).await?));
             }
-            #[cfg(not(feature="mysql"))] {
-                return Err(anyhow::Error::msg("Database was not compiled with mysql support enabled. cannot make a connection for mysql"));
+            DatabaseUrl::MySql(_) => {
+                #[cfg(feature = "mysql")]
+                return Ok(Self::MySql(crate::drivers::mysql::connect_pool_with(url, settings).await?));
             }
         }
-        else  {
-            return Err(anyhow::Error::msg("Unsupported or unknown database protocol"))
-        }
-        
+        Err(url.backend().not_compiled())
     }
     pub async fn aquire(&self) -> Result<UserDbConnection,anyhow::Error> {
         match self {
@@ -84,7 +62,7 @@ impl UserDatabasePool {
             }
             #[allow(unreachable_patterns)]
             _=> {
-                return Err(anyhow::Error::msg(ERROR_NO_DRIVERS_AVAILABLE))
+                Err(anyhow::Error::msg(ERROR_NO_DRIVERS_AVAILABLE))
             }
 
         }
@@ -100,7 +78,7 @@ pub enum UserDbConnection {
     MySql(sqlx::pool::PoolConnection<sqlx::mysql::MySql>)
 }
 pub struct UserDatabases {
-    pools: DashMap<Id,UserDatabasePool>,
+    pools: DashMap<Id<User>,UserDatabasePool>,
     // data_directory: PathBuf
     // todo postgres
 }
@@ -117,7 +95,7 @@ impl UserDatabases {
             // data_directory
         }
     }
-    pub async fn connect<S: AsRef<str>>(&mut self, database_url: S, user_id: Id) -> Result<UserDbConnection, anyhow::Error> {
+    pub async fn connect<S: AsRef<str>>(&mut self, database_url: S, user_id: Id<User>) -> Result<UserDbConnection, anyhow::Error> {
         // let options = sqlx::any::AnyConnectOptions::from_str(database_url.as_ref())?;
         if let Some(pool) = self.pools.get(&user_id) {
             let c = pool.aquire().await?;
@@ -130,28 +108,22 @@ impl UserDatabases {
        self.pools.insert(user_id, p);
         Ok(c)
     }
-    pub async fn run_migrations(&self,id: &Id) -> Result<(),anyhow::Error> {
+    pub async fn run_migrations(&self,id: &Id<User>) -> Result<(),anyhow::Error> {
         let p = self.pools.get(id).ok_or_else(|| anyhow::Error::msg("pool not connected. make sure you connect() first"))?;
     
         // let c = p.aquire().await?;
         match p.value() {
             #[cfg(feature = "sqlite")]
             UserDatabasePool::Sqlite(p) => {
-                let migrator = sqlx::migrate!("./migrations/sqlite/user");
-                migrator.run(p).await?;
-                Ok(())
+                crate::drivers::run_user_multitenent_migrations_sqlite_pooled(p).await
             }
             #[cfg(feature = "postgresql")]
             UserDatabasePool::Postgres(p) => {
-                let migrator = sqlx::migrate!("./migrations/postgres/user");
-                migrator.run(p).await?;
-                Ok(())
+                crate::drivers::run_user_multitenent_migrations_postgres_pooled(p).await
             }
             #[cfg(feature = "mysql")]
             UserDatabasePool::MySql(p) => {
-                let migrator = sqlx::migrate!("./migrations/postgres/user");
-                migrator.run(p).await?;
-                Ok(())
+                crate::drivers::run_user_multitenant_migrations_mysql_pooled(p).await
             }
             
         }
@@ -164,7 +136,7 @@ impl UserDatabases {
 
 impl User {
     // pub fn get(connection: &UserDbConnection,id: Id<self>)
-    pub fn sqlite_get<'r,E>(executor: E,id: Id) -> Result<Self, anyhow::Error> where E: sqlx::Executor<'r,Database = sqlx::Sqlite> {
+    pub fn sqlite_get<'r,E>(_executor: E,_id: Id<User>) -> Result<Self, anyhow::Error> where E: sqlx::Executor<'r,Database = sqlx::Sqlite> {
         todo!()
     }
     // 
@@ -187,16 +159,6 @@ impl User {
 }
 
 
-impl Table for User {
-    const TABLE_NAME: &'static str = "users";
-}
-
-
-impl GetId for User {
-    fn id(&self) -> &Id where Self:Sized {
-        &self.id
-    }
-}
 
 
 // #[tokio::test]
@@ -0,0 +1,75 @@
+// one parsed url for every backend, with the pool settings taken from the query string
+
+use sql::core::models::UserId;
+use sql::drivers::{DatabaseUrl, PoolSettings, connect_tenant_any_pool_url};
+use sql::user::{User, UserDatabasePool};
+
+#[tokio::test]
+async fn sqlite_memory_pool_is_shared() -> Result<(), anyhow::Error> {
+    // every connection in the pool sees the same in memory database
+    let store = connect_tenant_any_pool_url("sqlite::memory:?max_connections=4", true).await?;
+    let id = UserId::new_v7();
+    store.insert_user(&User { id: id.clone() }).await?;
+    let mut reads = tokio::task::JoinSet::new();
+    for _ in 0..4 {
+        let (store, id) = (store.clone(), id.clone());
+        reads.spawn(async move { store.get_user(&id).await });
+    }
+    while let Some(user) = reads.join_next().await {
+        assert!(user??.is_some());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_pool_settings_from_url() -> Result<(), anyhow::Error> {
+    let dir = std::env::temp_dir().join(format!("database_url_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    let raw = format!(
+        "sqlite://{}/a.db?mode=rwc&wal=true&busy_timeout=250&foreign_keys=false",
+        dir.display()
+    );
+    let (url, settings) = DatabaseUrl::parse(&raw)?;
+    assert_eq!(
+        url.as_str(),
+        format!("sqlite://{}/a.db?mode=rwc", dir.display())
+    );
+
+    let settings = settings.or(&PoolSettings {
+        max_connections: Some(2),
+        wal: Some(false),
+        ..Default::default()
+    });
+    assert_eq!(
+        (settings.max_connections, settings.wal),
+        (Some(2), Some(true))
+    );
+    let UserDatabasePool::Sqlite(pool) = UserDatabasePool::connect_with(&url, &settings).await?
+    else {
+        unreachable!()
+    };
+    let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+        .fetch_one(&pool)
+        .await?;
+    let timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+        .fetch_one(&pool)
+        .await?;
+    let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!((mode.as_str(), timeout, foreign_keys), ("wal", 250, 0));
+    pool.close().await;
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn unknown_backends_are_rejected() {
+    let err = DatabaseUrl::parse("oracle://host/db").unwrap_err();
+    assert!(err.to_string().contains("unsupported database url"));
+    if !cfg!(feature = "mysql") {
+        let err = DatabaseUrl::parse("mysql://host/db").unwrap_err();
+        assert!(err.to_string().contains("compiled without mysql"));
+    }
+}
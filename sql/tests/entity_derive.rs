@@ -0,0 +1,39 @@
+// the crud #[derive(Entity)] generates, against an in memory sqlite database
+
+use sql::core::{Entity, GetId, Id, Table};
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, Entity)]
+#[table = "widgets"]
+struct Widget {
+    id: Id<Widget>,
+    name: String,
+    count: i64,
+}
+
+#[tokio::test]
+async fn sqlite_widget_crud() -> Result<(), anyhow::Error> {
+    let pool = sqlx::SqlitePool::connect("sqlite::memory:").await?;
+    sqlx::query("CREATE TABLE widgets (id BLOB NOT NULL PRIMARY KEY, name TEXT NOT NULL, count INTEGER NOT NULL)")
+        .execute(&pool)
+        .await?;
+    assert_eq!(Widget::TABLE_NAME, "widgets");
+
+    let mut w = Widget {
+        id: Id::new_v7(),
+        name: "bolt".into(),
+        count: 3,
+    };
+    w.insert(&pool).await?;
+    assert!(w.insert(&pool).await.is_err());
+    assert_eq!(Widget::get_one(&pool, w.id()).await?, Some(w.clone()));
+
+    w.count = 4;
+    assert!(w.update(&pool).await?);
+    assert_eq!(Widget::get_many(&pool).await?, vec![w.clone()]);
+
+    assert!(Widget::delete(&pool, w.id()).await?);
+    assert!(!Widget::delete(&pool, w.id()).await?);
+    assert!(!w.update(&pool).await?);
+    assert_eq!(Widget::get_one(&pool, w.id()).await?, None);
+    Ok(())
+}
@@ -0,0 +1,79 @@
+// every way of bringing up a tenant database has to end with the same schema.
+// connect_tenant_any_url, UserDatabases::run_migrations and the sql_migrations binary all
+// run sql_migrations::user_multitenant, this keeps them from drifting apart again
+
+use std::path::{Path, PathBuf};
+
+use sql::core::Id;
+use sqlx::{Connection, Row};
+
+fn scratch_db(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{name}_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn url(path: &Path) -> String {
+    format!("sqlite://{}?mode=rwc", path.display())
+}
+
+async fn schema(path: &Path) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let mut c = sqlx::SqliteConnection::connect(&url(path)).await?;
+    let rows = sqlx::query(
+        "SELECT name, sql FROM sqlite_master WHERE name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(&mut c)
+    .await?;
+    let mut schema: Vec<(String, String)> = rows
+        .iter()
+        .map(|r| {
+            (
+                r.get::<String, _>(0),
+                r.get::<Option<String>, _>(1).unwrap_or_default(),
+            )
+        })
+        .collect();
+    let versions: Vec<(i64,)> =
+        sqlx::query_as("SELECT version FROM schema_migrations ORDER BY version")
+            .fetch_all(&mut c)
+            .await?;
+    schema.push((
+        "schema_migrations versions".to_string(),
+        format!("{versions:?}"),
+    ));
+    c.close().await?;
+    Ok(schema)
+}
+
+#[tokio::test]
+async fn sqlite_entry_points_agree() -> Result<(), anyhow::Error> {
+    // connect_tenant_any_url
+    let a = scratch_db("tenant_any_url");
+    sql::drivers::connect_tenant_any_url(url(&a), true).await?;
+
+    // UserDatabases::run_migrations
+    let b = scratch_db("user_databases");
+    let id = Id::new_v7();
+    let mut databases = sql::user::UserDatabases::new().await;
+    databases.connect(url(&b), id.clone()).await?;
+    databases.run_migrations(&id).await?;
+
+    // what the sql_migrations binary does with --tenant
+    let c = scratch_db("migrations_binary");
+    sqlx::any::install_default_drivers();
+    let mut conn = sqlx::AnyConnection::connect(&url(&c)).await?;
+    let mut tx = conn.begin().await?;
+    sql_migrations::user_multitenant::bring_up::<barrel::backend::Sqlite, sqlx::Any>(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    let expected = schema(&a).await?;
+    assert!(expected.iter().any(|(name, _)| name == "entries"));
+    assert_eq!(schema(&b).await?, expected);
+    assert_eq!(schema(&c).await?, expected);
+
+    for path in [a, b, c] {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
@@ -0,0 +1,231 @@
+// the audit log written by the tenant store, on a sqlite file in a scratch directory
+
+use chrono::NaiveDate;
+use sql::audit::{AuditContext, ChangeKind, SYSTEM_ACTOR};
+use sql::core::models::{Account, AccountId, AccountKind, Entry, EntryId, Passkey, PasskeyId, UserId};
+use sql::drivers::connect_tenant_any_url;
+use sql::user::User;
+
+#[tokio::test]
+async fn tenant_writes_are_audited() -> Result<(), anyhow::Error> {
+    let root = std::env::temp_dir().join(format!("tenant_audit_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root)?;
+    let url = format!("sqlite://{}/tenant.db?mode=rwc", root.display());
+    let store = connect_tenant_any_url(&url, true).await?;
+
+    let user = UserId::new_v7();
+    store.insert_user(&User { id: user.clone() }).await?;
+    let mut cash = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Cash".into(),
+        kind: AccountKind::Asset,
+    };
+    store.insert_account(&cash).await?;
+    cash.name = "Wallet".into();
+    assert!(store.update_account(&cash).await?);
+
+    let history = store.history("accounts", &cash.id.to_string()).await?;
+    assert_eq!(history.iter().map(|r| r.kind).collect::<Vec<_>>(), [ChangeKind::Insert, ChangeKind::Update]);
+    assert_eq!(history[1].before, history[0].after);
+    assert!(history[1].after.as_deref().unwrap().contains("Wallet"));
+    assert!(history.iter().all(|r| r.actor == SYSTEM_ACTOR && r.request_id.is_none()));
+
+    // a write that did not happen is not recorded
+    assert!(!store.delete_account(&AccountId::new_v7()).await?);
+
+    // the changes of a transaction carry its actor and request, and go with it on rollback
+    let entry = Entry {
+        id: EntryId::new_v7(),
+        account_id: cash.id.clone(),
+        posted_on: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+        amount: 1250,
+        payee: Some("Grocer".into()),
+        memo: None,
+    };
+    let tx = store.begin_as(AuditContext::new(user.to_string()).request_id("req-1")).await?;
+    tx.insert_entry(&entry).await?;
+    assert_eq!(tx.history("entries", &entry.id.to_string()).await?.len(), 1);
+    tx.rollback().await?;
+    assert!(store.history("entries", &entry.id.to_string()).await?.is_empty());
+
+    let tx = store.begin_as(AuditContext::new(user.to_string()).request_id("req-2")).await?;
+    tx.insert_entry(&entry).await?;
+    assert!(tx.delete_entry(&entry.id).await?);
+    tx.commit().await?;
+    let history = store.history("entries", &entry.id.to_string()).await?;
+    assert_eq!(history.len(), 2);
+    assert!(history.iter().all(|r| r.actor == user.to_string() && r.request_id.as_deref() == Some("req-2")));
+    assert_eq!(history[1].before, history[0].after);
+
+    // revoking keeps the row but is recorded as the passkey going away
+    let passkey = Passkey {
+        id: PasskeyId::new_v7(),
+        credential_id: vec![1, 2, 3],
+        public_key: vec![4, 5, 6],
+        sign_count: 0,
+        nickname: None,
+        last_used_at: None,
+    };
+    store.save_passkey(&user, &passkey).await?;
+    assert!(store.rename_passkey(&passkey.id, Some("laptop")).await?);
+    assert!(store.revoke_passkey(&passkey.id).await?);
+    assert!(!store.revoke_passkey(&passkey.id).await?);
+    let history = store.history("passkeys", &passkey.id.to_string()).await?;
+    assert_eq!(
+        history.iter().map(|r| r.kind).collect::<Vec<_>>(),
+        [ChangeKind::Insert, ChangeKind::Update, ChangeKind::Delete]
+    );
+    assert!(history[2].before.as_deref().unwrap().contains("laptop"));
+    assert!(history.iter().all(|r| !r.after.as_deref().unwrap_or("").contains("040506")));
+
+    store.verify_audit_log().await?;
+
+    // editing a record behind the store's back breaks the chain
+    let raw = sqlx::SqlitePool::connect(&url).await?;
+    sqlx::query("UPDATE audit_log SET actor = 'someone else' WHERE seq = 2").execute(&raw).await?;
+    assert!(store.verify_audit_log().await.is_err());
+
+    raw.close().await;
+    store.close().await;
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
+
+#[tokio::test]
+async fn revert_an_import() -> Result<(), anyhow::Error> {
+    use sql::audit::revert::{revert_change, revert_change_id, revert_request, revert_request_id};
+
+    let root = std::env::temp_dir().join(format!("tenant_revert_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root)?;
+    let url = format!("sqlite://{}/tenant.db?mode=rwc", root.display());
+    let store = connect_tenant_any_url(&url, true).await?;
+    let on = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+
+    let checking = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Checking".into(),
+        kind: AccountKind::Asset,
+    };
+    let mut rent = Entry {
+        id: EntryId::new_v7(),
+        account_id: checking.id.clone(),
+        posted_on: on(1),
+        amount: -90000,
+        payee: Some("Landlord".into()),
+        memo: None,
+    };
+    let coffee = Entry {
+        id: EntryId::new_v7(),
+        posted_on: on(3),
+        amount: -450,
+        payee: Some("Cafe".into()),
+        ..rent.clone()
+    };
+    let import = store.begin_as(AuditContext::new("ofx-import").request_id("import-1")).await?;
+    import.insert_account(&checking).await?;
+    import.insert_entry(&rent).await?;
+    import.insert_entry(&coffee).await?;
+    rent.memo = Some("october".into());
+    import.update_entry(&rent).await?;
+    import.commit().await?;
+
+    // the period holding the rent is closed, so nothing is undone
+    assert!(revert_request(store.as_ref(), "import-1", "me", Some(on(2))).await.is_err());
+    assert_eq!(store.list_entries(&checking.id).await?.len(), 2);
+
+    // a row edited behind the log is not overwritten
+    let raw = sqlx::SqlitePool::connect(&url).await?;
+    sqlx::query("UPDATE entries SET amount = 1").execute(&raw).await?;
+    assert!(revert_request(store.as_ref(), "import-1", "me", None).await.is_err());
+    sqlx::query("UPDATE entries SET amount = -450 WHERE amount = 1 AND payee = 'Cafe'").execute(&raw).await?;
+    sqlx::query("UPDATE entries SET amount = -90000 WHERE amount = 1").execute(&raw).await?;
+    raw.close().await;
+
+    let plan = revert_request(store.as_ref(), "import-1", "me", Some(on(1).pred_opt().unwrap())).await?;
+    assert_eq!(plan.len(), 4);
+    assert!(store.get_account(&checking.id).await?.is_none());
+    assert!(store.get_entry(&rent.id).await?.is_none());
+    let history = store.history("entries", &rent.id.to_string()).await?;
+    assert_eq!(history.last().unwrap().kind, ChangeKind::Delete);
+    assert_eq!(history.last().unwrap().actor, "me");
+    assert_eq!(history.last().unwrap().request_id, Some(revert_request_id("import-1")));
+
+    // undoing the revert puts the import back as it was
+    revert_request(store.as_ref(), &revert_request_id("import-1"), "me", None).await?;
+    assert_eq!(store.get_entry(&rent.id).await?, Some(rent.clone()));
+    assert_eq!(store.list_entries(&checking.id).await?.len(), 2);
+    store.verify_audit_log().await?;
+
+    // single changes go back one at a time, newest first
+    let mut edited = rent.clone();
+    edited.amount = -95000;
+    store.update_entry(&edited).await?;
+    edited.payee = Some("Property manager".into());
+    store.update_entry(&edited).await?;
+    let history = store.history("entries", &rent.id.to_string()).await?;
+    let (amount_seq, payee_seq) = (history[history.len() - 2].seq, history[history.len() - 1].seq);
+    assert!(revert_change(store.as_ref(), amount_seq, "me", None).await.is_err());
+    assert!(revert_change(store.as_ref(), payee_seq, "me", Some(on(1))).await.is_err());
+    assert!(revert_change(store.as_ref(), i64::MAX, "me", None).await.is_err());
+    revert_change(store.as_ref(), payee_seq, "me", None).await?;
+    edited.payee = rent.payee.clone();
+    assert_eq!(store.get_entry(&rent.id).await?, Some(edited));
+    let history = store.history("entries", &rent.id.to_string()).await?;
+    assert_eq!(history.last().unwrap().request_id, Some(revert_change_id(payee_seq)));
+    store.verify_audit_log().await?;
+
+    store.close().await;
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
+
+#[tokio::test]
+async fn sealed_log_catches_rewrites_and_truncation() -> Result<(), anyhow::Error> {
+    use sql::audit::{AuditKey, TenantAudit};
+    use sql::registry::{TenantOptions, UserStoreFactory};
+
+    let root = std::env::temp_dir().join(format!("tenant_audit_sealed_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let factory = UserStoreFactory::with_options(TenantOptions {
+        url_template: Some(format!("sqlite://{}/data/{{user_id}}.db", root.display())),
+        audit: Some(TenantAudit::new(|_| Ok(AuditKey::from_bytes([9_u8; 32])), root.join("heads"))),
+        ..Default::default()
+    });
+    let user = UserId::new_v7();
+    let url = factory.tenant_url(&user)?;
+    let store = factory.provision(&user).await?;
+    store.insert_user(&User { id: user.clone() }).await?;
+    let mut cash = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Cash".into(),
+        kind: AccountKind::Asset,
+    };
+    store.insert_account(&cash).await?;
+    let tx = store.begin().await?;
+    cash.name = "Wallet".into();
+    assert!(tx.update_account(&cash).await?);
+    tx.commit().await?;
+    store.verify_audit_log().await?;
+    assert!(root.join("heads").join(format!("{}.audit-head", user.value)).exists());
+
+    // a store without the key can not vouch for the log
+    let unsealed = connect_tenant_any_url(&url, false).await?;
+    assert!(unsealed.verify_audit_log().await.is_err());
+    unsealed.close().await;
+
+    // what is left after cutting off the newest record is a valid chain, but short of the head
+    let raw = sqlx::SqlitePool::connect(&url).await?;
+    sqlx::query("DELETE FROM audit_log WHERE seq = 3").execute(&raw).await?;
+    let error = store.verify_audit_log().await.expect_err("the log was truncated");
+    assert!(error.to_string().contains("removed from its end"));
+
+    raw.close().await;
+    store.close().await;
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
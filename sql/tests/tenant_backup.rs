@@ -0,0 +1,187 @@
+// backups of a tenant while it is open, and restoring them
+#![cfg(feature = "backup")]
+
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use sql::backup::{ArchiveKind, bundle_from_bytes, bundle_to_bytes, export_tenant, import_tenant, open_archive};
+use sql::audit::{AuditKey, TenantAudit};
+use sql::core::models::{Account, AccountId, AccountKind, Entry, EntryId, UserId};
+use sql::drivers::{UserTenantStore, connect_tenant_any_pool_url};
+use sql::encryption::DatabaseKey;
+use sql::registry::{TenantOptions, UserStoreFactory};
+use sql::user::User;
+
+async fn fill(store: &dyn UserTenantStore, id: &UserId) -> Result<Entry, anyhow::Error> {
+    let assets = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Current Assets".into(),
+        kind: AccountKind::Asset,
+    };
+    // sorts ahead of its parent, import has to put the parent in first
+    let cash = Account {
+        id: AccountId::new_v7(),
+        parent_id: Some(assets.id.clone()),
+        name: "Cash".into(),
+        kind: AccountKind::Asset,
+    };
+    let entry = Entry {
+        id: EntryId::new_v7(),
+        account_id: cash.id.clone(),
+        posted_on: NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+        amount: 1250,
+        payee: Some("Grocer".into()),
+        memo: None,
+    };
+    store.insert_user(&User { id: id.clone() }).await?;
+    store.insert_account(&assets).await?;
+    store.insert_account(&cash).await?;
+    store.insert_entry(&entry).await?;
+    Ok(entry)
+}
+
+#[tokio::test]
+async fn bundle_round_trip() -> Result<(), anyhow::Error> {
+    let source = connect_tenant_any_pool_url("sqlite::memory:", true).await?;
+    let id = UserId::new_v7();
+    fill(source.as_ref(), &id).await?;
+    let bundle = export_tenant(source.as_ref(), &id).await?;
+
+    let key = DatabaseKey::from_bytes([9; 32]);
+    let sealed = bundle_to_bytes(&bundle, Some(&key))?;
+    assert!(bundle_from_bytes(&sealed, None).is_err());
+    assert!(bundle_from_bytes(&sealed, Some(&DatabaseKey::from_bytes([8; 32]))).is_err());
+    let read = bundle_from_bytes(&sealed, Some(&key))?;
+    assert_eq!(read, bundle);
+    // the header says what the archive holds, bare json is not taken for a bundle
+    assert_eq!(open_archive(&bundle_to_bytes(&bundle, None)?, None)?.0, ArchiveKind::Bundle);
+    assert!(bundle_from_bytes(&serde_json::to_vec(&bundle)?, None).is_err());
+
+    let target = connect_tenant_any_pool_url("sqlite::memory:", true).await?;
+    let mut newer = read.clone();
+    newer.schema_version += 1;
+    assert!(import_tenant(target.as_ref(), &newer).await.is_err());
+    // an audit log that does not verify is refused with the rest of the bundle
+    let mut tampered = read.clone();
+    tampered.audit[1].actor = "someone else".into();
+    assert!(import_tenant(target.as_ref(), &tampered).await.is_err());
+    assert!(target.get_user(&id).await?.is_none());
+    import_tenant(target.as_ref(), &read).await?;
+    // the log comes across as it was, and the import itself is not logged
+    assert_eq!(bundle.audit.len(), 4);
+    target.verify_audit_log().await?;
+    let mut copied = export_tenant(target.as_ref(), &id).await?;
+    copied.exported_at = bundle.exported_at;
+    assert_eq!(copied, bundle);
+    // only into an empty tenant
+    assert!(import_tenant(target.as_ref(), &read).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_backup_and_restore() -> Result<(), anyhow::Error> {
+    let root = std::env::temp_dir().join(format!("tenant_backups_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let factory = Arc::new(UserStoreFactory::with_options(TenantOptions {
+        url_template: Some(format!("sqlite://{}/data/{{user_id}}.db", root.display())),
+        audit: Some(TenantAudit::new(|_| Ok(AuditKey::from_bytes([4; 32])), root.join("heads"))),
+        ..Default::default()
+    }));
+    let id = UserId::new_v7();
+    let store = factory.provision(&id).await?;
+    let entry = fill(store.as_ref(), &id).await?;
+
+    let key = DatabaseKey::from_bytes([3; 32]);
+    let archive = factory
+        .backup(&id, &root.join("backups"), Some(&key))
+        .await?;
+    assert!(sql::backup::is_sealed(&std::fs::read(&archive)?));
+    let plain = factory.backup(&id, &root.join("backups"), None).await?;
+    assert_eq!(open_archive(&std::fs::read(&plain)?, None)?.0, ArchiveKind::SqliteFile);
+    // the store stays usable while and after it is backed up
+    assert!(store.delete_entry(&entry.id).await?);
+
+    assert!(factory.restore(&id, &archive, None).await.is_err());
+    assert!(
+        factory
+            .store(&id)
+            .await?
+            .get_entry(&entry.id)
+            .await?
+            .is_none()
+    );
+    factory.restore(&id, &archive, Some(&key)).await?;
+    assert_eq!(
+        factory.store(&id).await?.get_entry(&entry.id).await?,
+        Some(entry)
+    );
+    // the log is shorter than before the restore, its head went back with it
+    factory.store(&id).await?.verify_audit_log().await?;
+    // another user's backup does not go over this tenant
+    let other = UserId::new_v7();
+    let err = factory.restore(&other, &archive, Some(&key)).await.expect_err("the backup is not theirs");
+    assert!(err.to_string().contains("belongs to user"));
+    assert!(!root.join(format!("data/{}.db", other.value)).exists());
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn bundle_restore_replaces_the_tenant_after_importing() -> Result<(), anyhow::Error> {
+    use sql::directory::{TenantStatus, connect_directory_any_url};
+
+    let root = std::env::temp_dir().join(format!("tenant_bundle_restore_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root)?;
+    let options = TenantOptions {
+        url_template: Some(format!("sqlite://{}/data/{{user_id}}.db", root.display())),
+        audit: Some(TenantAudit::new(|_| Ok(AuditKey::from_bytes([4; 32])), root.join("heads"))),
+        ..Default::default()
+    };
+    let primary = format!("sqlite://{}/primary.db", root.display());
+    let factory = UserStoreFactory::with_directory(options.clone(), connect_directory_any_url(&primary, true).await?);
+    let id = UserId::new_v7();
+    let store = factory.provision(&id).await?;
+    let entry = fill(store.as_ref(), &id).await?;
+    let bundle = export_tenant(store.as_ref(), &id).await?;
+    let archive = root.join("tenant.json");
+    std::fs::write(&archive, bundle_to_bytes(&bundle, None)?)?;
+    assert!(store.delete_entry(&entry.id).await?);
+    drop(store);
+    let data_files = || std::fs::read_dir(root.join("data")).map(|d| d.count());
+
+    // an import that fails leaves the tenant as it was, and nothing next to it
+    let mut broken = bundle.clone();
+    broken.accounts[0].parent_id = Some(AccountId::new_v7());
+    let broken_archive = root.join("broken.json");
+    std::fs::write(&broken_archive, bundle_to_bytes(&broken, None)?)?;
+    let before = factory.directory().unwrap().lookup(&id).await?.unwrap();
+    assert!(factory.restore(&id, &broken_archive, None).await.is_err());
+    assert_eq!(factory.directory().unwrap().lookup(&id).await?.unwrap().url, before.url);
+    assert_eq!(data_files()?, 1);
+    assert!(factory.store(&id).await?.get_user(&id).await?.is_some());
+
+    factory.restore(&id, &archive, None).await?;
+    let after = factory.directory().unwrap().lookup(&id).await?.unwrap();
+    assert_ne!(after.url, before.url);
+    assert_eq!(after.status, TenantStatus::Active);
+    assert_eq!(data_files()?, 1);
+    assert_eq!(factory.store(&id).await?.get_entry(&entry.id).await?, Some(entry.clone()));
+    factory.store(&id).await?.verify_audit_log().await?;
+
+    // without a directory the tenant stays at the template url
+    let plain = UserStoreFactory::with_options(options);
+    let other = UserId::new_v7();
+    let mut moved = bundle.clone();
+    moved.user_id = other.clone();
+    std::fs::write(&archive, bundle_to_bytes(&moved, None)?)?;
+    plain.restore(&other, &archive, None).await?;
+    assert!(root.join("data").join(format!("{}.db", other.value)).exists());
+    assert_eq!(data_files()?, 2);
+    assert_eq!(plain.store(&other).await?.get_entry(&entry.id).await?, Some(entry));
+
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
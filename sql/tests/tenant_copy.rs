@@ -0,0 +1,82 @@
+// copying a tenant into another database, as a user moving off sqlite would.
+// the postgres and mysql targets run when TEST_POSTGRES_URL / TEST_MYSQL_URL are set, the copy
+// goes into a new database next to the one named there
+
+use sql::core::models::{Account, AccountId, AccountKind, UserId};
+use sql::drivers::{DatabaseUrl, connect_tenant_any_pool_url, copy_tenant_url};
+use sql::user::User;
+
+// the tests run side by side, `name` keeps their scratch directories apart
+async fn copy_from_sqlite(name: &str, target_url: &str) -> Result<(), anyhow::Error> {
+    let root = std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let source_url = format!("sqlite://{}/source.db", root.display());
+    sql::drivers::provision_tenant_url(&source_url).await?;
+
+    let source = connect_tenant_any_pool_url(&source_url, true).await?;
+    let id = UserId::new_v7();
+    source.insert_user(&User { id: id.clone() }).await?;
+    // the sub-account sorts ahead of its parent, copy has to put the parent in first
+    let household_id = AccountId::new_v7();
+    let account = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Checking".into(),
+        kind: AccountKind::Asset,
+    };
+    let household = Account {
+        id: household_id,
+        parent_id: Some(account.id.clone()),
+        name: "Household".into(),
+        kind: AccountKind::Asset,
+    };
+    source.insert_account(&account).await?;
+    source.insert_account(&household).await?;
+
+    let (from, to) = (
+        source_url.parse::<DatabaseUrl>()?,
+        target_url.parse::<DatabaseUrl>()?,
+    );
+    let copied = copy_tenant_url(&from, &to).await?;
+    let accounts = copied.iter().find(|t| t.table == "accounts").unwrap();
+    assert_eq!(accounts.rows, 2);
+
+    let target = connect_tenant_any_pool_url(target_url, false).await?;
+    assert!(target.get_user(&id).await?.is_some());
+    assert_eq!(target.list_accounts().await?, vec![account, household]);
+    target.verify_audit_log().await?;
+    // only into an empty tenant
+    assert!(copy_tenant_url(&from, &to).await.is_err());
+
+    source.close().await;
+    target.close().await;
+    sql::drivers::drop_tenant_url(target_url).await?;
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_tenant_copies_with_checksums() -> Result<(), anyhow::Error> {
+    let root = std::env::temp_dir().join(format!("tenant_copy_target_{}", std::process::id()));
+    copy_from_sqlite("tenant_copy", &format!("sqlite://{}/target.db", root.display())).await?;
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sqlite_tenant_copies_to_postgres() -> Result<(), anyhow::Error> {
+    let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+        eprintln!("no postgres to test against, skipping");
+        return Ok(());
+    };
+    copy_from_sqlite("tenant_copy_postgres", &sql::drivers::sibling_url(&url, "copy")?).await
+}
+
+#[tokio::test]
+async fn sqlite_tenant_copies_to_mysql() -> Result<(), anyhow::Error> {
+    let Ok(url) = std::env::var("TEST_MYSQL_URL") else {
+        eprintln!("no mysql to test against, skipping");
+        return Ok(());
+    };
+    copy_from_sqlite("tenant_copy_mysql", &sql::drivers::sibling_url(&url, "copy")?).await
+}
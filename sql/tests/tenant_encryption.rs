@@ -0,0 +1,94 @@
+// sqlite tenants encrypted with sqlcipher, under a key per user
+
+use sql::drivers::{DatabaseUrl, PoolSettings, connect_tenant_with};
+
+#[cfg(feature = "sqlcipher")]
+fn keys(master: u8) -> sql::encryption::TenantEncryption {
+    sql::encryption::TenantEncryption::from_master_key(zeroize::Zeroizing::new([master; 32]))
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+#[tokio::test]
+async fn plain_sqlite_refuses_keys() -> Result<(), anyhow::Error> {
+    use sql::encryption::DatabaseKey;
+    let (url, settings) = DatabaseUrl::parse("sqlite::memory:")?;
+    let settings = PoolSettings {
+        key: Some(DatabaseKey::from_bytes([7; 32])),
+        ..settings
+    };
+    let err = connect_tenant_with(&url, &settings, true)
+        .await
+        .err()
+        .expect("plain sqlite can not encrypt");
+    assert!(err.to_string().contains("sqlcipher"));
+    Ok(())
+}
+
+#[cfg(feature = "sqlcipher")]
+#[tokio::test]
+async fn encrypted_tenant_rekey() -> Result<(), anyhow::Error> {
+    use sql::core::models::UserId;
+    use sql::registry::{TenantOptions, UserStoreFactory};
+    use sql::user::User;
+    use std::sync::Arc;
+
+    let root = std::env::temp_dir().join(format!("encrypted_tenants_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let options = |master| TenantOptions {
+        url_template: Some(format!("sqlite://{}/{{user_id}}.db", root.display())),
+        encryption: Some(keys(master)),
+        ..Default::default()
+    };
+    let id = UserId::new_v7();
+    let file = root.join(format!("{}.db", id.value));
+
+    let factory = UserStoreFactory::with_options(options(1));
+    factory
+        .provision(&id)
+        .await?
+        .insert_user(&User { id: id.clone() })
+        .await?;
+    factory.evict(&id);
+    assert!(!std::fs::read(&file)?.starts_with(b"SQLite format 3"));
+
+    // the wrong key, or none at all, does not open it
+    let url = factory.tenant_url(&id)?;
+    let (parsed, settings) = DatabaseUrl::parse(&url)?;
+    assert!(
+        sql::drivers::connect_tenant_any_pool_url(&url, false)
+            .await?
+            .get_user(&id)
+            .await
+            .is_err()
+    );
+    let wrong = PoolSettings {
+        key: Some(keys(2).key_for(&id, 0)?),
+        ..settings
+    };
+    assert!(connect_tenant_with(&parsed, &wrong, false).await.is_err());
+    assert_eq!(
+        keys(1).key_for(&id, 0)?.as_bytes(),
+        &*vault::crypto::derive_tenant_database_key(&[1; 32], id.as_bytes(), 0)?
+    );
+
+    assert_eq!(factory.rekey(&id).await?, 1);
+    let old = PoolSettings { key: Some(keys(1).key_for(&id, 0)?), ..Default::default() };
+    assert!(connect_tenant_with(&parsed, &old, false).await.is_err());
+    assert!(factory.store(&id).await?.get_user(&id).await?.is_some());
+    factory.evict(&id);
+    // the version is on disk, so a restarted factory derives the new key
+    let restarted = Arc::new(UserStoreFactory::with_options(options(1)));
+    assert!(restarted.store(&id).await?.get_user(&id).await?.is_some());
+    restarted.evict(&id);
+
+    // a rekey cut short before sqlcipher ran is refused on open and finished by rekeying again
+    let mut pending = file.clone().into_os_string();
+    pending.push("-key-next");
+    std::fs::write(&pending, "2")?;
+    assert!(restarted.store(&id).await.is_err());
+    assert_eq!(restarted.rekey(&id).await?, 2);
+    assert!(restarted.store(&id).await?.get_user(&id).await?.is_some());
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
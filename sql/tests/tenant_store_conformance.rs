@@ -0,0 +1,228 @@
+// every UserTenantStore backend has to pass the same suite.
+// sqlite runs in memory. postgres and mysql run when TEST_POSTGRES_URL / TEST_MYSQL_URL are set
+
+use sql::core::models::{
+    Account, AccountId, AccountKind, Entry, EntryId, Passkey, PasskeyId, PasskeyUse, UserId,
+};
+use sql::drivers::{TenantOperations, UserTenantStore};
+use sql::user::User;
+
+async fn conformance(store: &dyn TenantOperations) -> Result<(), anyhow::Error> {
+    // users
+    let user_id = UserId::new_v7();
+    store.insert_user(&User { id: user_id.clone() }).await?;
+    let user = store.get_user(&user_id).await?.expect("inserted user");
+    assert_eq!(user.id, user_id);
+    assert!(store.get_user(&UserId::new_v7()).await?.is_none());
+    // the id is the primary key
+    assert!(store.insert_user(&User { id: user_id.clone() }).await.is_err());
+
+    // passkeys
+    // credential ids are unique per store and conformance runs more than once against one
+    let credential = |n: u8| [user_id.as_bytes().as_slice(), &[n]].concat();
+    let mut passkey = Passkey {
+        id: PasskeyId::new_v7(),
+        credential_id: credential(1),
+        public_key: vec![4, 5, 6],
+        sign_count: 0,
+        nickname: None,
+        last_used_at: None,
+    };
+    let laptop = Passkey {
+        id: PasskeyId::new_v7(),
+        credential_id: credential(2),
+        sign_count: 10,
+        nickname: Some("laptop".to_string()),
+        ..passkey.clone()
+    };
+    store.save_passkey(&user_id, &passkey).await?;
+    store.save_passkey(&user_id, &laptop).await?;
+    assert_eq!(store.get_passkeys(&user_id).await?, vec![passkey.clone(), laptop.clone()]);
+    assert_eq!(store.find_passkey(&credential(2)).await?, Some(laptop.clone()));
+    assert!(store.find_passkey(&credential(0)).await?.is_none());
+
+    // authenticators without a counter always send 0, the rest have to move forward
+    assert_eq!(store.use_passkey(&credential(1), 0).await?, PasskeyUse::Accepted);
+    assert_eq!(store.use_passkey(&credential(2), 11).await?, PasskeyUse::Accepted);
+    assert_eq!(
+        store.use_passkey(&credential(2), 11).await?,
+        PasskeyUse::CounterRegressed { stored: 11, presented: 11 }
+    );
+    assert_eq!(store.use_passkey(&credential(0), 1).await?, PasskeyUse::Unknown);
+    let used = store.find_passkey(&credential(2)).await?.expect("registered passkey");
+    assert_eq!(used.sign_count, 11);
+    assert!(used.last_used_at.is_some());
+    // counters past i32::MAX are kept whole
+    assert_eq!(store.use_passkey(&credential(2), u32::MAX).await?, PasskeyUse::Accepted);
+    assert_eq!(store.find_passkey(&credential(2)).await?.map(|p| p.sign_count), Some(u32::MAX));
+
+    assert!(store.rename_passkey(&passkey.id, Some("phone")).await?);
+    passkey.nickname = Some("phone".to_string());
+    assert!(store.revoke_passkey(&laptop.id).await?);
+    assert!(!store.revoke_passkey(&laptop.id).await?);
+    assert_eq!(store.use_passkey(&credential(2), 12).await?, PasskeyUse::Revoked);
+    assert!(store.find_passkey(&credential(2)).await?.is_none());
+    let remaining = store.get_passkeys(&user_id).await?;
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].nickname, passkey.nickname);
+
+    // accounts
+    let checking = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Checking".to_string(),
+        kind: AccountKind::Asset,
+    };
+    let mut repairs = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Repairs".to_string(),
+        kind: AccountKind::Expense,
+    };
+    store.insert_account(&checking).await?;
+    store.insert_account(&repairs).await?;
+    repairs.parent_id = Some(checking.id.clone());
+    repairs.name = "Home Repairs".to_string();
+    assert!(store.update_account(&repairs).await?);
+    assert_eq!(store.get_account(&repairs.id).await?, Some(repairs.clone()));
+    assert_eq!(
+        store.list_accounts().await?,
+        vec![checking.clone(), repairs.clone()]
+    );
+
+    // entries
+    let day = |d| chrono::NaiveDate::from_ymd_opt(2026, 4, d).unwrap();
+    let mut lumber = Entry {
+        id: EntryId::new_v7(),
+        account_id: checking.id.clone(),
+        posted_on: day(12),
+        amount: -4_250,
+        payee: Some("Home Depot".to_string()),
+        memo: None,
+    };
+    let paycheck = Entry {
+        id: EntryId::new_v7(),
+        account_id: checking.id.clone(),
+        posted_on: day(1),
+        amount: 250_000,
+        payee: None,
+        memo: Some("april".to_string()),
+    };
+    store.insert_entry(&lumber).await?;
+    store.insert_entry(&paycheck).await?;
+    // an entry is posted to an account that exists
+    let stray = Entry {
+        id: EntryId::new_v7(),
+        account_id: AccountId::new_v7(),
+        ..paycheck.clone()
+    };
+    assert!(store.insert_entry(&stray).await.is_err());
+    lumber.memo = Some("deck boards".to_string());
+    assert!(store.update_entry(&lumber).await?);
+    assert_eq!(store.get_entry(&lumber.id).await?, Some(lumber.clone()));
+    assert_eq!(
+        store.list_entries(&checking.id).await?,
+        vec![paycheck.clone(), lumber.clone()]
+    );
+
+    // an account with entries cannot be deleted
+    assert!(!store.delete_account(&checking.id).await?);
+    assert!(store.delete_entry(&lumber.id).await?);
+    assert!(!store.delete_entry(&lumber.id).await?);
+    assert!(store.delete_entry(&paycheck.id).await?);
+    // nor one with sub-accounts
+    assert!(!store.delete_account(&checking.id).await?);
+    assert!(store.delete_account(&repairs.id).await?);
+    assert!(store.delete_account(&checking.id).await?);
+    assert!(store.get_account(&checking.id).await?.is_none());
+    assert!(store.list_accounts().await?.is_empty());
+
+    assert!(store.delete_user(&user_id).await?);
+    assert!(!store.delete_user(&user_id).await?);
+    Ok(())
+}
+
+// the same suite through begin(), then commit and rollback have to do what they say
+async fn transaction_conformance(store: &dyn UserTenantStore) -> Result<(), anyhow::Error> {
+    let tx = store.begin().await?;
+    conformance(tx.as_ref()).await?;
+    tx.commit().await?;
+
+    let account = Account {
+        id: AccountId::new_v7(),
+        parent_id: None,
+        name: "Savings".to_string(),
+        kind: AccountKind::Asset,
+    };
+    let tx = store.begin().await?;
+    tx.insert_account(&account).await?;
+    assert_eq!(tx.get_account(&account.id).await?, Some(account.clone()));
+    tx.rollback().await?;
+    assert!(store.get_account(&account.id).await?.is_none());
+
+    // dropping without commit is a rollback too
+    {
+        let tx = store.begin().await?;
+        tx.insert_account(&account).await?;
+    }
+    assert!(store.get_account(&account.id).await?.is_none());
+
+    let tx = store.begin().await?;
+    tx.insert_account(&account).await?;
+    tx.commit().await?;
+    assert_eq!(store.get_account(&account.id).await?, Some(account.clone()));
+    assert!(store.delete_account(&account.id).await?);
+    Ok(())
+}
+
+async fn full_conformance(store: &dyn UserTenantStore) -> Result<(), anyhow::Error> {
+    conformance(store).await?;
+    duplicate_credential(store).await?;
+    transaction_conformance(store).await
+}
+
+// outside of conformance, a failed statement aborts the rest of a postgres transaction
+async fn duplicate_credential(store: &dyn UserTenantStore) -> Result<(), anyhow::Error> {
+    let user_id = UserId::new_v7();
+    let passkey = Passkey {
+        id: PasskeyId::new_v7(),
+        credential_id: user_id.as_bytes().to_vec(),
+        public_key: vec![1],
+        sign_count: 0,
+        nickname: None,
+        last_used_at: None,
+    };
+    store.save_passkey(&user_id, &passkey).await?;
+    let again = Passkey {
+        id: PasskeyId::new_v7(),
+        ..passkey
+    };
+    assert!(store.save_passkey(&user_id, &again).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenant_store_conformance_sqlite_memory() -> Result<(), anyhow::Error> {
+    let store = sql::drivers::connect_tenant_any_url("sqlite::memory:", true).await?;
+    full_conformance(store.as_ref()).await
+}
+
+#[tokio::test]
+async fn tenant_store_conformance_postgres() -> Result<(), anyhow::Error> {
+    let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+        eprintln!("no postgres to test against, skipping");
+        return Ok(());
+    };
+    let store = sql::drivers::connect_tenant_any_url(url, true).await?;
+    full_conformance(store.as_ref()).await
+}
+
+#[tokio::test]
+async fn tenant_store_conformance_mysql() -> Result<(), anyhow::Error> {
+    let Ok(url) = std::env::var("TEST_MYSQL_URL") else {
+        eprintln!("no mysql to test against, skipping");
+        return Ok(());
+    };
+    let store = sql::drivers::connect_tenant_any_url(url, true).await?;
+    full_conformance(store.as_ref()).await
+}
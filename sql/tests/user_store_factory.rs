@@ -0,0 +1,96 @@
+// tenant lifecycle through UserStoreFactory, on sqlite files in a scratch directory
+
+use std::{sync::Arc, time::Duration};
+
+use sql::core::models::UserId;
+use sql::registry::{TenantOptions, UserStoreFactory};
+use sql::user::User;
+
+#[tokio::test]
+async fn sqlite_tenant_lifecycle() -> Result<(), anyhow::Error> {
+    let root = std::env::temp_dir().join(format!("tenants_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let factory = Arc::new(UserStoreFactory::with_options(TenantOptions {
+        url_template: Some(format!("sqlite://{}/data/{{user_id}}.db", root.display())),
+        idle_timeout: Some(Duration::from_secs(60)),
+        max_open_tenants: Some(2),
+        archive_directory: Some(root.join("archive")),
+        ..Default::default()
+    }));
+    let [a, b, c] = [(); 3].map(|_| UserId::new_v7());
+    let file = |id: &UserId| root.join("data").join(format!("{}.db", id.value));
+
+    // store() does not create databases, provision() does
+    assert!(factory.store(&a).await.is_err());
+    let store = factory.provision(&a).await?;
+    assert!(file(&a).exists());
+    store.insert_user(&User { id: a.clone() }).await?;
+    assert!(Arc::ptr_eq(&store, &factory.store(&a).await?));
+
+    // the least recently used tenant makes room
+    factory.provision(&b).await?;
+    factory.get(&a);
+    factory.provision(&c).await?;
+    assert_eq!(factory.open_tenants(), 2);
+    assert!(factory.get(&b).is_none());
+    assert!(factory.get(&a).is_some());
+
+    // reopening an evicted tenant finds its data
+    assert!(factory.evict(&a));
+    assert!(factory.store(&a).await?.get_user(&a).await?.is_some());
+
+    // nothing has been idle for a minute
+    assert!(factory.evict_idle().is_empty());
+
+    let archived = factory.archive(&a).await?;
+    assert!(archived.exists());
+    assert!(!file(&a).exists());
+
+    factory.deprovision(&c).await?;
+    assert!(!file(&c).exists());
+    assert!(factory.get(&c).is_none());
+
+    drop(store);
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenants_resolve_through_the_directory() -> Result<(), anyhow::Error> {
+    use sql::directory::{TenantStatus, connect_directory_any_url};
+
+    let root = std::env::temp_dir().join(format!("directory_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root)?;
+    let primary = format!("sqlite://{}/primary.db", root.display());
+    let options = TenantOptions {
+        url_template: Some(format!("sqlite://{}/{{user_id}}.db", root.display())),
+        archive_directory: Some(root.join("archive")),
+        ..Default::default()
+    };
+    let a = UserId::new_v7();
+
+    let factory = UserStoreFactory::with_directory(options.clone(), connect_directory_any_url(&primary, true).await?);
+    assert!(factory.store(&a).await.is_err());
+    factory.provision(&a).await?.insert_user(&User { id: a.clone() }).await?;
+    let record = factory.directory().unwrap().lookup(&a).await?.expect("registered");
+    assert_eq!(record.backend, "sqlite");
+    assert_eq!(record.status, TenantStatus::Active);
+    assert!(record.schema_version > 0);
+
+    // a restarted server without a url template still finds the tenant
+    let restarted = UserStoreFactory::with_directory(
+        TenantOptions { url_template: None, ..options.clone() },
+        connect_directory_any_url(&primary, true).await?,
+    );
+    assert!(restarted.store(&a).await?.get_user(&a).await?.is_some());
+
+    let archived = restarted.archive(&a).await?;
+    let record = restarted.directory().unwrap().lookup(&a).await?.unwrap();
+    assert_eq!(record.status, TenantStatus::Archived);
+    assert!(record.url.ends_with(&*archived.to_string_lossy()));
+    assert!(restarted.store(&a).await.is_err());
+
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
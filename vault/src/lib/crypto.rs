@@ -57,6 +57,37 @@ pub(crate) fn gen_hkdf_intermediate_from_256_bits_entropy(
     Ok(key)
 }
 
+// fixed, the master key is already uniformly random. changing it changes every tenant key
+const TENANT_DATABASE_KEY_SALT: &[u8] = b"VAULT | tenant database";
+
+/// the key a tenant database is encrypted with, from the vault master key with the user id and
+/// key version as info. the same master key, user and version always give the same key, so tenant
+/// keys are never stored. a rekey moves the tenant to the next version
+pub fn derive_tenant_database_key(
+    master_key: &[u8; 32],
+    user_id: &[u8],
+    key_version: u32,
+) -> Result<zeroize::Zeroizing<[u8; 32]>, anyhow::Error> {
+    let info = [user_id, &key_version.to_be_bytes()].concat();
+    let (out, _) = hdkf(master_key, TENANT_DATABASE_KEY_SALT, &info)
+        .context("While deriving a tenant database key")?;
+    Ok(zeroize::Zeroizing::new(out))
+}
+
+// a different salt from the database keys, so an audit key never equals a database key
+const TENANT_AUDIT_KEY_SALT: &[u8] = b"VAULT | tenant audit log";
+
+/// the key a tenant's audit log records are MACed with, from the vault master key with the user
+/// id as info. it does not change on a rekey, the log keeps verifying across database keys
+pub fn derive_tenant_audit_key(
+    master_key: &[u8; 32],
+    user_id: &[u8],
+) -> Result<zeroize::Zeroizing<[u8; 32]>, anyhow::Error> {
+    let (out, _) = hdkf(master_key, TENANT_AUDIT_KEY_SALT, user_id)
+        .context("While deriving a tenant audit key")?;
+    Ok(zeroize::Zeroizing::new(out))
+}
+
 #[test]
 pub fn vault_crypto_test_intermediate_key() {
     let mut entropy = [0_u8; 32];
@@ -67,3 +98,19 @@ pub fn vault_crypto_test_intermediate_key() {
         gen_hkdf_intermediate_from_256_bits_entropy(&entropy, "VAULT", "0.1.0", "SECRETS").unwrap();
     // dbg!(key);
 }
+
+#[test]
+pub fn vault_crypto_test_tenant_database_key() {
+    let mut master = [0_u8; 32];
+    aws_lc_rs::rand::fill(&mut master).unwrap();
+    let a = derive_tenant_database_key(&master, b"user a", 0).unwrap();
+    assert_eq!(a, derive_tenant_database_key(&master, b"user a", 0).unwrap());
+    assert_ne!(a, derive_tenant_database_key(&master, b"user b", 0).unwrap());
+    assert_ne!(a, derive_tenant_database_key(&master, b"user a", 1).unwrap());
+    master[0] ^= 1;
+    assert_ne!(a, derive_tenant_database_key(&master, b"user a", 0).unwrap());
+    let audit = derive_tenant_audit_key(&master, b"user a").unwrap();
+    assert_eq!(audit, derive_tenant_audit_key(&master, b"user a").unwrap());
+    assert_ne!(audit, derive_tenant_audit_key(&master, b"user b").unwrap());
+    assert_ne!(audit, derive_tenant_database_key(&master, b"user a", 0).unwrap());
+}